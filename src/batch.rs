@@ -0,0 +1,224 @@
+use crate::{input::Input, output::OutputInfo, template::SharpTemplate, Sharp, SharpError};
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/** Where the output of a batch item is written. */
+#[derive(Debug, Clone)]
+pub enum BatchTarget {
+    /** Write to a file, the format is inferred from the extension unless set by the pipeline. */
+    File(PathBuf),
+    /** Keep the output in memory, returned in {@link BatchResult}. */
+    Buffer,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    pub input: Input,
+    pub target: BatchTarget,
+}
+
+impl BatchItem {
+    pub fn file<P: Into<PathBuf>>(input: Input, file_out: P) -> Self {
+        Self {
+            input,
+            target: BatchTarget::File(file_out.into()),
+        }
+    }
+
+    pub fn buffer(input: Input) -> Self {
+        Self {
+            input,
+            target: BatchTarget::Buffer,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /**
+     * Number of items processed at the same time.
+     * (optional, default is the number of CPU cores divided by the libvips thread pool size, see {@link Sharp::set_concurrency})
+     */
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct BatchResult {
+    /** Position of the item in the input iterator. */
+    pub index: usize,
+    pub target: BatchTarget,
+    /** Output info, or the error that stopped this item. */
+    pub result: Result<OutputInfo, SharpError>,
+    /** Output data when the target is `BatchTarget::Buffer`, otherwise empty. */
+    pub data: Vec<u8>,
+    /** Time spent building and running the pipeline for this item. */
+    pub elapsed: Duration,
+}
+
+/**
+ * Results of a running batch, yielded in order of completion as each item finishes.
+ *
+ * Dropping the batch stops workers from starting new items.
+ */
+pub struct Batch {
+    receiver: Receiver<BatchResult>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Iterator for Batch {
+    type Item = BatchResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/**
+ * Run a template over many inputs with bounded concurrency.
+ *
+ * A failing item is reported in its own result and does not stop the rest of the batch.
+ * The libvips operation cache, see {@link Sharp::set_cache}, is shared by all items.
+ *
+ * @example
+ * let template = SharpTemplate::new(|sharp| sharp.resize(300, 200)?.webp(None))?;
+ * let items = files.into_iter().map(|file| BatchItem::file(Input::path(&file), file.with_extension("webp")));
+ * for result in sharp::batch::run(template, items, None) {
+ *   if let Err(e) = result.result {
+ *     eprintln!("item {} failed after {:?}: {}", result.index, result.elapsed, e);
+ *   }
+ * }
+ */
+pub fn run<I>(template: SharpTemplate, items: I, options: Option<BatchOptions>) -> Batch
+where
+    I: IntoIterator<Item = BatchItem>,
+    I::IntoIter: Send + 'static,
+{
+    run_with(items, options, move |input| template.apply(input))
+}
+
+/**
+ * Run a pipeline built by `build` for each input, with bounded concurrency.
+ *
+ * @example
+ * let template = SharpTemplate::new(|sharp| sharp.resize(300, 200))?;
+ * let items = files.into_iter().map(|file| BatchItem::buffer(Input::path(file)));
+ * let results: Vec<BatchResult> = sharp::batch::run_with(items, None, move |input| {
+ *   template.apply(input)?.webp(None)
+ * }).collect();
+ */
+pub fn run_with<I, F>(items: I, options: Option<BatchOptions>, build: F) -> Batch
+where
+    I: IntoIterator<Item = BatchItem>,
+    I::IntoIter: Send + 'static,
+    F: Fn(Input) -> Result<Sharp, SharpError> + Send + Sync + 'static,
+{
+    let concurrency =
+        options.and_then(|options| options.concurrency).unwrap_or_else(default_concurrency).max(1);
+    let items = Arc::new(Mutex::new(items.into_iter().enumerate()));
+    let build = Arc::new(build);
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = channel();
+
+    for index in 0..concurrency {
+        let items = items.clone();
+        let build = build.clone();
+        let stop = stop.clone();
+        let sender = sender.clone();
+        thread::Builder::new()
+            .name(format!("sharp-batch-{}", index))
+            .spawn(move || work(items, build, stop, sender))
+            .expect("Failed to spawn worker thread");
+    }
+
+    Batch {
+        receiver,
+        stop,
+    }
+}
+
+/*
+  Divide the CPU cores between concurrent items, each of which uses the libvips thread pool
+*/
+pub(crate) fn default_concurrency() -> usize {
+    let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    let threads = Sharp::get_concurrency().max(1) as usize;
+    (cores / threads).max(1)
+}
+
+type SharedItems<T> = Arc<Mutex<std::iter::Enumerate<T>>>;
+
+fn work<T, F>(
+    items: SharedItems<T>,
+    build: Arc<F>,
+    stop: Arc<AtomicBool>,
+    sender: Sender<BatchResult>,
+) where
+    T: Iterator<Item = BatchItem>,
+    F: Fn(Input) -> Result<Sharp, SharpError>,
+{
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let next = match items.lock() {
+            Ok(mut items) => items.next(),
+            Err(_) => return,
+        };
+        let (index, item) = match next {
+            Some(next) => next,
+            None => return,
+        };
+
+        let started = Instant::now();
+        let target = item.target.clone();
+        let output =
+            catch_unwind(AssertUnwindSafe(|| process(&*build, item))).unwrap_or_else(|_| {
+                Err(SharpError::Operation("Processing thread panicked".to_string()))
+            });
+        let (result, data) = match output {
+            Ok((info, data)) => (Ok(info), data),
+            Err(e) => (Err(e), Vec::new()),
+        };
+
+        let result = BatchResult {
+            index,
+            target,
+            result,
+            data,
+            elapsed: started.elapsed(),
+        };
+        if sender.send(result).is_err() {
+            return;
+        }
+    }
+}
+
+fn process<F>(build: &F, item: BatchItem) -> Result<(OutputInfo, Vec<u8>), SharpError>
+where
+    F: Fn(Input) -> Result<Sharp, SharpError>,
+{
+    let sharp = build(item.input)?;
+    match item.target {
+        BatchTarget::File(file_out) => Ok((sharp.to_file_with_info(file_out)?, Vec::new())),
+        BatchTarget::Buffer => {
+            let (data, info) = sharp.to_buffer_with_info()?;
+            Ok((info, data))
+        }
+    }
+}
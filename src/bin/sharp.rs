@@ -0,0 +1,660 @@
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand};
+use serde_json::{json, Value};
+use sharp::{
+    batch::{self, BatchItem, BatchOptions},
+    composite::OverlayOptions,
+    input::Input,
+    recipe::{Operation, Recipe},
+    resize::Gravity,
+    template::SharpTemplate,
+    Colour, Sharp,
+};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Parser)]
+#[command(name = "sharp", version, about = "Resize, convert and inspect images with libvips")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /** Resize to a width and/or height */
+    Resize {
+        #[command(flatten)]
+        resize: ResizeArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /** Extend the edges with a background colour or derived pixels */
+    Extend {
+        #[command(flatten)]
+        extend: ExtendArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /** Extract a region */
+    Extract {
+        #[command(flatten)]
+        region: RegionArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /** Trim pixels from all edges that are similar to the background */
+    Trim {
+        #[command(flatten)]
+        trim: TrimArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /** Composite an overlay image on top of each input */
+    Composite {
+        #[command(flatten)]
+        composite: CompositeArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /** Convert to another format */
+    Convert {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /** Apply a recipe exported with `Sharp::to_recipe` */
+    Apply {
+        /** JSON recipe file */
+        #[arg(long)]
+        recipe: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /** Generate a tile pyramid, such as Deep Zoom or IIIF */
+    Tile {
+        #[command(flatten)]
+        tile: TileArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /** Write a Windows icon containing several sizes */
+    Icon {
+        /** Icon sizes in pixels, e.g. 256,48,16 */
+        #[arg(long, value_delimiter = ',')]
+        sizes: Option<Vec<u16>>,
+        /** Input files, directories or glob patterns */
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        /** Output .ico file, or directory when there are several inputs */
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /** Print metadata as JSON */
+    Metadata {
+        /** Input files, directories or glob patterns */
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+    /** Print pixel statistics as JSON */
+    Stats {
+        /** Input files, directories or glob patterns */
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /** Input files, directories or glob patterns */
+    #[arg(required = true)]
+    inputs: Vec<String>,
+    /** Output file, or directory when there are several inputs */
+    #[arg(short, long)]
+    output: PathBuf,
+    /** Output format, e.g. jpeg, png, webp, avif, gif, tiff, jxl. Inferred from the output extension by default */
+    #[arg(short, long)]
+    format: Option<String>,
+    /** Output quality, 1-100 */
+    #[arg(short, long)]
+    quality: Option<i32>,
+    /** Number of images processed at the same time */
+    #[arg(long)]
+    concurrency: Option<usize>,
+}
+
+#[derive(Args)]
+struct ResizeArgs {
+    #[arg(long)]
+    width: Option<i32>,
+    #[arg(long)]
+    height: Option<i32>,
+    /** cover, contain, fill, inside or outside */
+    #[arg(long)]
+    fit: Option<String>,
+    /** centre, top, right, bottom, left, right_top, ..., entropy or attention */
+    #[arg(long)]
+    position: Option<String>,
+    /** Background colour for contain, as hex e.g. #ffffff */
+    #[arg(long)]
+    background: Option<String>,
+    /** nearest, linear, cubic, mitchell, lanczos2 or lanczos3 */
+    #[arg(long)]
+    kernel: Option<String>,
+    #[arg(long)]
+    without_enlargement: bool,
+    #[arg(long)]
+    without_reduction: bool,
+}
+
+#[derive(Args)]
+struct ExtendArgs {
+    #[arg(long)]
+    top: Option<i32>,
+    #[arg(long)]
+    left: Option<i32>,
+    #[arg(long)]
+    bottom: Option<i32>,
+    #[arg(long)]
+    right: Option<i32>,
+    /** Background colour as hex, e.g. #000000 */
+    #[arg(long)]
+    background: Option<String>,
+    /** background, copy, repeat or mirror */
+    #[arg(long)]
+    extend_with: Option<String>,
+}
+
+#[derive(Args)]
+struct RegionArgs {
+    #[arg(long)]
+    left: u32,
+    #[arg(long)]
+    top: u32,
+    #[arg(long)]
+    width: u32,
+    #[arg(long)]
+    height: u32,
+}
+
+#[derive(Args)]
+struct TrimArgs {
+    /** Background colour as hex, defaults to the top-left pixel */
+    #[arg(long)]
+    background: Option<String>,
+    #[arg(long)]
+    threshold: Option<f64>,
+    #[arg(long)]
+    line_art: bool,
+}
+
+#[derive(Args)]
+struct CompositeArgs {
+    /** Overlay image file */
+    #[arg(long)]
+    overlay: PathBuf,
+    /** centre, north, east, south, west, northeast, southeast, southwest or northwest */
+    #[arg(long)]
+    gravity: Option<String>,
+    #[arg(long)]
+    top: Option<i32>,
+    #[arg(long)]
+    left: Option<i32>,
+    /** Repeat the overlay across the image */
+    #[arg(long)]
+    tile: bool,
+}
+
+#[derive(Args)]
+struct TileArgs {
+    #[arg(long)]
+    size: Option<i32>,
+    #[arg(long)]
+    overlap: Option<i32>,
+    /** dz, iiif, iiif3, zoomify or google */
+    #[arg(long)]
+    layout: Option<String>,
+    /** fs or zip */
+    #[arg(long)]
+    container: Option<String>,
+    /** onepixel, onetile or one */
+    #[arg(long)]
+    depth: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Some(args) = cli.command.output_args() {
+        check_quality(args);
+    }
+    match run(cli.command) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("sharp: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/*
+  Run a command, returning false when some of the inputs failed
+*/
+fn run(command: Command) -> Result<bool, String> {
+    match command {
+        Command::Resize {
+            resize,
+            output,
+        } => {
+            let options = json!({
+                "width": resize.width,
+                "height": resize.height,
+                "fit": resize.fit,
+                "position": resize.position,
+                "background": colour(resize.background)?,
+                "kernel": resize.kernel,
+                "without_enlargement": resize.without_enlargement.then_some(true),
+                "without_reduction": resize.without_reduction.then_some(true),
+            });
+            transform(vec![operation("resize", options)?], None, output)
+        }
+        Command::Extend {
+            extend,
+            output,
+        } => {
+            let options = json!({
+                "top": extend.top,
+                "left": extend.left,
+                "bottom": extend.bottom,
+                "right": extend.right,
+                "background": colour(extend.background)?,
+                "extend_with": extend.extend_with,
+            });
+            transform(vec![operation("extend", options)?], None, output)
+        }
+        Command::Extract {
+            region,
+            output,
+        } => {
+            let operation = Operation::Extract {
+                region: sharp::resize::Region {
+                    left: region.left,
+                    top: region.top,
+                    width: region.width,
+                    height: region.height,
+                },
+            };
+            transform(vec![operation], None, output)
+        }
+        Command::Trim {
+            trim,
+            output,
+        } => {
+            let options = json!({
+                "background": colour(trim.background)?,
+                "threshold": trim.threshold,
+                "line_art": trim.line_art.then_some(true),
+            });
+            transform(vec![operation("trim", options)?], None, output)
+        }
+        Command::Composite {
+            composite,
+            output,
+        } => {
+            let gravity = composite.gravity.as_deref().map(gravity).transpose()?;
+            let overlay = Overlay {
+                input: Input::path(&composite.overlay),
+                gravity,
+                top: composite.top,
+                left: composite.left,
+                tile: composite.tile.then_some(true),
+            };
+            transform(Vec::new(), Some(overlay), output)
+        }
+        Command::Convert {
+            output,
+        } => transform(Vec::new(), None, output),
+        Command::Apply {
+            recipe,
+            output,
+        } => {
+            let recipe = std::fs::read_to_string(&recipe).map_err(|e| e.to_string())?;
+            let recipe: Recipe = serde_json::from_str(&recipe).map_err(|e| e.to_string())?;
+            transform(recipe.operations, None, output)
+        }
+        Command::Tile {
+            tile,
+            output,
+        } => {
+            let options = json!({
+                "size": tile.size,
+                "overlap": tile.overlap,
+                "layout": tile.layout,
+                "container": tile.container,
+                "depth": tile.depth,
+            });
+            transform(vec![operation("tile", options)?], None, output)
+        }
+        Command::Icon {
+            sizes,
+            inputs,
+            output,
+        } => {
+            let files = expand(&inputs)?;
+            let outputs = output_files(&files, &output, Some("ico"))?;
+            let mut succeeded = true;
+            for (file, output) in files.iter().zip(outputs) {
+                if let Err(e) = Sharp::new_from_file(file)
+                    .and_then(|sharp| sharp.to_icon(&output, sizes.clone()))
+                {
+                    eprintln!("sharp: {}: {}", file.display(), e);
+                    succeeded = false;
+                }
+            }
+            Ok(succeeded)
+        }
+        Command::Metadata {
+            inputs,
+        } => inspect(inputs, |sharp| {
+            sharp.metadata().map(|metadata| serde_json::to_value(metadata).unwrap_or_default())
+        }),
+        Command::Stats {
+            inputs,
+        } => inspect(inputs, |sharp| {
+            sharp.stats().map(|stats| serde_json::to_value(stats).unwrap_or_default())
+        }),
+    }
+}
+
+impl Command {
+    fn output_args(&self) -> Option<&OutputArgs> {
+        match self {
+            Command::Resize {
+                output,
+                ..
+            }
+            | Command::Extend {
+                output,
+                ..
+            }
+            | Command::Extract {
+                output,
+                ..
+            }
+            | Command::Trim {
+                output,
+                ..
+            }
+            | Command::Composite {
+                output,
+                ..
+            }
+            | Command::Convert {
+                output,
+            }
+            | Command::Apply {
+                output,
+                ..
+            }
+            | Command::Tile {
+                output,
+                ..
+            } => Some(output),
+            Command::Icon {
+                ..
+            }
+            | Command::Metadata {
+                ..
+            }
+            | Command::Stats {
+                ..
+            } => None,
+        }
+    }
+}
+
+/*
+  Exit with a usage error when --quality is given for an output format that has no quality setting
+*/
+fn check_quality(args: &OutputArgs) {
+    if args.quality.is_none() {
+        return;
+    }
+    let format = match args.format.clone().or_else(|| extension(&args.output)) {
+        Some(format) => normalise_format(&format),
+        None => return,
+    };
+    if matches!(format.as_str(), "gif" | "raw") {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("--quality is not supported for {} output", format),
+            )
+            .exit();
+    }
+}
+
+/*
+  Overlay for the composite command, rebuilt for each input
+*/
+struct Overlay {
+    input: Input,
+    gravity: Option<Gravity>,
+    top: Option<i32>,
+    left: Option<i32>,
+    tile: Option<bool>,
+}
+
+/*
+  Run the operations over every input, followed by the output format when one is given
+*/
+fn transform(
+    mut operations: Vec<Operation>,
+    overlay: Option<Overlay>,
+    args: OutputArgs,
+) -> Result<bool, String> {
+    if let Some(format) = &args.format {
+        operations.push(operation(
+            &normalise_format(format),
+            json!({
+                "quality": args.quality,
+            }),
+        )?);
+    } else if args.quality.is_some() {
+        let format = extension(&args.output)
+            .ok_or("--quality requires --format or an output file extension")?;
+        operations.push(operation(
+            &normalise_format(&format),
+            json!({
+                "quality": args.quality,
+            }),
+        )?);
+    }
+
+    let recipe = Recipe {
+        operations,
+    };
+    let template = SharpTemplate::new(|sharp| {
+        let sharp = match &overlay {
+            Some(overlay) => sharp.composite(&[OverlayOptions {
+                input: overlay.input.clone(),
+                gravity: overlay.gravity.clone(),
+                top: overlay.top,
+                left: overlay.left,
+                tile: overlay.tile,
+                ..Default::default()
+            }])?,
+            None => sharp,
+        };
+        sharp.apply_recipe(&recipe)
+    })
+    .map_err(|e| e.to_string())?;
+
+    let files = expand(&args.inputs)?;
+    let outputs = output_files(&files, &args.output, args.format.as_deref())?;
+    let items: Vec<BatchItem> = files
+        .iter()
+        .zip(outputs)
+        .map(|(file, output)| BatchItem::file(Input::path(file), output))
+        .collect();
+
+    let mut succeeded = true;
+    let options = BatchOptions {
+        concurrency: args.concurrency,
+    };
+    for result in batch::run(template, items, Some(options)) {
+        if let Err(e) = result.result {
+            eprintln!("sharp: {}: {}", files[result.index].display(), e);
+            succeeded = false;
+        }
+    }
+
+    Ok(succeeded)
+}
+
+/*
+  Print the JSON produced for each input, keyed by file name when there are several
+*/
+fn inspect<F>(inputs: Vec<String>, describe: F) -> Result<bool, String>
+where
+    F: Fn(Sharp) -> Result<Value, sharp::SharpError>,
+{
+    let files = expand(&inputs)?;
+    let mut succeeded = true;
+    let mut values = serde_json::Map::new();
+    for file in files.iter() {
+        match Sharp::new_from_file(file).and_then(&describe) {
+            Ok(value) => {
+                values.insert(file.to_string_lossy().to_string(), value);
+            }
+            Err(e) => {
+                eprintln!("sharp: {}: {}", file.display(), e);
+                succeeded = false;
+            }
+        }
+    }
+
+    let output = if files.len() == 1 {
+        values.into_iter().next().map(|(_, value)| value).unwrap_or_default()
+    } else {
+        Value::Object(values)
+    };
+    println!("{}", serde_json::to_string_pretty(&output).map_err(|e| e.to_string())?);
+    Ok(succeeded)
+}
+
+/*
+  Expand directories and glob patterns into a sorted list of files
+*/
+fn expand(inputs: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(path)
+                .map_err(|e| format!("{}: {}", input, e))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file())
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else if path.is_file() {
+            files.push(path.to_path_buf());
+        } else {
+            let matches: Vec<PathBuf> = glob::glob(input)
+                .map_err(|e| format!("{}: {}", input, e))?
+                .filter_map(|entry| entry.ok())
+                .filter(|path| path.is_file())
+                .collect();
+            if matches.is_empty() {
+                return Err(format!("{}: no such file", input));
+            }
+            files.extend(matches);
+        }
+    }
+    Ok(files)
+}
+
+/*
+  Output file for each input: the output itself for a single input, otherwise a file in the output directory
+*/
+fn output_files(
+    files: &[PathBuf],
+    output: &Path,
+    format: Option<&str>,
+) -> Result<Vec<PathBuf>, String> {
+    if files.len() == 1 && !output.is_dir() {
+        return Ok(vec![output.to_path_buf()]);
+    }
+
+    std::fs::create_dir_all(output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    Ok(files
+        .iter()
+        .map(|file| {
+            let name = file.file_name().unwrap_or_default();
+            let file_out = output.join(name);
+            match format {
+                Some(format) => file_out.with_extension(format),
+                None => file_out,
+            }
+        })
+        .collect())
+}
+
+fn operation(op: &str, options: Value) -> Result<Operation, String> {
+    serde_json::from_value(json!({
+        "op": op,
+        "options": options,
+    }))
+    .map_err(|e| format!("{}: {}", op, e))
+}
+
+fn normalise_format(format: &str) -> String {
+    match format.to_lowercase().as_str() {
+        "jpg" => "jpeg".to_string(),
+        "tif" => "tiff".to_string(),
+        "heic" => "heif".to_string(),
+        format => format.to_string(),
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|extension| extension.to_string_lossy().to_string())
+}
+
+/*
+  Parse a hex colour such as #ff0000 or #ff000080
+*/
+fn colour(hex: Option<String>) -> Result<Value, String> {
+    let hex = match hex {
+        Some(hex) => hex,
+        None => return Ok(Value::Null),
+    };
+    let digits = hex.trim_start_matches('#');
+    let value = u32::from_str_radix(digits, 16)
+        .ok()
+        .filter(|_| digits.len() == 6 || digits.len() == 8)
+        .ok_or_else(|| format!("Invalid colour {}", hex))?;
+    let colour = if digits.len() == 8 {
+        let alpha = (value & 0xFF) as f32 / 255.0;
+        Colour::new(value >> 24, (value >> 16) & 0xFF, (value >> 8) & 0xFF, alpha)
+    } else {
+        Colour::from_hex(value)
+    };
+    serde_json::to_value(colour).map_err(|e| e.to_string())
+}
+
+fn gravity(gravity: &str) -> Result<Gravity, String> {
+    match gravity.to_lowercase().as_str() {
+        "centre" | "center" => Ok(Gravity::Centre),
+        "north" => Ok(Gravity::North),
+        "east" => Ok(Gravity::East),
+        "south" => Ok(Gravity::South),
+        "west" => Ok(Gravity::West),
+        "northeast" => Ok(Gravity::Northeast),
+        "southeast" => Ok(Gravity::Southeast),
+        "southwest" => Ok(Gravity::Southwest),
+        "northwest" => Ok(Gravity::Northwest),
+        _ => Err(format!("Invalid gravity {}", gravity)),
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    in_range,
+    output::{
+        AvifOptions, FormatEnum, FormatOptions, JpegOptions, JxlOptions, OutputInfo, WebpOptions,
+    },
+    pipeline::{self, Decoded, PipelineBaton},
+    resize::ResizeOptions,
+    InvalidParameterError, Sharp, SharpError,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct MaxBytesOptions {
+    /** Options for the output format, see {@link to_format}. The quality, or distance for JXL, is chosen by the search. */
+    pub format_options: Option<FormatOptions>,
+    /** Lowest quality to try for JPEG, WebP and AVIF, integer 1-100 (optional, default 1) */
+    pub min_quality: Option<i32>,
+    /** Highest distance to try for JXL, between 0.1 and 15.0 (optional, default 15.0) */
+    pub max_distance: Option<f64>,
+    /** Reduce the width when the lowest quality is still too large (optional, default false) */
+    pub reduce: Option<bool>,
+    /** Factor applied to the width at each reduction, between 0.1 and 0.95 (optional, default 0.8) */
+    pub reduce_factor: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SsimOptions {
+    /** Options for the output format, see {@link to_format}. The quality is chosen by the search. */
+    pub format_options: Option<FormatOptions>,
+    /** Lowest quality to try, integer 1-100 (optional, default 1) */
+    pub min_quality: Option<i32>,
+}
+
+impl Sharp {
+    /**
+     * Write output to a Buffer of at most `limit` bytes, using the highest quality that fits.
+     *
+     * The quality of JPEG, WebP and AVIF output, or the distance of JXL output, is found with a binary search,
+     * encoding at most 9 times for each width. The input is decoded and processed only once.
+     *
+     * When even the lowest quality is too large and `reduce` is set, the width is reduced step by step
+     * until the output fits, otherwise a `LimitExceeded` error with the smallest size reached is returned.
+     *
+     * Returns the output data and info along with the quality, or distance for JXL, that was chosen.
+     *
+     * @example
+     * let (data, info, quality) = Sharp::new_from_file("input.jpg")?
+     *   .resize(1600, 1600)?
+     *   .to_buffer_max_bytes(200 * 1024, FormatEnum::Webp, None)?;
+     */
+    pub fn to_buffer_max_bytes(
+        mut self,
+        limit: usize,
+        format: FormatEnum,
+        options: Option<MaxBytesOptions>,
+    ) -> Result<(Vec<u8>, OutputInfo, f64), SharpError> {
+        if limit == 0 {
+            return Err(InvalidParameterError!("limit", "positive integer", limit));
+        }
+        let options = options.unwrap_or_default();
+        let search = QualitySearch::new(
+            format,
+            options.format_options.clone(),
+            options.min_quality,
+            options.max_distance,
+        )?;
+        let reduce = options.reduce.unwrap_or(false);
+        let reduce_factor = options.reduce_factor.unwrap_or(0.8);
+        if !in_range(reduce_factor, 0.1, 0.95) {
+            return Err(InvalidParameterError!(
+                "reduceFactor",
+                "number between 0.1 and 0.95",
+                reduce_factor
+            ));
+        }
+
+        let branch = self.fork().options;
+        let decoded = pipeline::decode(self.options)?;
+        let mut width = decoded.width();
+        let mut resize = None;
+
+        loop {
+            let (data, info) = search.encode(&decoded, &branch, resize, 0)?;
+            if data.len() <= limit {
+                let (data, info, level) =
+                    search.highest(&decoded, &branch, resize, limit, (data, info))?;
+                return Ok((data, info, search.value(level)));
+            }
+            if !reduce || width <= 1 {
+                return Err(SharpError::LimitExceeded(format!(
+                    "Unable to encode {} output within {} bytes, smallest output was {} bytes",
+                    format,
+                    limit,
+                    data.len()
+                )));
+            }
+            width = ((width as f64 * reduce_factor) as i32).max(1);
+            resize = Some(width);
+        }
+    }
+
+    /**
+     * Write output to a Buffer using the lowest quality whose structural similarity (SSIM) to the processed image
+     * is at least `target`, a number between 0 and 1.
+     *
+     * The quality of JPEG, WebP and AVIF output is found with a binary search, decoding each candidate
+     * to compare its luminance with the image before encoding. The input is decoded and processed only once.
+     * A DSSIM budget `d` corresponds to a `target` of `1 - 2 * d`.
+     *
+     * Returns the output data and info, with the achieved score as `ssim`, along with the quality that was chosen.
+     * An `Encode` error is returned when even the highest quality does not reach `target`,
+     * and an `InvalidParameter` error when the input has more than one page.
+     *
+     * @example
+     * let (data, info, quality) = Sharp::new_from_file("input.jpg")?
+     *   .resize(1600, 1600)?
+     *   .to_buffer_min_ssim(0.98, FormatEnum::Webp, None)?;
+     */
+    pub fn to_buffer_min_ssim(
+        mut self,
+        target: f64,
+        format: FormatEnum,
+        options: Option<SsimOptions>,
+    ) -> Result<(Vec<u8>, OutputInfo, f64), SharpError> {
+        if !in_range(target, 0.0, 1.0) {
+            return Err(InvalidParameterError!("target", "number between 0 and 1", target));
+        }
+        if !matches!(
+            format,
+            FormatEnum::Jpeg | FormatEnum::Jpg | FormatEnum::Webp | FormatEnum::Avif
+        ) {
+            return Err(InvalidParameterError!("format", "one of jpeg, webp or avif", format));
+        }
+        let options = options.unwrap_or_default();
+        let search = QualitySearch::new(format, options.format_options, options.min_quality, None)?;
+
+        let branch = self.fork().options;
+        let decoded = pipeline::decode(self.options)?;
+        if decoded.pages() > 1 {
+            return Err(InvalidParameterError!("input", "single page image", decoded.pages()));
+        }
+        let attempt = |level: i32| -> Result<(Vec<u8>, OutputInfo, f64), SharpError> {
+            let (data, info) = search.encode(&decoded, &branch, None, level)?;
+            let score = decoded.ssim(&data)?;
+            Ok((data, info, score))
+        };
+
+        // Lowest level that reaches the target, given that the highest one does
+        let mut best = attempt(search.levels)?;
+        if best.2 < target {
+            return Err(SharpError::Encode(format!(
+                "Unable to reach an SSIM of {} with {} output, highest quality scored {}",
+                target, format, best.2
+            )));
+        }
+        let mut level = search.levels;
+        let mut low = 0;
+        while low < level {
+            let mid = (low + level) / 2;
+            let candidate = attempt(mid)?;
+            if candidate.2 >= target {
+                best = candidate;
+                level = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        let (data, mut info, score) = best;
+        info.ssim = Some(score);
+        Ok((data, info, search.value(level)))
+    }
+}
+
+/*
+  Quality levels of a format, from 0 (smallest output) upwards
+*/
+pub(crate) struct QualitySearch {
+    format: FormatEnum,
+    format_options: FormatOptions,
+    min_quality: i32,
+    max_distance: f64,
+    levels: i32,
+}
+
+impl QualitySearch {
+    pub(crate) fn new(
+        format: FormatEnum,
+        format_options: Option<FormatOptions>,
+        min_quality: Option<i32>,
+        max_distance: Option<f64>,
+    ) -> Result<Self, SharpError> {
+        let min_quality = min_quality.unwrap_or(1);
+        if !in_range(min_quality as _, 1.0, 100.0) {
+            return Err(InvalidParameterError!(
+                "minQuality",
+                "integer between 1 and 100",
+                min_quality
+            ));
+        }
+        let max_distance = max_distance.unwrap_or(15.0);
+        if !in_range(max_distance, 0.1, 15.0) {
+            return Err(InvalidParameterError!(
+                "maxDistance",
+                "number between 0.1 and 15.0",
+                max_distance
+            ));
+        }
+
+        let levels = match format {
+            FormatEnum::Jpeg | FormatEnum::Jpg | FormatEnum::Webp | FormatEnum::Avif => {
+                100 - min_quality
+            }
+            FormatEnum::Jxl => ((max_distance - 0.1) * 10.0).round() as i32,
+            _ => {
+                return Err(InvalidParameterError!(
+                    "format",
+                    "one of jpeg, webp, avif or jxl",
+                    format
+                ))
+            }
+        };
+
+        Ok(Self {
+            format,
+            format_options: format_options.unwrap_or_default(),
+            min_quality,
+            max_distance,
+            levels,
+        })
+    }
+
+    /*
+      Quality, or distance for JXL, of a level
+    */
+    pub(crate) fn value(&self, level: i32) -> f64 {
+        match self.format {
+            FormatEnum::Jxl => ((self.max_distance - level as f64 * 0.1) * 10.0).round() / 10.0,
+            _ => (self.min_quality + level) as f64,
+        }
+    }
+
+    fn format_options(&self, level: i32) -> FormatOptions {
+        let mut options = self.format_options.clone();
+        let quality = Some(self.min_quality + level);
+        match self.format {
+            FormatEnum::Jxl => {
+                options.jxl_options = Some(JxlOptions {
+                    quality: None,
+                    distance: Some(self.value(level)),
+                    ..options.jxl_options.unwrap_or_default()
+                })
+            }
+            FormatEnum::Webp => {
+                options.webp_options = Some(WebpOptions {
+                    quality,
+                    ..options.webp_options.unwrap_or_default()
+                })
+            }
+            FormatEnum::Avif => {
+                options.avif_options = Some(AvifOptions {
+                    quality,
+                    ..options.avif_options.unwrap_or_default()
+                })
+            }
+            _ => {
+                options.jpeg_options = Some(JpegOptions {
+                    quality,
+                    ..options.jpeg_options.unwrap_or_default()
+                })
+            }
+        }
+        options
+    }
+
+    /*
+      Encode the shared image at a level, optionally resized to a width
+    */
+    pub(crate) fn encode(
+        &self,
+        decoded: &Decoded,
+        branch: &PipelineBaton,
+        width: Option<i32>,
+        level: i32,
+    ) -> Result<(Vec<u8>, OutputInfo), SharpError> {
+        let mut sharp = Sharp {
+            options: branch.clone(),
+        };
+        if let Some(width) = width {
+            sharp = sharp.resize_with_opts(ResizeOptions {
+                width: Some(width),
+                ..Default::default()
+            })?;
+        }
+        let mut sharp = sharp.to_format(self.format, Some(self.format_options(level)))?;
+        sharp.options.file_out = String::new();
+
+        let mut baton = decoded.finish(sharp.options)?;
+        Ok((std::mem::take(&mut baton.buffer_out), Sharp::create_output_info(baton)))
+    }
+
+    /*
+      Binary search for the highest level whose output is within the limit, given the output of level 0
+    */
+    fn highest(
+        &self,
+        decoded: &Decoded,
+        branch: &PipelineBaton,
+        width: Option<i32>,
+        limit: usize,
+        lowest: (Vec<u8>, OutputInfo),
+    ) -> Result<(Vec<u8>, OutputInfo, i32), SharpError> {
+        let (mut data, mut info) = lowest;
+        let mut low = 0;
+        let mut high = self.levels;
+        while low < high {
+            let mid = (low + high + 1) / 2;
+            let (candidate, candidate_info) = self.encode(decoded, branch, width, mid)?;
+            if candidate.len() <= limit {
+                data = candidate;
+                info = candidate_info;
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        Ok((data, info, low))
+    }
+}
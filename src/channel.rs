@@ -1,7 +1,8 @@
 use crate::{
     in_range,
     input::{create_input_descriptor, Input, SharpInput, SharpOptions},
-    InvalidParameterError, Sharp,
+    recipe::Operation,
+    InvalidParameterError, Sharp, SharpError,
 };
 use rs_vips::ops::OperationBoolean;
 
@@ -22,7 +23,8 @@ impl Sharp {
      */
     pub fn remove_alpha(mut self) -> Self {
         self.options.remove_alpha = true;
-        self
+        self.record(Operation::RemoveAlpha);
+        self.step()
     }
 
     /**
@@ -49,13 +51,17 @@ impl Sharp {
      * @returns {Sharp}
      * @throws {Error} Invalid alpha transparency level
      */
-    pub fn ensure_alpha(mut self, alpha: f64) -> Result<Self, String> {
+    pub fn ensure_alpha(mut self, alpha: f64) -> Result<Self, SharpError> {
+        let operation = Operation::EnsureAlpha {
+            alpha,
+        };
         if in_range(alpha, 0.0, 1.1) {
             self.options.ensure_alpha = alpha;
         } else {
             return Err(InvalidParameterError!("alpha", "number between 0 and 1", alpha));
         }
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -79,7 +85,10 @@ impl Sharp {
      * @returns {Sharp}
      * @throws {Error} Invalid channel
      */
-    pub fn extract_channel(mut self, channel: u32) -> Result<Self, String> {
+    pub fn extract_channel(mut self, channel: u32) -> Result<Self, SharpError> {
+        let operation = Operation::ExtractChannel {
+            channel,
+        };
         if in_range(channel as _, 0.0, 4.0) {
             self.options.extract_channel = channel as _;
         } else {
@@ -89,6 +98,7 @@ impl Sharp {
                 channel
             ));
         }
+        self.record(operation);
         Ok(self)
     }
 
@@ -112,7 +122,7 @@ impl Sharp {
         mut self,
         images: &[Input],
         options: Option<SharpOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         if images.is_empty() && options.is_none() {
             return Ok(self);
         }
@@ -126,7 +136,7 @@ impl Sharp {
             self.options.join_channel_in.push(descriptor);
         }
 
-        Ok(self)
+        Ok(self.step())
     }
 
     /**
@@ -146,7 +156,11 @@ impl Sharp {
      * @throws {Error} Invalid parameters
      */
     pub fn bandbool(mut self, bool_op: OperationBoolean) -> Self {
+        let operation = Operation::Bandbool {
+            operator: bool_op,
+        };
         self.options.band_bool_op = bool_op;
-        self
+        self.record(operation);
+        self.step()
     }
 }
@@ -1,4 +1,4 @@
-use crate::{Colour, Sharp};
+use crate::{recipe::Operation, Colour, Sharp};
 pub use rs_vips::ops::Interpretation;
 
 impl Sharp {
@@ -16,8 +16,12 @@ impl Sharp {
      * @throws {Error} Invalid parameter
      */
     pub fn tint(mut self, tint: Colour) -> Self {
+        let operation = Operation::Tint {
+            colour: tint.clone(),
+        };
         self.options.tint = tint.rgba;
-        self
+        self.record(operation);
+        self.step()
     }
 
     /**
@@ -35,7 +39,11 @@ impl Sharp {
      * @returns {Sharp}
      */
     pub fn greyscale(mut self, greyscale: bool) -> Self {
+        let operation = Operation::Greyscale {
+            greyscale,
+        };
         self.options.greyscale = greyscale;
+        self.record(operation);
         self
     }
 
@@ -60,7 +68,11 @@ impl Sharp {
      * @throws {Error} Invalid parameters
      */
     pub fn pipeline_colourspace(mut self, colourspace: Interpretation) -> Self {
+        let operation = Operation::PipelineColourspace {
+            colourspace,
+        };
         self.options.colourspace_pipeline = colourspace;
+        self.record(operation);
         self
     }
 
@@ -79,7 +91,11 @@ impl Sharp {
      * @throws {Error} Invalid parameters
      */
     pub fn to_colourspace(mut self, colourspace: Interpretation) -> Self {
+        let operation = Operation::ToColourspace {
+            colourspace,
+        };
         self.options.colourspace = colourspace;
+        self.record(operation);
         self
     }
 }
@@ -1,20 +1,32 @@
-use crate::util::{get_g_type, new_c_string, G_TYPE_INT};
+use crate::{
+    output::{MetadataPolicy, Progress},
+    stream::StreamReader,
+    task::CancellationToken,
+    util::{get_g_type, new_c_string, G_TYPE_INT},
+};
 use rs_vips::{
     bindings::{
-        g_signal_connect_data, vips_blob_get_type, vips_error, vips_foreign_find_load,
-        vips_foreign_find_load_buffer, vips_image_is_sequential, vips_image_map,
-        vips_image_set_kill, vips_interpretation_max_alpha, vips_malloc, GValue,
-        VIPS_META_ICC_NAME, VIPS_META_ORIENTATION, VIPS_META_PAGE_HEIGHT, VIPS_META_SEQUENTIAL,
+        g_signal_connect_data, g_timer_elapsed, vips_blob_get_type, vips_error,
+        vips_foreign_find_load, vips_foreign_find_load_buffer, vips_foreign_find_load_source,
+        vips_image_is_sequential, vips_image_iskilled, vips_image_map, vips_image_set_kill,
+        vips_interpretation_max_alpha, GClosure, GValue, VIPS_META_ICC_NAME, VIPS_META_ORIENTATION,
+        VIPS_META_PAGE_HEIGHT, VIPS_META_SEQUENTIAL, VIPS_META_XMP_NAME,
     },
     error::Error::OperationError,
     ops::{Access, Align, BandFormat, FailOn, Interpretation, TextWrap},
     voption::{Setter, VOption},
-    Result, VipsImage,
+    Result, VipsImage, VipsSource,
 };
 use std::{
     collections::HashMap,
     ffi::{c_char, c_int, c_void, CStr, CString},
-    sync::OnceLock,
+    fmt,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
 };
 
 #[derive(Debug, Clone)]
@@ -22,6 +34,7 @@ pub(crate) struct InputDescriptor {
     pub(crate) file: String,
     pub(crate) auto_orient: bool,
     pub(crate) buffer: Vec<u8>,
+    pub(crate) reader: Option<StreamReader>,
     pub(crate) fail_on: FailOn,
     pub(crate) limit_input_pixels: usize,
     pub(crate) unlimited: bool,
@@ -78,6 +91,7 @@ impl Default for InputDescriptor {
         Self {
             auto_orient: false,
             buffer: Vec::new(),
+            reader: None,
             fail_on: FailOn::Warning,
             limit_input_pixels: 0x3FFF * 0x3FFF,
             unlimited: false,
@@ -353,6 +367,24 @@ pub(crate) fn determine_image_type(buffer: &[u8]) -> ImageType {
     }
 }
 
+/*
+  Determine image format of a source, reads the first few bytes of the stream
+*/
+pub(crate) fn determine_image_type_from_source(source: &VipsSource) -> ImageType {
+    let load = unsafe { vips_foreign_find_load_source(source.as_mut_ptr()) };
+
+    if load.is_null() {
+        return ImageType::UNKNOWN;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(load) };
+    if let Ok(loader_name) = c_str.to_str() {
+        loader_to_type().get(loader_name).cloned().unwrap_or(ImageType::UNKNOWN)
+    } else {
+        ImageType::UNKNOWN
+    }
+}
+
 /*
   Determine image format, reads the first few bytes of the file
 */
@@ -472,6 +504,171 @@ pub(crate) fn remove_exif(image: VipsImage) -> VipsImage {
     image
 }
 
+/*
+  Remove the EXIF tags and XMP properties that the policy does not allow.
+  libvips drops any EXIF tag without a matching "exif-ifdN-Name" field when saving.
+*/
+pub(crate) fn strip_metadata(image: VipsImage, policy: &MetadataPolicy) -> Result<VipsImage> {
+    let image = image.copy()?;
+
+    let mut field_names = Box::new(Vec::<String>::new());
+    let field_names_ptr: *mut c_void = &mut *field_names as *mut _ as *mut c_void;
+    unsafe {
+        vips_image_map(image.as_mut_ptr(), Some(remove_exif_callback), field_names_ptr);
+    }
+    for name in field_names.iter() {
+        if let Some((ifd, tag)) = name.strip_prefix("exif-").and_then(|name| name.split_once('-')) {
+            if ifd.starts_with("ifd") && !policy.allows(&[tag, ifd]) {
+                image.remove(name)?;
+            }
+        }
+    }
+
+    if image.get_typeof(VIPS_META_XMP_NAME)? == unsafe { vips_blob_get_type() } {
+        let xmp = image.get_blob(VIPS_META_XMP_NAME)?;
+        let xmp = strip_xmp(&String::from_utf8_lossy(&xmp), policy);
+        image.set_blob(VIPS_META_XMP_NAME, xmp.as_bytes())?;
+    }
+
+    Ok(image)
+}
+
+/*
+  Remove the properties of top-level rdf:Description elements, written either as
+  child elements or as attributes, that the policy does not allow
+*/
+pub(crate) fn strip_xmp(xmp: &str, policy: &MetadataPolicy) -> String {
+    let allows = |name: &str| {
+        let local = name.split_once(':').map(|(_, local)| local).unwrap_or(name);
+        policy.allows(&[name, local])
+    };
+    let is_description = |stack: &[&str]| {
+        stack.len() >= 2
+            && stack[stack.len() - 1] == "rdf:Description"
+            && stack[stack.len() - 2] == "rdf:RDF"
+    };
+
+    let mut output = String::with_capacity(xmp.len());
+    let mut stack: Vec<&str> = Vec::new();
+    // Depth of the property being removed
+    let mut removing: Option<usize> = None;
+    let mut rest = xmp;
+
+    while let Some(start) = rest.find('<') {
+        if removing.is_none() {
+            output.push_str(&rest[..start]);
+        }
+        let (tag, after) = rest[start..].split_at(xml_tag_len(&rest[start..]));
+        rest = after;
+
+        if tag.starts_with("<?") || tag.starts_with("<!") {
+            if removing.is_none() {
+                output.push_str(tag);
+            }
+            continue;
+        }
+
+        if tag.starts_with("</") {
+            stack.pop();
+            if removing == Some(stack.len()) {
+                removing = None;
+            } else if removing.is_none() {
+                output.push_str(tag);
+            }
+            continue;
+        }
+
+        let self_closing = tag.ends_with("/>");
+        let name = tag[1..]
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or_default();
+        if removing.is_none() {
+            if is_description(&stack) && !allows(name) {
+                if !self_closing {
+                    removing = Some(stack.len());
+                }
+            } else if name == "rdf:Description" && stack.last() == Some(&"rdf:RDF") {
+                output.push_str(&strip_xml_attributes(tag, name, &allows));
+            } else {
+                output.push_str(tag);
+            }
+        }
+        if !self_closing {
+            stack.push(name);
+        }
+    }
+    if removing.is_none() {
+        output.push_str(rest);
+    }
+
+    output
+}
+
+/*
+  Length of the tag, comment, CDATA section or processing instruction at the start of xml
+*/
+pub(crate) fn xml_tag_len(xml: &str) -> usize {
+    let end = |terminator: &str| xml.find(terminator).map(|index| index + terminator.len());
+    let len = if xml.starts_with("<!--") {
+        end("-->")
+    } else if xml.starts_with("<![CDATA[") {
+        end("]]>")
+    } else if xml.starts_with("<?") {
+        end("?>")
+    } else {
+        let mut quote = None;
+        xml.char_indices().find_map(|(index, c)| match (quote, c) {
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                None
+            }
+            (Some(open), c) if c == open => {
+                quote = None;
+                None
+            }
+            (None, '>') => Some(index + 1),
+            _ => None,
+        })
+    };
+    len.unwrap_or(xml.len())
+}
+
+/*
+  Rewrite a start tag without the property attributes that are not allowed,
+  always keeping namespace declarations and rdf attributes
+*/
+fn strip_xml_attributes(tag: &str, name: &str, allows: &dyn Fn(&str) -> bool) -> String {
+    let mut output = format!("<{}", name);
+    let mut rest = &tag[1 + name.len()..];
+
+    loop {
+        rest = rest.trim_start();
+        let name_len = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(0);
+        if name_len == 0 || rest.starts_with('/') || rest.starts_with('>') {
+            break;
+        }
+        let attribute = &rest[..name_len];
+        let value = rest[name_len..].trim_start().trim_start_matches('=').trim_start();
+        let quote = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => break,
+        };
+        let value_len = value[1..].find(quote).map(|index| index + 2).unwrap_or(value.len());
+        if attribute.starts_with("xmlns") || attribute.starts_with("rdf:") || allows(attribute) {
+            output.push_str(&format!(" {}={}", attribute, &value[..value_len]));
+        }
+        rest = &value[value_len..];
+    }
+
+    output.push_str(if tag.ends_with("/>") {
+        "/>"
+    } else {
+        ">"
+    });
+    output
+}
+
 /*
   Get EXIF Orientation of image, if any.
 */
@@ -623,21 +820,20 @@ pub(crate) fn assert_image_type_dimensions(image: &VipsImage, image_type: ImageT
 }
 
 /*
-  Attach an event listener for progress updates, used to detect timeout
+  Attach an event listener for progress updates, used to detect timeout.
+  The returned flag is set once the timeout has stopped processing
 */
-pub(crate) fn set_timeout(image: &VipsImage, seconds: u32) {
+pub(crate) fn set_timeout(image: &VipsImage, seconds: u32) -> Arc<AtomicBool> {
+    let expired = Arc::new(AtomicBool::new(false));
     unsafe {
         if seconds > 0 {
             let im = image.as_mut_ptr();
 
             if (*im).progress_signal.is_null() {
-                let timeout =
-                    vips_malloc(im as *mut _, std::mem::size_of::<c_int>() as _) as *mut c_int;
-                if timeout.is_null() {
-                    panic!("Failed to allocate timeout");
-                }
-
-                *timeout = seconds as i32;
+                let timeout = Box::into_raw(Box::new(Timeout {
+                    seconds: seconds as i32,
+                    expired: expired.clone(),
+                }));
 
                 g_signal_connect_data(
                     im as *mut _,
@@ -646,7 +842,7 @@ pub(crate) fn set_timeout(image: &VipsImage, seconds: u32) {
                         vips_progress_call_back as *const (),
                     )),
                     timeout as *mut _,
-                    None,
+                    Some(free_timeout),
                     0,
                 );
 
@@ -654,6 +850,12 @@ pub(crate) fn set_timeout(image: &VipsImage, seconds: u32) {
             }
         }
     }
+    expired
+}
+
+struct Timeout {
+    seconds: c_int,
+    expired: Arc<AtomicBool>,
 }
 
 /*
@@ -662,18 +864,133 @@ pub(crate) fn set_timeout(image: &VipsImage, seconds: u32) {
 unsafe extern "C" fn vips_progress_call_back(
     im: *mut rs_vips::bindings::VipsImage,
     progress: *mut rs_vips::bindings::VipsProgress,
-    timeout: *mut c_int,
+    timeout: *mut Timeout,
 ) {
-    if *timeout > 0 && (*progress).run >= *timeout {
+    if (*timeout).seconds > 0 && (*progress).run >= (*timeout).seconds {
         vips_image_set_kill(im, 1);
+        (*timeout).expired.store(true, Ordering::Relaxed);
         let c_str_domain = CString::new("timeout").unwrap();
         let c_str_fmt = CString::new("%d%% complete").unwrap();
         vips_error(c_str_domain.as_ptr(), c_str_fmt.as_ptr(), (*progress).percent);
-        *timeout = 0;
+        (*timeout).seconds = 0;
     }
     (*progress).run += 1;
 }
 
+unsafe extern "C" fn free_timeout(data: *mut c_void, _closure: *mut GClosure) {
+    drop(Box::from_raw(data as *mut Timeout));
+}
+
+/*
+  Attach an event listener for progress updates, used to detect cancellation
+*/
+pub(crate) fn set_cancel(image: &VipsImage, cancel: &Option<CancellationToken>) {
+    if let Some(cancel) = cancel {
+        unsafe {
+            let im = image.as_mut_ptr();
+            let token = Box::into_raw(Box::new(cancel.clone()));
+
+            g_signal_connect_data(
+                im as *mut _,
+                b"eval\0".as_ptr() as *const _,
+                Some(std::mem::transmute::<*const (), unsafe extern "C" fn()>(
+                    vips_cancel_call_back as *const (),
+                )),
+                token as *mut _,
+                Some(free_cancel),
+                0,
+            );
+
+            image.set_progress(true);
+        }
+    }
+}
+
+/*
+  Event listener for progress updates, used to detect cancellation
+*/
+unsafe extern "C" fn vips_cancel_call_back(
+    im: *mut rs_vips::bindings::VipsImage,
+    progress: *mut rs_vips::bindings::VipsProgress,
+    cancel: *mut CancellationToken,
+) {
+    if (*cancel).is_cancelled() && vips_image_iskilled(im) == 0 {
+        vips_image_set_kill(im, 1);
+        let c_str_domain = CString::new("cancel").unwrap();
+        let c_str_fmt = CString::new("%d%% complete").unwrap();
+        vips_error(c_str_domain.as_ptr(), c_str_fmt.as_ptr(), (*progress).percent);
+    }
+}
+
+unsafe extern "C" fn free_cancel(data: *mut c_void, _closure: *mut GClosure) {
+    drop(Box::from_raw(data as *mut CancellationToken));
+}
+
+/*
+  User callback receiving progress updates
+*/
+#[derive(Clone)]
+pub(crate) struct ProgressHandler(pub(crate) Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl fmt::Debug for ProgressHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHandler")
+    }
+}
+
+/*
+  Attach an event listener for progress updates, used to report progress
+*/
+pub(crate) fn set_progress(image: &VipsImage, handler: &Option<ProgressHandler>) {
+    if let Some(handler) = handler {
+        unsafe {
+            let im = image.as_mut_ptr();
+            let handler = Box::into_raw(Box::new(handler.clone()));
+
+            g_signal_connect_data(
+                im as *mut _,
+                b"eval\0".as_ptr() as *const _,
+                Some(std::mem::transmute::<*const (), unsafe extern "C" fn()>(
+                    vips_report_call_back as *const (),
+                )),
+                handler as *mut _,
+                Some(free_progress),
+                0,
+            );
+
+            image.set_progress(true);
+        }
+    }
+}
+
+/*
+  Event listener for progress updates, used to report progress
+*/
+unsafe extern "C" fn vips_report_call_back(
+    _im: *mut rs_vips::bindings::VipsImage,
+    progress: *mut rs_vips::bindings::VipsProgress,
+    handler: *mut ProgressHandler,
+) {
+    let elapsed = if (*progress).start.is_null() {
+        0.0
+    } else {
+        g_timer_elapsed((*progress).start, std::ptr::null_mut())
+    };
+    let update = Progress {
+        percent: (*progress).percent,
+        pixels: (*progress).npels as _,
+        total_pixels: (*progress).tpels as _,
+        elapsed: Duration::from_secs_f64(elapsed.max(0.0)),
+    };
+
+    // Never unwind into libvips
+    let _ = catch_unwind(AssertUnwindSafe(|| ((*handler).0)(update)));
+}
+
+unsafe extern "C" fn free_progress(data: *mut c_void, _closure: *mut GClosure) {
+    drop(Box::from_raw(data as *mut ProgressHandler));
+}
+
 /*
   Calculate the (left, top) coordinates of the output image
   within the input image, applying the given gravity during an embed.
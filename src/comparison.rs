@@ -0,0 +1,186 @@
+use crate::{
+    common::{ensure_alpha, remove_alpha},
+    input::{create_input_descriptor, open_input, CreateRaw, Input, SharpInput, SharpOptions},
+    operation::ssim,
+    pipeline::init_options,
+    util::VipsGuard,
+    Colour, InvalidParameterError, Sharp, SharpError,
+};
+use rs_vips::{
+    error::Error::OperationError,
+    operator::{Ge, Index},
+    ops::{BandFormat, Interpretation},
+    Result, VipsImage,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    /** Largest difference of a channel, integer 0-255, for a pixel to count as unchanged (optional, default 0) */
+    pub threshold: Option<i32>,
+    /** Colour of changed pixels in the diff image (optional, default red) */
+    pub highlight: Option<Colour>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelComparison {
+    /** Largest absolute difference, 0-255 */
+    pub max_error: f64,
+    /** Mean squared error */
+    pub mse: f64,
+    /** Peak signal-to-noise ratio in decibels, infinite for identical channels */
+    pub psnr: f64,
+}
+
+/**
+ * Differences between two images, as returned by {@link compare}.
+ *
+ * Channels are compared as 8-bit sRGB, with an opaque alpha channel added to an image without one
+ * when the other image has one.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Comparison {
+    pub width: i32,
+    pub height: i32,
+    pub channels: Vec<ChannelComparison>,
+    /** Mean squared error over all channels */
+    pub mse: f64,
+    /** Peak signal-to-noise ratio over all channels in decibels, infinite for identical images */
+    pub psnr: f64,
+    /** Mean structural similarity of the luminance, between 0 and 1 */
+    pub ssim: f64,
+    /** Number of pixels with any channel differing by more than `threshold` */
+    pub different_pixels: u64,
+    #[serde(skip)]
+    diff: Vec<u8>,
+}
+
+impl Comparison {
+    /** Whether the images have no differing pixels. */
+    pub fn is_identical(&self) -> bool {
+        self.different_pixels == 0
+    }
+
+    /**
+     * A faded copy of the first image with every differing pixel in the highlight colour,
+     * ready for any of the output methods.
+     *
+     * @example
+     * let comparison = sharp::compare(Input::path("expected.png"), Input::path("actual.png"))?;
+     * comparison.diff_image()?.png(None)?.to_file("diff.png")?;
+     */
+    pub fn diff_image(&self) -> core::result::Result<Sharp, SharpError> {
+        Sharp::new_from_buffer_with_opts(
+            self.diff.clone(),
+            SharpOptions {
+                raw: Some(CreateRaw {
+                    width: self.width,
+                    height: self.height,
+                    channels: 3,
+                    premultiplied: false,
+                    page_height: None,
+                }),
+                ..Default::default()
+            },
+        )
+    }
+}
+
+/**
+ * Compare two images of the same dimensions pixel by pixel.
+ *
+ * @example
+ * let comparison = sharp::compare(Input::path("expected.png"), Input::path("actual.png"))?;
+ * assert!(comparison.ssim > 0.99);
+ */
+pub fn compare(a: Input, b: Input) -> core::result::Result<Comparison, SharpError> {
+    compare_with_opts(a, b, CompareOptions::default())
+}
+
+pub fn compare_with_opts(
+    a: Input,
+    b: Input,
+    options: CompareOptions,
+) -> core::result::Result<Comparison, SharpError> {
+    let threshold = options.threshold.unwrap_or(0);
+    if !(0..=255).contains(&threshold) {
+        return Err(InvalidParameterError!("threshold", "integer between 0 and 255", threshold));
+    }
+    let highlight = options.highlight.unwrap_or(Colour::rgb(255, 0, 0));
+
+    Sharp::init()?;
+    let mut baton = init_options();
+    let a = create_input_descriptor(SharpInput::Single(a.inner), None, &mut baton)?;
+    let b = create_input_descriptor(SharpInput::Single(b.inner), None, &mut baton)?;
+
+    let _guard = VipsGuard;
+    let (a, _) = open_input(&a)?;
+    let (b, _) = open_input(&b)?;
+    compare_images(a, b, threshold, &highlight.rgba[0..3]).map_err(SharpError::from)
+}
+
+fn compare_images(
+    a: VipsImage,
+    b: VipsImage,
+    threshold: i32,
+    highlight: &[f64],
+) -> Result<Comparison> {
+    if a.get_width() != b.get_width() || a.get_height() != b.get_height() {
+        return Err(OperationError("Images must have the same dimensions".to_string()));
+    }
+
+    let mut a = a.colourspace(Interpretation::Srgb)?.cast(BandFormat::Uchar)?;
+    let mut b = b.colourspace(Interpretation::Srgb)?.cast(BandFormat::Uchar)?;
+    if a.hasalpha() != b.hasalpha() {
+        a = ensure_alpha(a, 1.0)?;
+        b = ensure_alpha(b, 1.0)?;
+    }
+
+    let diff = a.subtract(&b)?.abs()?;
+    let squared = diff.multiply(&diff)?;
+    let mut channels = Vec::new();
+    let mut changed: Option<VipsImage> = None;
+    for band in 0..diff.get_bands() {
+        let mse = squared.at(band).avg()?;
+        channels.push(ChannelComparison {
+            max_error: diff.at(band).max()?,
+            mse,
+            psnr: psnr(mse),
+        });
+
+        let band_changed = diff.at(band).ge(threshold as f64 + 1.0);
+        changed = Some(match changed {
+            Some(changed) => changed | band_changed,
+            None => band_changed,
+        });
+    }
+    let mse = squared.avg()?;
+
+    // Mask is 255 for each changed pixel
+    let changed = changed.ok_or(OperationError("Images have no channels".to_string()))?;
+    let pixels = a.get_width() as f64 * a.get_height() as f64;
+    let different_pixels = (changed.avg()? / 255.0 * pixels).round() as u64;
+
+    let base = remove_alpha(a.copy()?)?.linear(&[0.3], &[178.0])?.cast(BandFormat::Uchar)?;
+    let highlight = VipsImage::new_from_image(&base, highlight)?;
+    let diff_image = changed.ifthenelse(&highlight, &base)?;
+
+    Ok(Comparison {
+        width: a.get_width(),
+        height: a.get_height(),
+        channels,
+        mse,
+        psnr: psnr(mse),
+        ssim: ssim(&a, &b)?,
+        different_pixels,
+        diff: diff_image.write_to_memory(),
+    })
+}
+
+fn psnr(mse: f64) -> f64 {
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+}
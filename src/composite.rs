@@ -4,7 +4,7 @@ use crate::{
     },
     pipeline::Composite,
     resize::Gravity,
-    InvalidParameterError, Sharp,
+    InvalidParameterError, Sharp, SharpError,
 };
 use rs_vips::ops::{BlendMode, FailOn};
 
@@ -43,7 +43,7 @@ pub struct OverlayOptions {
 }
 
 impl Sharp {
-    pub fn composite(mut self, images: &[OverlayOptions]) -> Result<Self, String> {
+    pub fn composite(mut self, images: &[OverlayOptions]) -> Result<Self, SharpError> {
         if images.is_empty() {
             return Err(InvalidParameterError!("images to composite", "array", images));
         }
@@ -72,7 +72,7 @@ impl Sharp {
 
         self.options.composite = composites;
 
-        Ok(self)
+        Ok(self.step())
     }
 }
 
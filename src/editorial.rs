@@ -0,0 +1,491 @@
+use crate::{
+    common::{strip_xmp, xml_tag_len},
+    output::MetadataPolicy,
+};
+use rs_vips::{
+    bindings::{vips_blob_get_type, VIPS_META_IPTC_NAME, VIPS_META_XMP_NAME},
+    Result, VipsImage,
+};
+use serde::{Deserialize, Serialize};
+
+/**
+ * Editorial metadata shared by IPTC IIM and XMP.
+ *
+ * When writing, only the fields that are set, or not empty, replace the existing values.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorialFields {
+    /** IPTC By-line (2:80) or XMP `dc:creator` */
+    pub creator: Vec<String>,
+    /** IPTC Copyright Notice (2:116) or XMP `dc:rights` */
+    pub rights: Option<String>,
+    /** IPTC Caption/Abstract (2:120) or XMP `dc:description` */
+    pub description: Option<String>,
+    /** IPTC Keywords (2:25) or XMP `dc:subject` */
+    pub keywords: Vec<String>,
+    /** IPTC Headline (2:105) or XMP `photoshop:Headline` */
+    pub headline: Option<String>,
+    /** IPTC Credit (2:110) or XMP `photoshop:Credit` */
+    pub credit: Option<String>,
+}
+
+const PHOTOSHOP: &[u8] = b"Photoshop 3.0\0";
+const IPTC_RESOURCE: u16 = 0x0404;
+
+// IIM record 1
+const CODED_CHARACTER_SET: u8 = 90;
+// IIM record 2
+const RECORD_VERSION: u8 = 0;
+const KEYWORDS: u8 = 25;
+const BY_LINE: u8 = 80;
+const HEADLINE: u8 = 105;
+const CREDIT: u8 = 110;
+const COPYRIGHT_NOTICE: u8 = 116;
+const CAPTION: u8 = 120;
+
+// Maximum length in bytes of each dataset of record 2
+const MAX_LENGTHS: [(u8, usize); 6] = [
+    (KEYWORDS, 64),
+    (BY_LINE, 32),
+    (HEADLINE, 256),
+    (CREDIT, 32),
+    (COPYRIGHT_NOTICE, 128),
+    (CAPTION, 2000),
+];
+
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+const PHOTOSHOP_NAMESPACE: &str = "http://ns.adobe.com/photoshop/1.0/";
+
+impl EditorialFields {
+    /*
+      IIM datasets of record 2 for the fields that are set, truncated to the maximum length of each dataset
+    */
+    fn datasets(&self) -> Vec<(u8, String)> {
+        let mut datasets = Vec::new();
+        datasets.extend(self.creator.iter().map(|creator| (BY_LINE, creator.clone())));
+        datasets.extend(self.rights.iter().map(|rights| (COPYRIGHT_NOTICE, rights.clone())));
+        datasets.extend(self.description.iter().map(|description| (CAPTION, description.clone())));
+        datasets.extend(self.keywords.iter().map(|keyword| (KEYWORDS, keyword.clone())));
+        datasets.extend(self.headline.iter().map(|headline| (HEADLINE, headline.clone())));
+        datasets.extend(self.credit.iter().map(|credit| (CREDIT, credit.clone())));
+        datasets.into_iter().map(|(dataset, value)| (dataset, truncate(value, dataset))).collect()
+    }
+
+    /*
+      XMP properties for the fields that are set, by name
+    */
+    fn properties(&self) -> Vec<(&'static str, String)> {
+        let items = |kind: &str, values: &[String], lang: &str| {
+            let items: String = values
+                .iter()
+                .map(|value| format!("<rdf:li{}>{}</rdf:li>", lang, escape(value)))
+                .collect();
+            format!("<rdf:{}>{}</rdf:{}>", kind, items, kind)
+        };
+        let alt = |value: &Option<String>| {
+            value
+                .as_ref()
+                .map(|value| items("Alt", std::slice::from_ref(value), " xml:lang=\"x-default\""))
+        };
+
+        let mut properties = Vec::new();
+        if !self.creator.is_empty() {
+            properties.push(("dc:creator", items("Seq", &self.creator, "")));
+        }
+        if let Some(rights) = alt(&self.rights) {
+            properties.push(("dc:rights", rights));
+        }
+        if let Some(description) = alt(&self.description) {
+            properties.push(("dc:description", description));
+        }
+        if !self.keywords.is_empty() {
+            properties.push(("dc:subject", items("Bag", &self.keywords, "")));
+        }
+        if let Some(headline) = &self.headline {
+            properties.push(("photoshop:Headline", escape(headline)));
+        }
+        if let Some(credit) = &self.credit {
+            properties.push(("photoshop:Credit", escape(credit)));
+        }
+        properties
+    }
+}
+
+/*
+  Editorial fields of an IPTC block, either raw IIM or within Photoshop image resources
+*/
+pub(crate) fn parse_iptc(blob: &[u8]) -> EditorialFields {
+    let mut fields = EditorialFields::default();
+
+    for (record, dataset, value) in iim_datasets(&iim(blob)) {
+        if record != 2 {
+            continue;
+        }
+        let value = decode_text(value);
+        match dataset {
+            BY_LINE => fields.creator.push(value),
+            COPYRIGHT_NOTICE => fields.rights = Some(value),
+            CAPTION => fields.description = Some(value),
+            KEYWORDS => fields.keywords.push(value),
+            HEADLINE => fields.headline = Some(value),
+            CREDIT => fields.credit = Some(value),
+            _ => {}
+        }
+    }
+
+    fields
+}
+
+/*
+  Editorial fields of an XMP packet
+*/
+pub(crate) fn parse_xmp(xmp: &str) -> EditorialFields {
+    let first = |name: &str| xmp_values(xmp, name).into_iter().next();
+
+    EditorialFields {
+        creator: xmp_values(xmp, "dc:creator"),
+        rights: first("dc:rights"),
+        description: first("dc:description"),
+        keywords: xmp_values(xmp, "dc:subject"),
+        headline: first("photoshop:Headline"),
+        credit: first("photoshop:Credit"),
+    }
+}
+
+/*
+  Merge the fields into the IPTC block of the image, creating one if needed.
+  TIFF holds raw IIM, other formats hold it within Photoshop image resources.
+*/
+pub(crate) fn set_iptc_fields(
+    image: VipsImage,
+    fields: &EditorialFields,
+    raw: bool,
+) -> Result<VipsImage> {
+    let image = image.copy()?;
+    let existing = if image.get_typeof(VIPS_META_IPTC_NAME)? == unsafe { vips_blob_get_type() } {
+        image.get_blob(VIPS_META_IPTC_NAME)?
+    } else {
+        Vec::new()
+    };
+
+    let iptc = merge_iptc(&existing, fields, raw);
+    image.set_blob(VIPS_META_IPTC_NAME, &iptc)?;
+    Ok(image)
+}
+
+/*
+  Merge the fields into the XMP packet of the image, creating one if needed
+*/
+pub(crate) fn set_xmp_fields(image: VipsImage, fields: &EditorialFields) -> Result<VipsImage> {
+    let image = image.copy()?;
+    let existing = if image.get_typeof(VIPS_META_XMP_NAME)? == unsafe { vips_blob_get_type() } {
+        String::from_utf8_lossy(&image.get_blob(VIPS_META_XMP_NAME)?).into_owned()
+    } else {
+        String::new()
+    };
+
+    let xmp = merge_xmp(&existing, fields);
+    image.set_blob(VIPS_META_XMP_NAME, xmp.as_bytes())?;
+    Ok(image)
+}
+
+fn merge_iptc(blob: &[u8], fields: &EditorialFields, raw: bool) -> Vec<u8> {
+    let replaced = fields.datasets();
+    let mut datasets: Vec<(u8, u8, Vec<u8>)> = iim_datasets(&iim(blob))
+        .into_iter()
+        .filter(|(record, dataset, _)| match *record {
+            1 => *dataset != CODED_CHARACTER_SET,
+            2 => !replaced.iter().any(|(replaced, _)| replaced == dataset),
+            _ => true,
+        })
+        .map(|(record, dataset, value)| (record, dataset, value.to_vec()))
+        .collect();
+
+    // Values are written as UTF-8
+    datasets.push((1, CODED_CHARACTER_SET, b"\x1b%G".to_vec()));
+    if !datasets.iter().any(|(record, dataset, _)| *record == 2 && *dataset == RECORD_VERSION) {
+        datasets.push((2, RECORD_VERSION, vec![0, 4]));
+    }
+    datasets.extend(replaced.into_iter().map(|(dataset, value)| (2, dataset, value.into_bytes())));
+    // Records in order, with the record version first in record 2
+    datasets.sort_by_key(|(record, dataset, _)| (*record, *dataset != RECORD_VERSION));
+
+    let mut iim = Vec::new();
+    for (record, dataset, value) in datasets {
+        iim.extend_from_slice(&[0x1c, record, dataset]);
+        if value.len() < 0x8000 {
+            iim.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        } else {
+            // Extended dataset with a 4 byte length
+            iim.extend_from_slice(&[0x80, 0x04]);
+            iim.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        }
+        iim.extend_from_slice(&value);
+    }
+    if raw {
+        return iim;
+    }
+
+    let mut resources = photoshop_resources(blob);
+    match resources.iter_mut().find(|(id, _, _)| *id == IPTC_RESOURCE) {
+        Some(resource) => resource.2 = iim,
+        None => resources.push((IPTC_RESOURCE, vec![0, 0], iim)),
+    }
+
+    let mut data = PHOTOSHOP.to_vec();
+    for (id, name, value) in resources {
+        data.extend_from_slice(b"8BIM");
+        data.extend_from_slice(&id.to_be_bytes());
+        data.extend_from_slice(&name);
+        data.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        data.extend_from_slice(&value);
+        if value.len() % 2 == 1 {
+            data.push(0);
+        }
+    }
+    data
+}
+
+fn merge_xmp(xmp: &str, fields: &EditorialFields) -> String {
+    let properties = fields.properties();
+    let description = format!(
+        "<rdf:Description rdf:about=\"\" xmlns:dc=\"{}\" xmlns:photoshop=\"{}\">\n{}</rdf:Description>\n",
+        DC_NAMESPACE,
+        PHOTOSHOP_NAMESPACE,
+        properties
+            .iter()
+            .map(|(name, value)| format!("<{}>{}</{}>\n", name, value, name))
+            .collect::<String>()
+    );
+
+    if xmp.contains("</rdf:RDF>") {
+        // Remove the existing values of the fields, then add them in a description of their own
+        let policy = MetadataPolicy {
+            deny: properties.iter().map(|(name, _)| name.to_string()).collect(),
+            ..Default::default()
+        };
+        let stripped = strip_xmp(xmp, &policy);
+        if let Some(index) = stripped.rfind("</rdf:RDF>") {
+            return format!("{}{}{}", &stripped[..index], description, &stripped[index..]);
+        }
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         {}</rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>",
+        description
+    )
+}
+
+/*
+  IIM data of an IPTC block
+*/
+fn iim(blob: &[u8]) -> Vec<u8> {
+    if blob.starts_with(PHOTOSHOP) {
+        photoshop_resources(blob)
+            .into_iter()
+            .find(|(id, _, _)| *id == IPTC_RESOURCE)
+            .map(|(_, _, value)| value)
+            .unwrap_or_default()
+    } else {
+        blob.to_vec()
+    }
+}
+
+/*
+  Photoshop image resources as id, padded Pascal name and data
+*/
+fn photoshop_resources(blob: &[u8]) -> Vec<(u16, Vec<u8>, Vec<u8>)> {
+    let mut resources = Vec::new();
+    if !blob.starts_with(PHOTOSHOP) {
+        return resources;
+    }
+
+    let mut offset = PHOTOSHOP.len();
+    while offset + 12 <= blob.len() && &blob[offset..offset + 4] == b"8BIM" {
+        let id = u16::from_be_bytes([blob[offset + 4], blob[offset + 5]]);
+        offset += 6;
+        // Length byte and name, padded to an even size
+        let name_len = (blob[offset] as usize + 2) & !1;
+        if offset + name_len + 4 > blob.len() {
+            break;
+        }
+        let name = blob[offset..offset + name_len].to_vec();
+        offset += name_len;
+        let size = u32::from_be_bytes([
+            blob[offset],
+            blob[offset + 1],
+            blob[offset + 2],
+            blob[offset + 3],
+        ]) as usize;
+        offset += 4;
+        if offset + size > blob.len() {
+            break;
+        }
+        resources.push((id, name, blob[offset..offset + size].to_vec()));
+        offset += size + size % 2;
+    }
+
+    resources
+}
+
+/*
+  IIM datasets as record, dataset and value
+*/
+fn iim_datasets(iim: &[u8]) -> Vec<(u8, u8, &[u8])> {
+    let mut datasets = Vec::new();
+
+    let mut offset = 0;
+    while offset + 5 <= iim.len() && iim[offset] == 0x1c {
+        let (record, dataset) = (iim[offset + 1], iim[offset + 2]);
+        let mut length = u16::from_be_bytes([iim[offset + 3], iim[offset + 4]]) as usize;
+        offset += 5;
+        if length & 0x8000 != 0 {
+            // Extended dataset, the length is held in the following bytes
+            let size = length & 0x7fff;
+            if size > 4 || offset + size > iim.len() {
+                break;
+            }
+            length = iim[offset..offset + size]
+                .iter()
+                .fold(0, |length, byte| (length << 8) | *byte as usize);
+            offset += size;
+        }
+        if offset + length > iim.len() {
+            break;
+        }
+        datasets.push((record, dataset, &iim[offset..offset + length]));
+        offset += length;
+    }
+
+    datasets
+}
+
+/*
+  UTF-8, falling back to Latin-1 for older files
+*/
+fn decode_text(value: &[u8]) -> String {
+    match std::str::from_utf8(value) {
+        Ok(text) => text.to_string(),
+        Err(_) => value.iter().map(|byte| *byte as char).collect(),
+    }
+}
+
+/*
+  Values of an XMP property, from the items of its array, its text or an attribute of its description
+*/
+fn xmp_values(xmp: &str, name: &str) -> Vec<String> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    for (start, _) in xmp.match_indices(&open) {
+        // Skip longer names with the same prefix, e.g. dc:creatorTool
+        if !xmp[start + open.len()..]
+            .starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        {
+            continue;
+        }
+        let content_start = start + xml_tag_len(&xmp[start..]);
+        if xmp[..content_start].ends_with("/>") {
+            continue;
+        }
+        let content = match xmp[content_start..].find(&close) {
+            Some(len) => &xmp[content_start..content_start + len],
+            None => continue,
+        };
+
+        let mut items = Vec::new();
+        let mut rest = content;
+        while let Some(item_start) = rest.find("<rdf:li") {
+            let item = &rest[item_start..];
+            let text_start = xml_tag_len(item);
+            let text_len = match item[text_start..].find("</rdf:li>") {
+                Some(len) => len,
+                None => break,
+            };
+            items.push(unescape(&item[text_start..text_start + text_len]));
+            rest = &item[text_start + text_len..];
+        }
+        if items.is_empty() && !content.trim().is_empty() {
+            items.push(unescape(content.trim()));
+        }
+        return items;
+    }
+
+    let attribute = format!("{}=", name);
+    for (start, _) in xmp.match_indices(&attribute) {
+        if !xmp[..start].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let value = &xmp[start + attribute.len()..];
+        let quote = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => continue,
+        };
+        if let Some(len) = value[1..].find(quote) {
+            return vec![unescape(&value[1..1 + len])];
+        }
+    }
+
+    Vec::new()
+}
+
+/*
+  Shorten a value to the maximum length of its dataset, on a character boundary
+*/
+fn truncate(mut value: String, dataset: u8) -> String {
+    if let Some((_, max)) = MAX_LENGTHS.iter().find(|(id, _)| *id == dataset) {
+        if value.len() > *max {
+            let end = (0..=*max).rev().find(|index| value.is_char_boundary(*index)).unwrap_or(0);
+            value.truncate(end);
+        }
+    }
+    value
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let character = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity
+                    .strip_prefix('#')
+                    .and_then(|decimal| decimal.parse().ok())
+                    .and_then(char::from_u32),
+            },
+        };
+        match character {
+            Some(character) => {
+                output.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
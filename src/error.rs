@@ -0,0 +1,105 @@
+use rs_vips::bindings::vips_error_buffer;
+use std::{ffi::CStr, fmt};
+
+/**
+ * Error returned by every fallible `Sharp` method.
+ *
+ * Errors raised by libvips carry the text of the libvips error buffer at the time of failure.
+ *
+ * @example
+ * match Sharp::new_from_file("input.jpg")?.blur(Some(options))?.to_buffer() {
+ *   Ok(data) => ...,
+ *   Err(SharpError::Timeout(_)) => ...,
+ *   Err(SharpError::InvalidParameter { name, .. }) => ...,
+ *   Err(e) => ...,
+ * }
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharpError {
+    /** A builder method received a value outside of its accepted range. */
+    InvalidParameter {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    /** The input or requested output format is unknown or unsupported by the installed libvips. */
+    UnsupportedFormat(String),
+    /** The input image could not be decoded. */
+    Decode(String),
+    /** The output image could not be encoded. */
+    Encode(String),
+    /** Processing was stopped after `timeout` seconds. */
+    Timeout(String),
+    /** Processing was stopped through a `CancellationToken` or by dropping an async output future. */
+    Cancelled(String),
+    /** The input image exceeds `limit_input_pixels`, or output cannot be encoded within a byte budget. */
+    LimitExceeded(String),
+    /** Reading from or writing to the filesystem failed. */
+    Io(String),
+    /** Any other libvips operation failure or invalid combination of options. */
+    Operation(String),
+}
+
+impl SharpError {
+    /** Text describing the error, without the variant name. */
+    pub fn message(&self) -> String {
+        match self {
+            SharpError::InvalidParameter {
+                name,
+                expected,
+                actual,
+            } => format!("Expected {:?} for {:?} but received {}", expected, name, actual),
+            SharpError::UnsupportedFormat(message)
+            | SharpError::Decode(message)
+            | SharpError::Encode(message)
+            | SharpError::Timeout(message)
+            | SharpError::Cancelled(message)
+            | SharpError::LimitExceeded(message)
+            | SharpError::Io(message)
+            | SharpError::Operation(message) => message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for SharpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for SharpError {}
+
+impl From<rs_vips::error::Error> for SharpError {
+    fn from(error: rs_vips::error::Error) -> Self {
+        vips_error(SharpError::Operation, error)
+    }
+}
+
+impl From<std::io::Error> for SharpError {
+    fn from(error: std::io::Error) -> Self {
+        SharpError::Io(error.to_string())
+    }
+}
+
+/*
+  Convert a libvips error into the variant matching where it was detected,
+  picking up the libvips error text before VipsGuard clears it
+*/
+pub(crate) fn vips_error(
+    variant: fn(String) -> SharpError,
+    error: rs_vips::error::Error,
+) -> SharpError {
+    let buffer = unsafe {
+        let ptr = vips_error_buffer();
+        if ptr.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(ptr).to_string_lossy().trim_end().to_string()
+        }
+    };
+    if buffer.is_empty() {
+        variant(error.to_string())
+    } else {
+        variant(format!("{}\n{}", error, buffer))
+    }
+}
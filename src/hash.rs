@@ -0,0 +1,173 @@
+use crate::{
+    common::{exif_orientation, InputDescriptor},
+    input::open_input,
+    operation::{flatten, luminance},
+    pipeline::calculate_exif_rotation_and_flop,
+    util::VipsGuard,
+    Sharp, SharpError,
+};
+use rs_vips::{
+    error::Error::OperationError,
+    ops::{Angle, Direction},
+    voption::{Setter, VOption},
+    Result, VipsImage,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashKind {
+    /** Pixels of an 8x8 thumbnail compared with their mean, 64 bits */
+    Average,
+    /** Neighbouring pixels of a 9x8 thumbnail compared with each other, 64 bits */
+    Difference,
+    /** Low frequencies of the DCT of a 32x32 thumbnail compared with their median, 64 bits */
+    Perceptual,
+    /** Average hash of a 16x16 thumbnail, 256 bits */
+    Average256,
+    /** Difference hash of a 17x16 thumbnail, 256 bits */
+    Difference256,
+    /** Perceptual hash of a 64x64 thumbnail, 256 bits */
+    Perceptual256,
+}
+
+impl HashKind {
+    /*
+      Side of the square of bits
+    */
+    fn size(&self) -> usize {
+        match self {
+            HashKind::Average | HashKind::Difference | HashKind::Perceptual => 8,
+            HashKind::Average256 | HashKind::Difference256 | HashKind::Perceptual256 => 16,
+        }
+    }
+}
+
+impl Sharp {
+    /**
+     * Compute a perceptual hash of the input image, for finding near-duplicates with {@link hamming_distance}.
+     *
+     * The image is auto-oriented, flattened onto white and converted to greyscale before hashing,
+     * without processing the operations of the pipeline or encoding any output.
+     *
+     * Returns 8 bytes for 64-bit hashes and 32 bytes for 256-bit hashes, most significant bit first.
+     *
+     * @example
+     * let a = Sharp::new_from_file("a.jpg")?.perceptual_hash(HashKind::Perceptual)?;
+     * let b = Sharp::new_from_file("b.jpg")?.perceptual_hash(HashKind::Perceptual)?;
+     * let similar = hamming_distance(&a, &b) <= 10;
+     */
+    pub fn perceptual_hash(&self, kind: HashKind) -> core::result::Result<Vec<u8>, SharpError> {
+        let _guard = VipsGuard;
+
+        get_hash(&self.options.input, kind)
+    }
+}
+
+/**
+ * Number of bits that differ between two hashes of the same kind.
+ *
+ * Any bytes beyond the end of the shorter hash count as differing in every bit.
+ */
+pub fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
+    let common: u32 = a.iter().zip(b.iter()).map(|(a, b)| (a ^ b).count_ones()).sum();
+    common + 8 * a.len().abs_diff(b.len()) as u32
+}
+
+fn get_hash(input: &InputDescriptor, kind: HashKind) -> core::result::Result<Vec<u8>, SharpError> {
+    let (mut image, _) = open_input(input)?;
+
+    // Respect EXIF Orientation
+    let (rotation, flop) = calculate_exif_rotation_and_flop(exif_orientation(&image));
+    if rotation != Angle::D0 {
+        image = image.rot(rotation)?;
+    }
+    if flop {
+        image = image.flip(Direction::Horizontal)?;
+    }
+    if image.hasalpha() {
+        image = flatten(image, &[255.0, 255.0, 255.0])?;
+    }
+    let grey = luminance(&image)?;
+
+    let size = kind.size();
+    let bits = match kind {
+        HashKind::Average | HashKind::Average256 => {
+            let pixels = thumbnail(&grey, size, size)?;
+            let mean = pixels.iter().sum::<f64>() / pixels.len() as f64;
+            pixels.iter().map(|pixel| *pixel > mean).collect::<Vec<bool>>()
+        }
+        HashKind::Difference | HashKind::Difference256 => {
+            let pixels = thumbnail(&grey, size + 1, size)?;
+            pixels
+                .chunks(size + 1)
+                .flat_map(|row| row.windows(2).map(|pair| pair[0] < pair[1]))
+                .collect()
+        }
+        HashKind::Perceptual | HashKind::Perceptual256 => {
+            let side = size * 4;
+            let pixels = thumbnail(&grey, side, side)?;
+            let coefficients = dct_low_frequencies(&pixels, side, size);
+            // Median without the DC coefficient, which only reflects overall brightness
+            let mut sorted = coefficients[1..].to_vec();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            let median = sorted[sorted.len() / 2];
+            coefficients.iter().map(|coefficient| *coefficient > median).collect()
+        }
+    };
+
+    Ok(bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |value, bit| (value << 1) | *bit as u8))
+        .collect())
+}
+
+/*
+  Pixels of a greyscale float image resized to exactly width x height, row by row
+*/
+fn thumbnail(image: &VipsImage, width: usize, height: usize) -> Result<Vec<f64>> {
+    let resized = image.resize_with_opts(
+        width as f64 / image.get_width() as f64,
+        VOption::new().set("vscale", height as f64 / image.get_height() as f64),
+    )?;
+
+    if resized.get_width() != width as i32 || resized.get_height() != height as i32 {
+        return Err(OperationError("Unable to resize image for hashing".to_string()));
+    }
+
+    Ok(resized
+        .write_to_memory()
+        .chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64)
+        .collect())
+}
+
+/*
+  Top-left size x size coefficients of the 2D DCT-II of a side x side block
+*/
+fn dct_low_frequencies(pixels: &[f64], side: usize, size: usize) -> Vec<f64> {
+    let n = side as f64;
+    let cosines: Vec<f64> = (0..size * side)
+        .map(|i| {
+            let (u, x) = (i / side, i % side);
+            ((2.0 * x as f64 + 1.0) * u as f64 * std::f64::consts::PI / (2.0 * n)).cos()
+        })
+        .collect();
+
+    // Rows first, then columns
+    let mut rows = vec![0.0; side * size];
+    for y in 0..side {
+        for u in 0..size {
+            rows[y * size + u] =
+                (0..side).map(|x| pixels[y * side + x] * cosines[u * side + x]).sum();
+        }
+    }
+    let mut coefficients = vec![0.0; size * size];
+    for v in 0..size {
+        for u in 0..size {
+            coefficients[v * size + u] =
+                (0..side).map(|y| rows[y * size + u] * cosines[v * side + y]).sum();
+        }
+    }
+    coefficients
+}
@@ -0,0 +1,144 @@
+use crate::{
+    common::{remove_alpha, ImageType},
+    input::open_input,
+    pipeline, InvalidParameterError, Sharp, SharpError,
+};
+use rs_vips::{
+    operator::Index,
+    ops::{Access, BandFormat, Interpretation},
+    Result, VipsImage,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default)]
+pub struct HistogramOptions {
+    /** Number of bins per channel, integer between 1 and 256 (optional, default 256) */
+    pub bins: Option<i32>,
+    /** Also count the luminance of each pixel (optional, default false) */
+    pub luminance: Option<bool>,
+    /** Weight each pixel by its alpha value, so that transparent pixels are not counted (optional, default false) */
+    pub alpha_weighted: Option<bool>,
+    /** Count the image after the operations of the pipeline instead of the input (optional, default false) */
+    pub processed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Histogram {
+    /** Number of bins per channel, each covering an equal share of the range 0-255 */
+    pub bins: i32,
+    /** Counts of each colour channel in sRGB or greyscale, excluding alpha */
+    pub channels: Vec<Vec<f64>>,
+    /** Counts of the luminance, when requested */
+    pub luminance: Option<Vec<f64>>,
+}
+
+impl Sharp {
+    /**
+     * Count the 8-bit sRGB (or greyscale) values of each channel of the image into bins.
+     *
+     * Counts are the number of pixels in each bin, or the sum of their alpha values scaled to 0-1 when `alpha_weighted` is set.
+     *
+     * By default the input image is counted without processing the operations of the pipeline.
+     * Set `processed` to count the image that would be encoded instead, for example to draw the histogram of an edited image.
+     *
+     * @example
+     * let histogram = Sharp::new_from_file("input.jpg")?.histogram(Some(HistogramOptions {
+     *   bins: Some(64),
+     *   luminance: Some(true),
+     *   ..Default::default()
+     * }))?;
+     * let red = &histogram.channels[0];
+     */
+    pub fn histogram(
+        &self,
+        options: Option<HistogramOptions>,
+    ) -> core::result::Result<Histogram, SharpError> {
+        let options = options.unwrap_or_default();
+        let bins = options.bins.unwrap_or(256);
+        if !(1..=256).contains(&bins) {
+            return Err(InvalidParameterError!("bins", "integer between 1 and 256", bins));
+        }
+        let luminance = options.luminance.unwrap_or(false);
+        let alpha_weighted = options.alpha_weighted.unwrap_or(false);
+
+        if options.processed.unwrap_or(false) {
+            let decoded = pipeline::decode(self.options.clone())?;
+            return get_histogram(&decoded.output_image()?, bins, luminance, alpha_weighted)
+                .map_err(SharpError::from);
+        }
+
+        let _guard = crate::util::VipsGuard;
+
+        let mut input_option = self.options.input.clone();
+        input_option.access = Access::Random;
+
+        let (image, image_type) = open_input(&input_option)?;
+        if image_type == ImageType::UNKNOWN {
+            return Ok(Histogram {
+                bins,
+                ..Default::default()
+            });
+        }
+        get_histogram(&image, bins, luminance, alpha_weighted).map_err(SharpError::from)
+    }
+}
+
+fn get_histogram(
+    image: &VipsImage,
+    bins: i32,
+    luminance: bool,
+    alpha_weighted: bool,
+) -> Result<Histogram> {
+    // Count greyscale images as a single channel
+    let interpretation = if remove_alpha(image.copy()?)?.get_bands() == 1 {
+        Interpretation::BW
+    } else {
+        Interpretation::Srgb
+    };
+    let image = image.colourspace(interpretation)?.cast(BandFormat::Uchar)?;
+
+    let alpha = if alpha_weighted && image.hasalpha() {
+        Some(image.at(image.get_bands() - 1))
+    } else {
+        None
+    };
+    let colour = remove_alpha(image)?;
+
+    let mut histogram = Histogram {
+        bins,
+        ..Default::default()
+    };
+    for band in 0..colour.get_bands() {
+        histogram.channels.push(count(&colour.at(band), alpha.as_ref(), bins)?);
+    }
+    if luminance {
+        let grey = colour.colourspace(Interpretation::BW)?.at(0).cast(BandFormat::Uchar)?;
+        histogram.luminance = Some(count(&grey, alpha.as_ref(), bins)?);
+    }
+
+    Ok(histogram)
+}
+
+/*
+  Count the values of a single uchar band, optionally summing alpha instead of pixels,
+  then merge the 256 values into bins
+*/
+fn count(band: &VipsImage, alpha: Option<&VipsImage>, bins: i32) -> Result<Vec<f64>> {
+    let hist = match alpha {
+        Some(alpha) => alpha.hist_find_indexed(band)?.linear(&[1.0 / 255.0], &[0.0])?,
+        None => band.hist_find()?,
+    };
+    let counts: Vec<f64> = hist
+        .cast(BandFormat::Double)?
+        .write_to_memory()
+        .chunks_exact(8)
+        .map(|bytes| f64::from_ne_bytes(bytes.try_into().unwrap_or_default()))
+        .collect();
+
+    let bins = bins as usize;
+    let mut binned = vec![0.0; bins];
+    for (value, count) in counts.iter().enumerate() {
+        binned[(value * bins / counts.len()).min(bins - 1)] += count;
+    }
+    Ok(binned)
+}
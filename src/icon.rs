@@ -4,7 +4,7 @@ use crate::{
     common::Canvas,
     input::{CreateRaw, SharpOptions},
     pipeline::{self, PipelineBaton},
-    Sharp,
+    Sharp, SharpError,
 };
 use std::path::Path;
 
@@ -13,9 +13,9 @@ impl Sharp {
         self,
         file_out: P,
         sizes: Option<Vec<u16>>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         if !self.options.join.is_empty() {
-            return Err("Multiple input is not supported".to_string());
+            return Err(SharpError::Operation("Multiple input is not supported".to_string()));
         }
 
         let icon_sizes = if let Some(sizes) = sizes {
@@ -24,16 +24,16 @@ impl Sharp {
             vec![256, 128, 64, 48, 32, 24, 16]
         };
 
-        let buffers = Self::to_png_buffer(&self.options, icon_sizes).unwrap();
+        let buffers = Self::to_png_buffer(&self.options, icon_sizes)?;
 
         let icon = encode(buffers)?;
 
-        std::fs::write(file_out.as_ref(), icon).map_err(|e| e.to_string())?;
+        std::fs::write(file_out.as_ref(), icon)?;
 
         Ok(self)
     }
 
-    fn to_png_buffer(options: &PipelineBaton, sizes: Vec<u16>) -> Result<Vec<Vec<u8>>, String> {
+    fn to_png_buffer(options: &PipelineBaton, sizes: Vec<u16>) -> Result<Vec<Vec<u8>>, SharpError> {
         let mut images = Vec::new();
         for size in sizes {
             let mut options = options.clone();
@@ -44,26 +44,26 @@ impl Sharp {
             // Fit "contain"
             options.canvas = Canvas::Embed;
             options.resize_background = vec![0.0, 0.0, 0.0, 0.0];
-            let baton = pipeline::pipline(options).map_err(|e| e.to_string())?;
+            let baton = pipeline::pipline(options)?;
             images.push(baton.buffer_out);
         }
         Ok(images)
     }
 
-    pub fn from_icon_file<P: AsRef<Path>>(icon_file: P) -> Result<Self, String> {
-        let buffer = std::fs::read(icon_file.as_ref()).map_err(|e| e.to_string())?;
+    pub fn from_icon_file<P: AsRef<Path>>(icon_file: P) -> Result<Self, SharpError> {
+        let buffer = std::fs::read(icon_file.as_ref())?;
         let entries = decode(buffer)?;
         Self::from_icon(entries)
     }
 
-    pub fn from_icon_buffer(buffer: Vec<u8>) -> Result<Self, String> {
+    pub fn from_icon_buffer(buffer: Vec<u8>) -> Result<Self, SharpError> {
         let entries = decode(buffer)?;
         Self::from_icon(entries)
     }
 
-    fn from_icon(entries: Vec<IconEntry>) -> Result<Self, String> {
+    fn from_icon(entries: Vec<IconEntry>) -> Result<Self, SharpError> {
         if entries.is_empty() {
-            return Err("Not icon data found".to_string());
+            return Err(SharpError::Decode("Not icon data found".to_string()));
         }
 
         let buffers: Vec<Vec<u8>> = entries.iter().map(|entry| entry.image_data.clone()).collect();
@@ -105,7 +105,7 @@ struct IconEntry {
     image_type: String,
 }
 
-fn encode(image_buffers: Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
+fn encode(image_buffers: Vec<Vec<u8>>) -> Result<Vec<u8>, SharpError> {
     let mut icon_buffer: Vec<u8> = Vec::new();
     let mut buffer_offset = 0u32;
 
@@ -144,7 +144,7 @@ fn encode(image_buffers: Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
     Ok(icon_buffer)
 }
 
-fn write_icon_entry(image_data: &[u8]) -> Result<Vec<u8>, String> {
+fn write_icon_entry(image_data: &[u8]) -> Result<Vec<u8>, SharpError> {
     if is_png(image_data) {
         write_icon_entry_png(image_data)
     } else {
@@ -152,11 +152,11 @@ fn write_icon_entry(image_data: &[u8]) -> Result<Vec<u8>, String> {
     }
 }
 
-fn write_icon_entry_png(image_data: &[u8]) -> Result<Vec<u8>, String> {
+fn write_icon_entry_png(image_data: &[u8]) -> Result<Vec<u8>, SharpError> {
     let mut buffer: Vec<u8> = Vec::new();
     if image_data[12] != 73 && image_data[13] != 72 && image_data[14] != 68 && image_data[15] != 82
     {
-        return Err("PNG's first chunk must be an IHDR".to_string());
+        return Err(SharpError::Encode("PNG's first chunk must be an IHDR".to_string()));
     }
     let mut width = read_u32_be(image_data, 16);
     let mut height = read_u32_be(image_data, 20);
@@ -170,7 +170,9 @@ fn write_icon_entry_png(image_data: &[u8]) -> Result<Vec<u8>, String> {
             && image_data[31].to_string() != "T"
             && image_data[32].to_string() != "E"
         {
-            return Err("PNG's second chunk must be a PLTE if indexed".to_string());
+            return Err(SharpError::Encode(
+                "PNG's second chunk must be a PLTE if indexed".to_string(),
+            ));
         }
 
         color_entries = (read_u32_be(image_data, 25) / 3) as _;
@@ -178,12 +180,12 @@ fn write_icon_entry_png(image_data: &[u8]) -> Result<Vec<u8>, String> {
 
     // Do some validation
     if width > 256 {
-        return Err("PNG width must not exceed 256".to_string());
+        return Err(SharpError::Encode("PNG width must not exceed 256".to_string()));
     } else if width == 256 {
         width = 0
     }
     if height > 256 {
-        return Err("PNG height must not exceed 256".to_string());
+        return Err(SharpError::Encode("PNG height must not exceed 256".to_string()));
     } else if height == 256 {
         height = 0
     }
@@ -206,7 +208,7 @@ fn write_icon_entry_png(image_data: &[u8]) -> Result<Vec<u8>, String> {
     Ok(buffer)
 }
 
-fn write_icon_entry_bmp(image_data: &[u8]) -> Result<Vec<u8>, String> {
+fn write_icon_entry_bmp(image_data: &[u8]) -> Result<Vec<u8>, SharpError> {
     let mut buffer = Vec::with_capacity(16);
     // Get information
     let mut width = read_u32_le(image_data, 18);
@@ -218,18 +220,18 @@ fn write_icon_entry_bmp(image_data: &[u8]) -> Result<Vec<u8>, String> {
 
     // Do some validation
     if width > 256 {
-        return Err("BMP width must not exceed 256".to_string());
+        return Err(SharpError::Encode("BMP width must not exceed 256".to_string()));
     } else if width == 256 {
         width = 0;
     }
     if height > 256 {
-        return Err("BMP height must not exceed 256".to_string());
+        return Err(SharpError::Encode("BMP height must not exceed 256".to_string()));
     } else if height == 256 {
         height = 0;
     }
 
     if color_planes != 1 {
-        return Err("BMP color planes must be 1".to_string());
+        return Err(SharpError::Encode("BMP color planes must be 1".to_string()));
     }
 
     if color_entries == 0 && bits_per_pixel != 32 {
@@ -260,20 +262,20 @@ fn write_icon_entry_bmp(image_data: &[u8]) -> Result<Vec<u8>, String> {
     Ok(buffer)
 }
 
-fn decode(buffer: Vec<u8>) -> Result<Vec<IconEntry>, String> {
+fn decode(buffer: Vec<u8>) -> Result<Vec<IconEntry>, SharpError> {
     let mut offset = 0;
 
     let buf = read_u16_le(buffer.as_slice(), offset);
     offset += 2;
     if buf != 0 {
-        return Err("Reserved must be 0".to_string());
+        return Err(SharpError::Decode("Reserved must be 0".to_string()));
     }
     let buf = read_u16_le(buffer.as_slice(), offset);
     offset += 2;
     let type_ = if buf == 1 || buf == 2 {
         buf
     } else {
-        return Err("image type must be ICO or CUR".to_string());
+        return Err(SharpError::Decode("image type must be ICO or CUR".to_string()));
     };
     let buf = read_u16_le(buffer.as_slice(), offset);
     offset += 2;
@@ -288,7 +290,7 @@ fn decode(buffer: Vec<u8>) -> Result<Vec<IconEntry>, String> {
     Ok(icon_entries)
 }
 
-fn read_icon_entry(buffer: &[u8], offset: &mut usize, type_: u16) -> Result<IconEntry, String> {
+fn read_icon_entry(buffer: &[u8], offset: &mut usize, type_: u16) -> Result<IconEntry, SharpError> {
     let mut entry = IconEntry::default();
 
     // Read width
@@ -317,7 +319,7 @@ fn read_icon_entry(buffer: &[u8], offset: &mut usize, type_: u16) -> Result<Icon
     let buf = buffer[*offset];
     *offset += 1;
     if buf != 0 {
-        return Err("Reserved must be 0".to_string());
+        return Err(SharpError::Decode("Reserved must be 0".to_string()));
     }
 
     // Read color planes or horizontal hotspot
@@ -325,7 +327,7 @@ fn read_icon_entry(buffer: &[u8], offset: &mut usize, type_: u16) -> Result<Icon
     *offset += 2;
     if type_ == 1 {
         if buf != 0 && buf != 1 {
-            return Err("Color plane was ${buf}, should be 0 or 1".to_string());
+            return Err(SharpError::Decode("Color plane was ${buf}, should be 0 or 1".to_string()));
         }
         entry.color_planes = buf;
     } else if type_ == 2 {
@@ -0,0 +1,465 @@
+use crate::{
+    output::FormatEnum,
+    resize::{Fit, Region, ResizeOptions},
+    InvalidParameterError, Sharp, SharpError,
+};
+use serde::{Deserialize, Serialize};
+
+/** The `{region}` segment of a IIIF Image API request. */
+#[derive(Debug, Clone, PartialEq)]
+pub enum IiifRegion {
+    /** `full` */
+    Full,
+    /** `square`, the largest centred square */
+    Square,
+    /** `x,y,w,h` in pixels */
+    Pixels {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /** `pct:x,y,w,h` in percent of the full image */
+    Percent {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+}
+
+/** The `{size}` segment of a IIIF Image API request, without the `^` prefix. */
+#[derive(Debug, Clone, PartialEq)]
+pub enum IiifScale {
+    /** `max` */
+    Max,
+    /** `w,` */
+    Width(u32),
+    /** `,h` */
+    Height(u32),
+    /** `pct:n` */
+    Percent(f64),
+    /** `w,h`, ignoring the aspect ratio */
+    Exact(u32, u32),
+    /** `!w,h`, the largest size that fits within `w` and `h` */
+    BestFit(u32, u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IiifSize {
+    /** Set by the `^` prefix, allows the output to be larger than the region. */
+    pub upscale: bool,
+    pub scale: IiifScale,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IiifRotation {
+    /** Set by the `!` prefix, mirrors the image before rotating. */
+    pub mirror: bool,
+    pub degrees: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IiifQuality {
+    Default,
+    Color,
+    Gray,
+    Bitonal,
+}
+
+/**
+ * A parsed IIIF Image API 3.0 request, `{region}/{size}/{rotation}/{quality}.{format}`.
+ *
+ * @example
+ * let request = IiifRequest::parse("pct:10,10,50,50/!400,400/!90/gray.webp")?;
+ * let data = Sharp::new_from_file("input.tif")?.iiif(&request)?.to_buffer()?;
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct IiifRequest {
+    pub region: IiifRegion,
+    pub size: IiifSize,
+    pub rotation: IiifRotation,
+    pub quality: IiifQuality,
+    /** One of `jpg`, `png`, `webp`, `tif`, `gif`, `jp2` or `avif` */
+    pub format: String,
+}
+
+impl IiifRequest {
+    /**
+     * Parse the last four segments of a request path. Any leading segments, such as the identifier, are ignored.
+     */
+    pub fn parse(path: &str) -> Result<Self, SharpError> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        if segments.len() < 4 {
+            return Err(InvalidParameterError!(
+                "request",
+                "{region}/{size}/{rotation}/{quality}.{format}",
+                path
+            ));
+        }
+        let segments = &segments[segments.len() - 4..];
+
+        let (quality, format) = segments[3]
+            .rsplit_once('.')
+            .ok_or_else(|| InvalidParameterError!("quality", "{quality}.{format}", segments[3]))?;
+
+        Ok(Self {
+            region: parse_region(segments[0])?,
+            size: parse_size(segments[1])?,
+            rotation: parse_rotation(segments[2])?,
+            quality: match quality {
+                "default" => IiifQuality::Default,
+                "color" => IiifQuality::Color,
+                "gray" => IiifQuality::Gray,
+                "bitonal" => IiifQuality::Bitonal,
+                _ => {
+                    return Err(InvalidParameterError!(
+                        "quality",
+                        "one of default, color, gray or bitonal",
+                        quality
+                    ))
+                }
+            },
+            format: match format {
+                "jpg" | "png" | "webp" | "tif" | "gif" | "jp2" | "avif" => format.to_string(),
+                _ => {
+                    return Err(InvalidParameterError!(
+                        "format",
+                        "one of jpg, png, webp, tif, gif, jp2 or avif",
+                        format
+                    ))
+                }
+            },
+        })
+    }
+
+    /** Media type of the response. */
+    pub fn content_type(&self) -> &'static str {
+        match self.format.as_str() {
+            "jpg" => "image/jpeg",
+            "png" => "image/png",
+            "webp" => "image/webp",
+            "tif" => "image/tiff",
+            "gif" => "image/gif",
+            "jp2" => "image/jp2",
+            "avif" => "image/avif",
+            _ => "application/octet-stream",
+        }
+    }
+}
+
+fn parse_region(region: &str) -> Result<IiifRegion, SharpError> {
+    match region {
+        "full" => Ok(IiifRegion::Full),
+        "square" => Ok(IiifRegion::Square),
+        _ => {
+            let invalid =
+                || InvalidParameterError!("region", "full, square, x,y,w,h or pct:x,y,w,h", region);
+            if let Some(percent) = region.strip_prefix("pct:") {
+                let values = parse_list::<f64>(percent).ok_or_else(invalid)?;
+                if values.iter().any(|value| *value < 0.0) || values[2] == 0.0 || values[3] == 0.0 {
+                    return Err(invalid());
+                }
+                Ok(IiifRegion::Percent {
+                    x: values[0],
+                    y: values[1],
+                    width: values[2],
+                    height: values[3],
+                })
+            } else {
+                let values = parse_list::<u32>(region).ok_or_else(invalid)?;
+                if values[2] == 0 || values[3] == 0 {
+                    return Err(invalid());
+                }
+                Ok(IiifRegion::Pixels {
+                    x: values[0],
+                    y: values[1],
+                    width: values[2],
+                    height: values[3],
+                })
+            }
+        }
+    }
+}
+
+fn parse_list<T: std::str::FromStr>(list: &str) -> Option<Vec<T>> {
+    let values =
+        list.split(',').map(|value| value.parse::<T>().ok()).collect::<Option<Vec<T>>>()?;
+    if values.len() == 4 {
+        Some(values)
+    } else {
+        None
+    }
+}
+
+fn parse_size(size: &str) -> Result<IiifSize, SharpError> {
+    let invalid =
+        || InvalidParameterError!("size", "max, w,, ,h, pct:n, w,h or !w,h with optional ^", size);
+    let (upscale, rest) = match size.strip_prefix('^') {
+        Some(rest) => (true, rest),
+        None => (false, size),
+    };
+    let positive = |value: &str| value.parse::<u32>().ok().filter(|value| *value > 0);
+
+    let scale = if rest == "max" {
+        IiifScale::Max
+    } else if let Some(percent) = rest.strip_prefix("pct:") {
+        let percent =
+            percent.parse::<f64>().ok().filter(|value| *value > 0.0).ok_or_else(invalid)?;
+        IiifScale::Percent(percent)
+    } else {
+        let (best_fit, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (width, height) = rest.split_once(',').ok_or_else(invalid)?;
+        match (width, height, best_fit) {
+            ("", "", _) => return Err(invalid()),
+            (width, "", false) => IiifScale::Width(positive(width).ok_or_else(invalid)?),
+            ("", height, false) => IiifScale::Height(positive(height).ok_or_else(invalid)?),
+            (width, height, false) => IiifScale::Exact(
+                positive(width).ok_or_else(invalid)?,
+                positive(height).ok_or_else(invalid)?,
+            ),
+            (width, height, true) => IiifScale::BestFit(
+                positive(width).ok_or_else(invalid)?,
+                positive(height).ok_or_else(invalid)?,
+            ),
+        }
+    };
+
+    Ok(IiifSize {
+        upscale,
+        scale,
+    })
+}
+
+fn parse_rotation(rotation: &str) -> Result<IiifRotation, SharpError> {
+    let (mirror, degrees) = match rotation.strip_prefix('!') {
+        Some(degrees) => (true, degrees),
+        None => (false, rotation),
+    };
+    // Only multiples of 90 are supported, as rotationArbitrary is not advertised
+    let degrees = match degrees {
+        "0" => 0.0,
+        "90" => 90.0,
+        "180" => 180.0,
+        "270" => 270.0,
+        _ => return Err(InvalidParameterError!("rotation", "one of 0, 90, 180 or 270", rotation)),
+    };
+
+    Ok(IiifRotation {
+        mirror,
+        degrees,
+    })
+}
+
+/** A tile size and the scale factors it is available at. */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IiifTile {
+    pub width: u32,
+    pub height: u32,
+    pub scale_factors: Vec<u32>,
+}
+
+/**
+ * The `info.json` document of an image service, serializable with any serde format.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IiifInfo {
+    #[serde(rename = "@context")]
+    pub context: String,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub protocol: String,
+    pub profile: String,
+    pub width: i32,
+    pub height: i32,
+    pub tiles: Vec<IiifTile>,
+    pub extra_formats: Vec<String>,
+    pub extra_qualities: Vec<String>,
+    pub extra_features: Vec<String>,
+}
+
+impl Sharp {
+    /**
+     * Apply a IIIF Image API request: region, then size, then rotation, then quality, then format.
+     *
+     * The region and size are resolved against the dimensions reported by {@link metadata},
+     * and the operations are applied in ordered mode.
+     *
+     * @example
+     * let request = IiifRequest::parse("full/^!1024,1024/0/default.jpg")?;
+     * let data = Sharp::new_from_file("input.tif")?.iiif(&request)?.to_buffer()?;
+     */
+    pub fn iiif(mut self, request: &IiifRequest) -> Result<Self, SharpError> {
+        let (width, height) = self.input_dimensions()?;
+        let (width, height) = (width as u32, height as u32);
+        let region = resolve_region(&request.region, width, height)?;
+        let (output_width, output_height) =
+            resolve_size(&request.size, region.width, region.height)?;
+
+        self = self.ordered(true);
+        if region.width != width || region.height != height {
+            self = self.extract(region.clone())?;
+        }
+
+        if output_width != region.width || output_height != region.height {
+            self = self.resize_with_opts(ResizeOptions {
+                width: Some(output_width as _),
+                height: Some(output_height as _),
+                fit: Some(Fit::Fill),
+                ..Default::default()
+            })?;
+        }
+
+        if request.rotation.mirror {
+            self = self.flop(true)?;
+        }
+        let degrees = request.rotation.degrees as i32;
+        if degrees != 0 {
+            self = self.rotate(degrees, None)?;
+        }
+
+        match request.quality {
+            IiifQuality::Gray => self = self.greyscale(true),
+            IiifQuality::Bitonal => self = self.threshold(None, None)?,
+            IiifQuality::Default | IiifQuality::Color => {}
+        }
+
+        let format = match request.format.as_str() {
+            "jpg" => FormatEnum::Jpeg,
+            "png" => FormatEnum::Png,
+            "webp" => FormatEnum::Webp,
+            "tif" => FormatEnum::Tiff,
+            "gif" => FormatEnum::Gif,
+            "jp2" => FormatEnum::Jp2,
+            "avif" => FormatEnum::Avif,
+            format => return Err(InvalidParameterError!("format", "IIIF format", format)),
+        };
+        self.to_format(format, None)
+    }
+
+    /**
+     * Create the IIIF Image API 3.0 `info.json` document for the input image.
+     *
+     * @example
+     * let info = Sharp::new_from_file("input.tif")?.iiif_info("https://example.com/iiif/input", None)?;
+     * let json = serde_json::to_string(&info)?;
+     */
+    pub fn iiif_info(&self, id: &str, tile_size: Option<u32>) -> Result<IiifInfo, SharpError> {
+        let (width, height) = self.input_dimensions()?;
+        let (width, height) = (width as u32, height as u32);
+        let tile_size = tile_size.unwrap_or(512).max(1);
+
+        let mut scale_factors = vec![1];
+        while tile_size * scale_factors[scale_factors.len() - 1] < width.max(height) {
+            scale_factors.push(scale_factors[scale_factors.len() - 1] * 2);
+        }
+
+        Ok(IiifInfo {
+            context: "http://iiif.io/api/image/3/context.json".to_string(),
+            id: id.trim_end_matches('/').to_string(),
+            type_: "ImageService3".to_string(),
+            protocol: "http://iiif.io/api/image".to_string(),
+            profile: "level2".to_string(),
+            width: width as _,
+            height: height as _,
+            tiles: vec![IiifTile {
+                width: tile_size,
+                height: tile_size,
+                scale_factors,
+            }],
+            extra_formats: ["webp", "tif", "gif", "jp2", "avif"].map(String::from).to_vec(),
+            extra_qualities: vec!["bitonal".to_string()],
+            extra_features: ["mirroring", "regionSquare", "sizeUpscaling"]
+                .map(String::from)
+                .to_vec(),
+        })
+    }
+}
+
+/*
+  Region in pixels, clipped to the image
+*/
+fn resolve_region(region: &IiifRegion, width: u32, height: u32) -> Result<Region, SharpError> {
+    let (x, y, w, h) = match *region {
+        IiifRegion::Full => (0, 0, width, height),
+        IiifRegion::Square => {
+            let side = width.min(height);
+            ((width - side) / 2, (height - side) / 2, side, side)
+        }
+        IiifRegion::Pixels {
+            x,
+            y,
+            width,
+            height,
+        } => (x, y, width, height),
+        IiifRegion::Percent {
+            x,
+            y,
+            width: w,
+            height: h,
+        } => (
+            (x * width as f64 / 100.0).round() as u32,
+            (y * height as f64 / 100.0).round() as u32,
+            (w * width as f64 / 100.0).round() as u32,
+            (h * height as f64 / 100.0).round() as u32,
+        ),
+    };
+
+    if x >= width || y >= height {
+        return Err(InvalidParameterError!("region", "region within the image", region));
+    }
+    let w = w.min(width - x);
+    let h = h.min(height - y);
+    if w == 0 || h == 0 {
+        return Err(InvalidParameterError!("region", "region of at least one pixel", region));
+    }
+
+    Ok(Region {
+        left: x,
+        top: y,
+        width: w,
+        height: h,
+    })
+}
+
+/*
+  Output size in pixels for a region
+*/
+fn resolve_size(size: &IiifSize, width: u32, height: u32) -> Result<(u32, u32), SharpError> {
+    let (w, h) = match size.scale {
+        IiifScale::Max => (width, height),
+        IiifScale::Width(w) => (w, (height as f64 * w as f64 / width as f64).round() as u32),
+        IiifScale::Height(h) => ((width as f64 * h as f64 / height as f64).round() as u32, h),
+        IiifScale::Percent(percent) => (
+            (width as f64 * percent / 100.0).round() as u32,
+            (height as f64 * percent / 100.0).round() as u32,
+        ),
+        IiifScale::Exact(w, h) => (w, h),
+        IiifScale::BestFit(w, h) => {
+            let scale = (w as f64 / width as f64).min(h as f64 / height as f64);
+            (
+                ((width as f64 * scale).round() as u32).min(w),
+                ((height as f64 * scale).round() as u32).min(h),
+            )
+        }
+    };
+
+    if w == 0 || h == 0 {
+        return Err(InvalidParameterError!("size", "size of at least one pixel", size));
+    }
+    if !size.upscale && (w > width || h > height) {
+        return Err(InvalidParameterError!(
+            "size",
+            "size no larger than the region without ^",
+            size
+        ));
+    }
+
+    Ok((w, h))
+}
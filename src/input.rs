@@ -1,19 +1,23 @@
-use std::path::Path;
+use std::{io::Read, path::Path};
 
 use crate::{
     common::{
-        determine_image_type, determine_image_type_from_str, image_type_supports_page,
-        image_type_supports_unlimited, set_density, ImageType, InputDescriptor,
+        determine_image_type, determine_image_type_from_source, determine_image_type_from_str,
+        image_type_supports_page, image_type_supports_unlimited, set_density, ImageType,
+        InputDescriptor,
     },
-    in_range, Colour, InvalidParameterError,
+    error::vips_error,
+    in_range,
+    stream::{new_source, StreamReader},
+    Colour, InvalidParameterError, SharpError,
 };
 use rs_vips::{
     bindings::{vips_band_format_is8bit, VIPS_META_N_PAGES, VIPS_META_PAGE_HEIGHT},
-    error::Error::OperationError,
     ops::{Align, BandFormat, FailOn, Interpretation, TextWrap},
     voption::{Setter, VOption},
-    Result, VipsImage,
+    VipsImage,
 };
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Default)]
 pub struct SharpOptions {
@@ -142,7 +146,7 @@ pub enum VerticalAlignment {
     Bottom,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RotateOptions {
     pub background: Colour,
 }
@@ -157,6 +161,7 @@ pub(crate) enum SharpInput {
 pub(crate) enum MixedInput {
     Path(String),
     Buffer(Vec<u8>),
+    Reader(StreamReader),
     Create(Create),
     Raw(CreateRaw),
     Text(CreateText),
@@ -192,6 +197,13 @@ impl Input {
         }
     }
 
+    /** Read the image from a stream, see {@link Inputs::reader}. */
+    pub fn reader<R: Read + Send + 'static>(reader: R) -> Self {
+        Self {
+            inner: MixedInput::Reader(StreamReader::new(reader)),
+        }
+    }
+
     pub fn create(create: Create) -> Self {
         Self {
             inner: MixedInput::Create(create),
@@ -233,6 +245,24 @@ impl Inputs {
         self
     }
 
+    /**
+     * Read the image from a stream, decoded incrementally as the pipeline runs.
+     *
+     * The reader is consumed by the first operation that opens the input, such as {@link metadata},
+     * {@link stats} or an output method, and any later operation returns `SharpError::Io`,
+     * so use a separate instance with its own reader for each of them.
+     *
+     * Shrink-on-load is not available, as the input cannot be read a second time.
+     *
+     * @example
+     * let file = std::fs::File::open("large.tiff")?;
+     * let data = Sharp::new(Inputs::new().reader(file))?.resize(320, 240)?.to_buffer()?;
+     */
+    pub fn reader<R: Read + Send + 'static>(mut self, reader: R) -> Self {
+        self.inner.push(MixedInput::Reader(StreamReader::new(reader)));
+        self
+    }
+
     pub fn create(mut self, create: Create) -> Self {
         self.inner.push(MixedInput::Create(create));
         self
@@ -253,7 +283,7 @@ pub(crate) fn create_input_descriptor(
     input: SharpInput,
     input_options: Option<SharpOptions>,
     baton: &mut crate::pipeline::PipelineBaton,
-) -> core::result::Result<InputDescriptor, String> {
+) -> core::result::Result<InputDescriptor, SharpError> {
     let mut input_descriptor = InputDescriptor {
         auto_orient: false,
         fail_on: FailOn::Warning,
@@ -266,7 +296,7 @@ pub(crate) fn create_input_descriptor(
     let mut input_options = input_options.clone();
     match input {
         SharpInput::Mixed(mixed) => {
-            let join: core::result::Result<Vec<InputDescriptor>, String> = mixed
+            let join: core::result::Result<Vec<InputDescriptor>, SharpError> = mixed
                 .into_iter()
                 .map(|input| {
                     create_input_descriptor(SharpInput::Single(input), input_options.clone(), baton)
@@ -281,11 +311,14 @@ pub(crate) fn create_input_descriptor(
                 }
                 MixedInput::Buffer(buffer) => {
                     if buffer.is_empty() {
-                        return Err("Input Buffer is empty".to_string());
+                        return Err(SharpError::Operation("Input Buffer is empty".to_string()));
                     }
                     input_descriptor.buffer = buffer;
                     input_descriptor.is_buffer = true;
                 }
+                MixedInput::Reader(reader) => {
+                    input_descriptor.reader = Some(reader);
+                }
                 MixedInput::Create(create) => {
                     input_options = Some(SharpOptions {
                         create: Some(create),
@@ -352,10 +385,10 @@ pub(crate) fn create_input_descriptor(
             if let Some(page_height) = raw.page_height {
                 if page_height > 0 && page_height <= raw.height {
                     if raw.height % page_height != 0 {
-                        return Err(format!(
+                        return Err(SharpError::Operation(format!(
                             "Expected raw.height {} to be a multiple of raw.pageHeight {}",
                             raw.height, page_height
-                        ));
+                        )));
                     } else {
                         input_descriptor.raw_page_height = page_height
                     }
@@ -405,10 +438,10 @@ pub(crate) fn create_input_descriptor(
             if let Some(page_height) = create.page_height {
                 if page_height > 0 && page_height <= create.height {
                     if create.height % page_height != 0 {
-                        return Err(format!(
+                        return Err(SharpError::Operation(format!(
                             "Expected create.height {} to be a multiple of create.pageHeight {}",
                             create.height, page_height
-                        ));
+                        )));
                     } else {
                         input_descriptor.create_page_height = page_height
                     }
@@ -533,15 +566,21 @@ pub(crate) fn create_input_descriptor(
 /*
     Open an image from the given InputDescriptor (filesystem, compressed buffer, raw pixel data)
 */
-pub(crate) fn open_input(descriptor: &InputDescriptor) -> Result<(VipsImage, ImageType)> {
-    if descriptor.is_buffer {
+pub(crate) fn open_input(
+    descriptor: &InputDescriptor,
+) -> core::result::Result<(VipsImage, ImageType), SharpError> {
+    if descriptor.reader.is_some() {
+        open_input_from_source(descriptor)
+    } else if descriptor.is_buffer {
         open_input_from_buffer(descriptor)
     } else {
         open_input_from(descriptor)
     }
 }
 
-pub(crate) fn open_input_from(descriptor: &InputDescriptor) -> Result<(VipsImage, ImageType)> {
+pub(crate) fn open_input_from(
+    descriptor: &InputDescriptor,
+) -> core::result::Result<(VipsImage, ImageType), SharpError> {
     let channels = descriptor.create_channels;
 
     let (image, image_type) = if channels > 0 {
@@ -648,9 +687,9 @@ pub(crate) fn open_input_from(descriptor: &InputDescriptor) -> Result<(VipsImage
                     "Input file is missing, did you mean Buffer.from('{:?}')",
                     descriptor.file[0..8].to_string()
                 );
-                return Err(OperationError(msg));
+                return Err(SharpError::Io(msg));
             }
-            return Err(OperationError(format!("Input file is missing: {}", descriptor.file)));
+            return Err(SharpError::Io(format!("Input file is missing: {}", descriptor.file)));
         }
         if image_type != ImageType::UNKNOWN {
             let mut option = VOption::new()
@@ -684,7 +723,8 @@ pub(crate) fn open_input_from(descriptor: &InputDescriptor) -> Result<(VipsImage
                 _ => {}
             };
 
-            let image = VipsImage::new_from_file_with_opts(&descriptor.file, option)?;
+            let image = VipsImage::new_from_file_with_opts(&descriptor.file, option)
+                .map_err(|e| vips_error(SharpError::Decode, e))?;
 
             if image_type == ImageType::SVG
                 || image_type == ImageType::PDF
@@ -695,7 +735,9 @@ pub(crate) fn open_input_from(descriptor: &InputDescriptor) -> Result<(VipsImage
                 (image, image_type)
             }
         } else {
-            return Err(OperationError("Input file contains unsupported image format".to_string()));
+            return Err(SharpError::UnsupportedFormat(
+                "Input file contains unsupported image format".to_string(),
+            ));
         }
     };
 
@@ -703,7 +745,7 @@ pub(crate) fn open_input_from(descriptor: &InputDescriptor) -> Result<(VipsImage
     if descriptor.limit_input_pixels > 0
         && image.get_width() * image.get_height() > descriptor.limit_input_pixels as i32
     {
-        return Err(OperationError("Input image exceeds pixel limit".to_string()));
+        return Err(SharpError::LimitExceeded("Input image exceeds pixel limit".to_string()));
     }
 
     Ok((image, image_type))
@@ -711,7 +753,7 @@ pub(crate) fn open_input_from(descriptor: &InputDescriptor) -> Result<(VipsImage
 
 pub(crate) fn open_input_from_buffer(
     descriptor: &InputDescriptor,
-) -> Result<(VipsImage, ImageType)> {
+) -> core::result::Result<(VipsImage, ImageType), SharpError> {
     let (image, image_type) = if descriptor.raw_channels > 0 {
         // Raw, uncompressed pixel data
         let is8bit = unsafe { vips_band_format_is8bit(descriptor.raw_depth as _) } == 1;
@@ -786,7 +828,8 @@ pub(crate) fn open_input_from_buffer(
                 ImageType::MAGICK => option.add("density", &density),
                 _ => {}
             };
-            let image = VipsImage::new_from_buffer_with_opts(&descriptor.buffer, "", option)?;
+            let image = VipsImage::new_from_buffer_with_opts(&descriptor.buffer, "", option)
+                .map_err(|e| vips_error(SharpError::Decode, e))?;
 
             if image_type == ImageType::SVG
                 || image_type == ImageType::PDF
@@ -797,7 +840,7 @@ pub(crate) fn open_input_from_buffer(
                 (image, image_type)
             }
         } else {
-            return Err(OperationError(
+            return Err(SharpError::UnsupportedFormat(
                 "Input buffer contains unsupported image format".to_string(),
             ));
         }
@@ -807,7 +850,81 @@ pub(crate) fn open_input_from_buffer(
     if descriptor.limit_input_pixels > 0
         && image.get_width() * image.get_height() > descriptor.limit_input_pixels as i32
     {
-        return Err(OperationError("Input image exceeds pixel limit".to_string()));
+        return Err(SharpError::LimitExceeded("Input image exceeds pixel limit".to_string()));
+    }
+
+    Ok((image, image_type))
+}
+
+pub(crate) fn open_input_from_source(
+    descriptor: &InputDescriptor,
+) -> core::result::Result<(VipsImage, ImageType), SharpError> {
+    let reader = match &descriptor.reader {
+        Some(reader) => reader,
+        None => return Err(SharpError::Io("Input stream is missing".to_string())),
+    };
+    if !reader.open() {
+        return Err(SharpError::Io(
+            "Input stream has already been read, use a new reader for each operation".to_string(),
+        ));
+    }
+    let source = new_source(reader)?;
+
+    // Compressed data, read incrementally
+    let image_type = determine_image_type_from_source(&source);
+    let (image, image_type) = if image_type != ImageType::UNKNOWN {
+        let mut option = VOption::new()
+            .set("access", descriptor.access as i32)
+            .set("fail_on", descriptor.fail_on as i32);
+
+        if descriptor.unlimited && image_type_supports_unlimited(&image_type) {
+            option.add("unlimited", true);
+        }
+
+        if image_type_supports_page(&image_type) {
+            option.add("n", descriptor.pages);
+            option.add("page", descriptor.page);
+        }
+
+        let density = descriptor.density.to_string();
+        match image_type {
+            ImageType::SVG => {
+                option.add("dpi", descriptor.density);
+                option.add("stylesheet", &descriptor.svg_stylesheet);
+                option.add("high_bitdepth", descriptor.svg_high_bitdepth)
+            }
+            ImageType::Tiff => option.add("subifd", descriptor.tiff_subifd),
+            ImageType::PDF => {
+                option.add("dpi", descriptor.density);
+                option.add("background", descriptor.pdf_background.as_slice())
+            }
+            ImageType::OPENSLIDE => option.add("openSlideLevel", descriptor.open_slide_level),
+            ImageType::JP2 => option.add("oneshot", descriptor.jp2_oneshot),
+            ImageType::MAGICK => option.add("density", &density),
+            _ => {}
+        };
+        let image = VipsImage::new_from_source_with_opts(&source, "", option)
+            .map_err(|e| vips_error(SharpError::Decode, e))?;
+
+        if image_type == ImageType::SVG
+            || image_type == ImageType::PDF
+            || image_type == ImageType::MAGICK
+        {
+            (set_density(image, descriptor.density)?, image_type)
+        } else {
+            (image, image_type)
+        }
+    } else {
+        return Err(SharpError::UnsupportedFormat(
+            "Input stream contains unsupported image format".to_string(),
+        ));
+    };
+
+    // Limit input images to a given number of pixels, where pixels = width * height
+    if descriptor.limit_input_pixels > 0
+        && image.get_width() * image.get_height() > descriptor.limit_input_pixels as i32
+    {
+        return Err(SharpError::LimitExceeded("Input image exceeds pixel limit".to_string()));
     }
 
     Ok((image, image_type))
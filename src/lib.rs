@@ -1,5 +1,5 @@
 use crate::{
-    common::rgba_from_hex,
+    common::{rgba_from_hex, ProgressHandler},
     input::{
         create_input_descriptor, CreateRaw, Input, Inputs, MixedInput, RotateOptions, SharpInput,
         SharpOptions,
@@ -8,9 +8,12 @@ use crate::{
         AffineOptions, BlurOptions, BooleanOptions, ClaheOptions, FlattenOptions, KernelOptions,
         ModulateOptions, NegateOptions, NormaliseOptions, SharpenOptions, ThresholdOptions,
     },
-    output::AvailableFormat,
-    pipeline::{init_options, PipelineBaton},
+    output::{AvailableFormat, Progress},
+    pipeline::{init_options, take_step, PipelineBaton},
+    recipe::Operation,
 };
+pub use comparison::{compare, compare_with_opts};
+pub use error::SharpError;
 pub use rs_vips::{
     ops::{
         BandFormat, BlendMode, Extend, FailOn, ForeignDzContainer, ForeignDzDepth, ForeignDzLayout,
@@ -19,36 +22,55 @@ pub use rs_vips::{
     },
     Vips,
 };
-use std::{collections::HashMap, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, sync::Arc};
+pub use task::CancellationToken;
 
+pub mod batch;
+pub mod budget;
 pub mod channel;
 pub mod colour;
 mod common;
+pub mod comparison;
 pub mod composite;
+pub mod editorial;
+pub mod error;
+pub mod hash;
+pub mod histogram;
 mod icon;
+pub mod iiif;
 pub mod input;
 pub mod metadata;
+pub mod negotiate;
 pub mod operation;
 pub mod output;
+pub mod palette;
 mod pipeline;
+pub mod placeholder;
+pub mod recipe;
 pub mod resize;
-mod stats;
+pub mod responsive;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod stats;
+mod stream;
+mod task;
+pub mod template;
 mod util;
 
 macro_rules! InvalidParameterError {
     ($name:expr, $expected:expr, $actual:expr) => {
-        format!(
-            "Expected {:?} for {:?} but received {:?}",
-            stringify!($expected),
-            $name,
-            stringify!($actual)
-        )
+        crate::SharpError::InvalidParameter {
+            name: $name.to_string(),
+            expected: stringify!($expected).trim_matches('"').to_string(),
+            actual: format!("{:?}", $actual),
+        }
     };
 }
 
 pub(crate) use InvalidParameterError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Colour {
     rgba: Vec<f64>,
 }
@@ -78,23 +100,23 @@ pub struct Sharp {
 }
 
 impl Sharp {
-    fn init() -> Result<(), String> {
-        Vips::init("sharp-rs").map_err(|e| e.to_string())?;
+    fn init() -> Result<(), SharpError> {
+        Vips::init("sharp-rs")?;
         Vips::leak_set(true);
         Ok(())
     }
 
-    pub fn new(inputs: Inputs) -> Result<Self, String> {
+    pub fn new(inputs: Inputs) -> Result<Self, SharpError> {
         Self::new_sharp(inputs, None)
     }
 
-    pub fn new_with_opts(inputs: Inputs, options: SharpOptions) -> Result<Self, String> {
+    pub fn new_with_opts(inputs: Inputs, options: SharpOptions) -> Result<Self, SharpError> {
         Self::new_sharp(inputs, Some(options))
     }
 
-    fn new_sharp(inputs: Inputs, options: Option<SharpOptions>) -> Result<Self, String> {
+    fn new_sharp(inputs: Inputs, options: Option<SharpOptions>) -> Result<Self, SharpError> {
         if inputs.inner.is_empty() {
-            return Err("Expected at least one input".to_string());
+            return Err(SharpError::Operation("Expected at least one input".to_string()));
         }
 
         if inputs.inner.len() > 1 {
@@ -114,9 +136,9 @@ impl Sharp {
     fn new_sharp_from_inputs(
         inputs: Inputs,
         options: Option<SharpOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         if inputs.inner.len() <= 1 {
-            return Err("Expected at least two inputs to join".to_string());
+            return Err(SharpError::Operation("Expected at least two inputs to join".to_string()));
         }
 
         Self::init()?;
@@ -128,21 +150,21 @@ impl Sharp {
         })
     }
 
-    pub fn new_from_file<P: AsRef<Path>>(filename: P) -> Result<Self, String> {
+    pub fn new_from_file<P: AsRef<Path>>(filename: P) -> Result<Self, SharpError> {
         Self::new_sharp_from_file(filename, None)
     }
 
     pub fn new_from_file_with_opts<P: AsRef<Path>>(
         filename: P,
         options: SharpOptions,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         Self::new_sharp_from_file(filename, Some(options))
     }
 
     fn new_sharp_from_file<P: AsRef<Path>>(
         filename: P,
         options: Option<SharpOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         Self::init()?;
         let mut baton = init_options();
         baton.input = create_input_descriptor(
@@ -155,23 +177,23 @@ impl Sharp {
         })
     }
 
-    pub fn new_from_files<P: AsRef<Path>>(files: &[P]) -> Result<Self, String> {
+    pub fn new_from_files<P: AsRef<Path>>(files: &[P]) -> Result<Self, SharpError> {
         Self::new_sharp_from_files(files, None)
     }
 
     pub fn new_from_files_with_opts<P: AsRef<Path>>(
         files: &[P],
         options: SharpOptions,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         Self::new_sharp_from_files(files, Some(options))
     }
 
     fn new_sharp_from_files<P: AsRef<Path>>(
         files: &[P],
         options: Option<SharpOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         if files.len() <= 1 {
-            return Err("Expected at least two images to join".to_string());
+            return Err(SharpError::Operation("Expected at least two images to join".to_string()));
         }
 
         Self::init()?;
@@ -185,21 +207,21 @@ impl Sharp {
         })
     }
 
-    pub fn new_from_buffer(buffer: Vec<u8>) -> Result<Self, String> {
+    pub fn new_from_buffer(buffer: Vec<u8>) -> Result<Self, SharpError> {
         Self::new_sharp_from_buffer(buffer, None)
     }
 
     pub fn new_from_buffer_with_opts(
         buffer: Vec<u8>,
         options: SharpOptions,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         Self::new_sharp_from_buffer(buffer, Some(options))
     }
 
     fn new_sharp_from_buffer(
         buffer: Vec<u8>,
         options: Option<SharpOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         Self::init()?;
         let mut baton = init_options();
         baton.input = create_input_descriptor(
@@ -213,23 +235,23 @@ impl Sharp {
         })
     }
 
-    pub fn new_from_buffers(buffers: Vec<Vec<u8>>) -> Result<Self, String> {
+    pub fn new_from_buffers(buffers: Vec<Vec<u8>>) -> Result<Self, SharpError> {
         Self::new_sharp_from_buffers(buffers, None)
     }
 
     pub fn new_from_buffers_with_opts(
         buffers: Vec<Vec<u8>>,
         options: SharpOptions,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         Self::new_sharp_from_buffers(buffers, Some(options))
     }
 
     fn new_sharp_from_buffers(
         buffers: Vec<Vec<u8>>,
         options: Option<SharpOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         if buffers.len() <= 1 {
-            return Err("Expected at least two images to join".to_string());
+            return Err(SharpError::Operation("Expected at least two images to join".to_string()));
         }
 
         Self::init()?;
@@ -249,7 +271,92 @@ impl Sharp {
      * @param options Object with a `seconds` attribute between 0 and 3600 (number)
      */
     pub fn timeout(mut self, seconds: u32) -> Self {
+        let operation = Operation::Timeout {
+            seconds,
+        };
         self.options.timeout_seconds = seconds;
+        self.record(operation);
+        self
+    }
+
+    /**
+     * Stop processing when the given token is cancelled, from any thread.
+     * The pending output method then returns `SharpError::Cancelled`.
+     *
+     * Async output methods always cancel when their future is dropped before completion.
+     *
+     * @example
+     * let token = CancellationToken::new();
+     * let sharp = Sharp::new_from_file("input.jpg")?.with_cancellation(token.clone());
+     * std::thread::spawn(move || sharp.to_buffer());
+     * token.cancel();
+     */
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.options.cancel = Some(token);
+        self
+    }
+
+    /**
+     * Report progress while the output image is computed, for example to drive a progress bar.
+     *
+     * The callback receives the percent complete, the number of pixels processed and the time elapsed
+     * since computation started. It is called from the libvips `eval` signal on whichever worker thread
+     * evaluates the image, not on the thread that called the output method, which is why it must be `Send + Sync`.
+     * Send updates to thread-bound state, such as a UI, through a channel instead of touching it directly.
+     *
+     * @example
+     * Sharp::new_from_file("huge.tiff")?
+     *   .on_progress(|p| println!("{}% after {:?}", p.percent, p.elapsed))
+     *   .tile(None)?
+     *   .to_file("output.dz")?;
+     */
+    pub fn on_progress<F: Fn(Progress) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.options.progress = Some(ProgressHandler(Arc::new(callback)));
+        self
+    }
+
+    /**
+     * Apply subsequent operations in the order they are called.
+     *
+     * By default each operation occupies a single slot in the pipeline and runs at a fixed position,
+     * so repeated calls replace earlier ones. In ordered mode every call to
+     * `rotate`, `flip`, `flop`, `affine`, `sharpen`, `median`, `blur`, `dilate`, `erode`, `flatten`, `unflatten`,
+     * `gamma`, `normalise`, `clahe`, `convolve`, `threshold`, `boolean`, `linear`, `recomb`, `modulate`,
+     * `resize`, `extend`, `extract`, `trim`, `tint`, `composite`, `remove_alpha`, `ensure_alpha`, `join_channel` and `bandbool`
+     * is recorded as a separate step and applied to the result of the previous step.
+     *
+     * Auto-orientation and `greyscale` happen before the first step;
+     * `negate`, `extract_channel` and `to_colourspace` happen after the last step.
+     *
+     * Operations configured before ordered mode is enabled run together, ahead of the first step.
+     *
+     * @example
+     * let output = Sharp::new_from_file(input)?
+     *   .ordered(true)
+     *   .rotate(90, None)?
+     *   .resize(320, 240)?
+     *   .rotate(45, None)?
+     *   .blur(Some(BlurOptions { sigma: 2.0, ..Default::default() }))?
+     *   .blur(Some(BlurOptions { sigma: 2.0, ..Default::default() }))?
+     *   .to_buffer()?;
+     */
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        let operation = Operation::Ordered {
+            ordered,
+        };
+        self.options.ordered = ordered;
+        self.record(operation);
+        self
+    }
+
+    /*
+     * Record the operations configured so far as a step when in ordered mode.
+     */
+    fn step(mut self) -> Self {
+        if self.options.ordered {
+            let step = take_step(&mut self.options);
+            self.options.steps.push(step);
+        }
         self
     }
 
@@ -282,7 +389,15 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn rotate(mut self, angle: i32, options: Option<RotateOptions>) -> Result<Self, String> {
+    pub fn rotate(
+        mut self,
+        angle: i32,
+        options: Option<RotateOptions>,
+    ) -> Result<Self, SharpError> {
+        let operation = Operation::Rotate {
+            angle,
+            options: options.clone(),
+        };
         if self.options.angle > 0 || self.options.rotation_angle > 0.0 {
             self.options.angle = 0;
             self.options.rotation_angle = 0.0;
@@ -297,7 +412,8 @@ impl Sharp {
             }
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -321,8 +437,9 @@ impl Sharp {
      *   });
      * readableStream.pipe(pipeline);
      */
-    pub fn auto_orient(mut self) -> Result<Self, String> {
+    pub fn auto_orient(mut self) -> Result<Self, SharpError> {
         self.options.input.auto_orient = true;
+        self.record(Operation::AutoOrient);
         Ok(self)
     }
 
@@ -335,9 +452,13 @@ impl Sharp {
      * @example
      * let output = await sharp(input).flip().toBuffer();
      */
-    pub fn flip(mut self, flip: bool) -> Result<Self, String> {
+    pub fn flip(mut self, flip: bool) -> Result<Self, SharpError> {
+        let operation = Operation::Flip {
+            flip,
+        };
         self.options.flip = flip;
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -347,9 +468,13 @@ impl Sharp {
      * @example
      * let output = await sharp(input).flop().toBuffer();
      */
-    pub fn flop(mut self, flop: bool) -> Result<Self, String> {
+    pub fn flop(mut self, flop: bool) -> Result<Self, SharpError> {
+        let operation = Operation::Flop {
+            flop,
+        };
         self.options.flop = flop;
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -389,8 +514,12 @@ impl Sharp {
         mut self,
         matrix: Vec<Vec<f64>>,
         options: Option<AffineOptions>,
-    ) -> Result<Self, String> {
-        let flat_matrix: Vec<f64> = matrix.into_iter().flatten().collect();
+    ) -> Result<Self, SharpError> {
+        let operation = Operation::Affine {
+            matrix: matrix.clone(),
+            options: options.clone(),
+        };
+        let flat_matrix: Vec<f64> = matrix.iter().flatten().copied().collect();
         if flat_matrix.len() == 4 {
             self.options.affine_matrix = flat_matrix;
         } else {
@@ -418,7 +547,8 @@ impl Sharp {
             }
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -450,7 +580,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn sharpen(mut self, options: Option<SharpenOptions>) -> Result<Self, String> {
+    pub fn sharpen(mut self, options: Option<SharpenOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Sharpen {
+            options: options.clone(),
+        };
         if let Some(options) = options {
             if !in_range(options.sigma, 0.000001, 10.0) {
                 return Err(InvalidParameterError!(
@@ -515,7 +648,8 @@ impl Sharp {
             // No arguments: default to mild sharpen
             self.options.sharpen_sigma = -1.0;
         }
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -528,7 +662,10 @@ impl Sharp {
      * @example
      * let output = await sharp(input).median(5).toBuffer();
      */
-    pub fn median(mut self, size: Option<i32>) -> Result<Self, String> {
+    pub fn median(mut self, size: Option<i32>) -> Result<Self, SharpError> {
+        let operation = Operation::Median {
+            size,
+        };
         if let Some(size) = size {
             if !in_range(size as _, 1.0, 1000.0) {
                 return Err(InvalidParameterError!("size", "integer between 1 and 1000", size));
@@ -538,7 +675,8 @@ impl Sharp {
             // No arguments: default to 3x3
             self.options.median_size = 3;
         }
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -558,13 +696,16 @@ impl Sharp {
      *   .blur(5)
      *   .toBuffer();
      */
-    pub fn blur(mut self, options: Option<BlurOptions>) -> Result<Self, String> {
+    pub fn blur(mut self, options: Option<BlurOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Blur {
+            options: options.clone(),
+        };
         if let Some(options) = options {
             if !in_range(options.sigma, 0.0, 1000.0) {
                 return Err(InvalidParameterError!(
                     "options.sigma",
                     "number between 0.0 and 1000",
-                    sigma
+                    options.sigma
                 ));
             }
             self.options.blur_sigma = options.sigma;
@@ -586,7 +727,8 @@ impl Sharp {
             self.options.blur_sigma = -1.0;
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -598,7 +740,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn dilate(mut self, width: Option<i32>) -> Result<Self, String> {
+    pub fn dilate(mut self, width: Option<i32>) -> Result<Self, SharpError> {
+        let operation = Operation::Dilate {
+            width,
+        };
         if let Some(width) = width {
             if width < 0 {
                 return Err(InvalidParameterError!("dilate", "positive integer", width));
@@ -607,7 +752,8 @@ impl Sharp {
         } else {
             self.options.dilate_width = 1;
         }
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -619,7 +765,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn erode(mut self, width: Option<i32>) -> Result<Self, String> {
+    pub fn erode(mut self, width: Option<i32>) -> Result<Self, SharpError> {
+        let operation = Operation::Erode {
+            width,
+        };
         if let Some(width) = width {
             if width < 0 {
                 return Err(InvalidParameterError!("erode", "positive integer", width));
@@ -628,7 +777,8 @@ impl Sharp {
         } else {
             self.options.erode_width = 1;
         }
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -642,14 +792,18 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn flatten(mut self, options: Option<FlattenOptions>) -> Result<Self, String> {
+    pub fn flatten(mut self, options: Option<FlattenOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Flatten {
+            options: options.clone(),
+        };
         self.options.flatten = true;
         if let Some(options) = options {
             if let Some(background) = options.background {
                 self.options.flatten_background = background.rgba;
             }
         }
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -673,9 +827,10 @@ impl Sharp {
      *   .unflatten()
      *   .toBuffer();
      */
-    pub fn unflatten(mut self) -> Result<Self, String> {
+    pub fn unflatten(mut self) -> Result<Self, SharpError> {
         self.options.unflatten = true;
-        Ok(self)
+        self.record(Operation::Unflatten);
+        Ok(self.step())
     }
 
     /**
@@ -688,7 +843,11 @@ impl Sharp {
      * Supply a second argument to use a different output gamma value, otherwise the first value is used in both cases.
      *
      */
-    pub fn gamma(mut self, gamma: Option<f64>, gamma_out: Option<f64>) -> Result<Self, String> {
+    pub fn gamma(mut self, gamma: Option<f64>, gamma_out: Option<f64>) -> Result<Self, SharpError> {
+        let operation = Operation::Gamma {
+            gamma,
+            gamma_out,
+        };
         if let Some(gamma) = gamma {
             if !in_range(gamma, 1.0, 3.0) {
                 return Err(InvalidParameterError!("gamma", "number between 1.0 and 3.0", gamma));
@@ -713,7 +872,8 @@ impl Sharp {
             self.options.gamma_out = self.options.gamma;
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -730,13 +890,22 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn negate(mut self, negate: bool, options: Option<NegateOptions>) -> Result<Self, String> {
+    pub fn negate(
+        mut self,
+        negate: bool,
+        options: Option<NegateOptions>,
+    ) -> Result<Self, SharpError> {
+        let operation = Operation::Negate {
+            negate,
+            options: options.clone(),
+        };
         self.options.negate = negate;
         if let Some(options) = options {
             if let Some(alpha) = options.alpha {
                 self.options.negate_alpha = alpha;
             }
         }
+        self.record(operation);
         Ok(self)
     }
 
@@ -759,7 +928,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn normalise(mut self, options: Option<NormaliseOptions>) -> Result<Self, String> {
+    pub fn normalise(mut self, options: Option<NormaliseOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Normalise {
+            options: options.clone(),
+        };
         if let Some(options) = options {
             if let Some(lower) = options.lower {
                 if !in_range(lower as _, 0.0, 99.0) {
@@ -779,11 +951,12 @@ impl Sharp {
             return Err(InvalidParameterError!(
                 "range",
                 "lower to be less than upper",
-                format!("{:?} >= {:?}", self.options.normaliseLower, self.options.normaliseUpper)
+                (self.options.normalise_lower, self.options.normalise_upper)
             ));
         }
         self.options.normalise = true;
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -803,7 +976,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn clahe(mut self, options: Option<ClaheOptions>) -> Result<Self, String> {
+    pub fn clahe(mut self, options: Option<ClaheOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Clahe {
+            options: options.clone(),
+        };
         if let Some(options) = options {
             if options.width > 0 {
                 self.options.clahe_width = options.width;
@@ -835,7 +1011,8 @@ impl Sharp {
             }
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -855,12 +1032,15 @@ impl Sharp {
      *   });
      *
      */
-    pub fn convolve(mut self, kernel: KernelOptions) -> Result<Self, String> {
+    pub fn convolve(mut self, kernel: KernelOptions) -> Result<Self, SharpError> {
+        let operation = Operation::Convolve {
+            kernel: kernel.clone(),
+        };
         if !in_range(kernel.width as _, 3.0, 1001.0)
             || !in_range(kernel.height as _, 3.0, 1001.0)
             || kernel.height * kernel.width != kernel.kernel.len() as i32
         {
-            return Err("Invalid convolution kernel".to_string());
+            return Err(SharpError::Operation("Invalid convolution kernel".to_string()));
         }
         // Default scale is sum of kernel values
         let mut scale = if let Some(scale) = kernel.scale {
@@ -887,7 +1067,8 @@ impl Sharp {
         self.options.conv_kernel_height = kernel.height;
         self.options.conv_kernel_width = kernel.width;
         self.options.conv_kernel = kernel.kernel;
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -897,7 +1078,11 @@ impl Sharp {
         mut self,
         threshold: Option<i32>,
         options: Option<ThresholdOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
+        let operation = Operation::Threshold {
+            threshold,
+            options: options.clone(),
+        };
         if let Some(threshold) = threshold {
             if !in_range(threshold as _, 0.0, 255.0) {
                 return Err(InvalidParameterError!(
@@ -921,7 +1106,8 @@ impl Sharp {
             self.options.threshold_grayscale = true;
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -936,7 +1122,7 @@ impl Sharp {
         operand: Input,
         operator: OperationBoolean,
         options: Option<BooleanOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         let mut sharp_options = SharpOptions::default();
         if let Some(options) = options {
             sharp_options.raw = Some(CreateRaw {
@@ -955,7 +1141,7 @@ impl Sharp {
 
         self.options.boolean_op = operator;
 
-        Ok(self)
+        Ok(self.step())
     }
 
     /**
@@ -978,7 +1164,11 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn linear(mut self, a: Option<Vec<f64>>, b: Option<Vec<f64>>) -> Result<Self, String> {
+    pub fn linear(mut self, a: Option<Vec<f64>>, b: Option<Vec<f64>>) -> Result<Self, SharpError> {
+        let operation = Operation::Linear {
+            a: a.clone(),
+            b: b.clone(),
+        };
         let (a, b) = if a.is_none() && b.is_some() {
             (Some(vec![1.0]), b)
         } else if a.is_some() && b.is_none() {
@@ -999,7 +1189,8 @@ impl Sharp {
             self.options.linear_b = Vec::new();
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -1021,7 +1212,10 @@ impl Sharp {
      *   });
      *
      */
-    pub fn recomb(mut self, input_matrix: Vec<Vec<f64>>) -> Result<Self, String> {
+    pub fn recomb(mut self, input_matrix: Vec<Vec<f64>>) -> Result<Self, SharpError> {
+        let operation = Operation::Recomb {
+            matrix: input_matrix.clone(),
+        };
         if input_matrix.len() != 3 && input_matrix.len() != 4 {
             return Err(InvalidParameterError!("input_matrix", "3x3 or 4x4 array", input_matrix));
         }
@@ -1034,7 +1228,8 @@ impl Sharp {
             ));
         }
         self.options.recomb_matrix = recomb_matrix;
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -1079,7 +1274,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn modulate(mut self, options: Option<ModulateOptions>) -> Result<Self, String> {
+    pub fn modulate(mut self, options: Option<ModulateOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Modulate {
+            options: options.clone(),
+        };
         if let Some(options) = options {
             if let Some(brightness) = options.brightness {
                 if brightness < 0.0 {
@@ -1108,7 +1306,8 @@ impl Sharp {
                 self.options.lightness = lightness;
             }
         }
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     pub fn available_formats() -> HashMap<String, AvailableFormat> {
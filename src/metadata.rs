@@ -3,8 +3,10 @@ use crate::{
         exif_orientation, get_density, has_density, has_profile, image_type_id, ImageType,
         InputDescriptor,
     },
+    editorial::{parse_iptc, parse_xmp, EditorialFields},
     input::open_input,
     util::{get_g_type, G_TYPE_INT},
+    SharpError,
 };
 use rs_vips::{
     bindings::{
@@ -37,6 +39,67 @@ pub struct BackgroundColor {
     pub gray: f64,
 }
 
+/** Value of an EXIF tag, typed by its EXIF format. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ExifValue {
+    /** Byte, Short, Long and their signed variants */
+    Integer(Vec<i64>),
+    /** Rational, SRational, Float and Double, as decimals */
+    Real(Vec<f64>),
+    /** ASCII, Undefined and any value that could not be parsed as a number */
+    Text(String),
+}
+
+impl ExifValue {
+    /** The value as text, when it is ASCII. */
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            ExifValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /** The first component of a numeric value. */
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ExifValue::Integer(values) => values.first().map(|value| *value as f64),
+            ExifValue::Real(values) => values.first().copied(),
+            ExifValue::Text(_) => None,
+        }
+    }
+}
+
+/** EXIF metadata parsed by libvips, with the most common tags as typed values. */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExifData {
+    /** Tags of the main image, keyed by tag name, e.g. `Make` */
+    pub ifd0: HashMap<String, ExifValue>,
+    /** Tags of the thumbnail */
+    pub ifd1: HashMap<String, ExifValue>,
+    /** Tags of the Exif IFD, e.g. `ExposureTime` */
+    pub ifd2: HashMap<String, ExifValue>,
+    /** Tags of the GPS IFD, e.g. `GPSLatitude` */
+    pub ifd3: HashMap<String, ExifValue>,
+    /** Tags of the Interoperability IFD */
+    pub ifd4: HashMap<String, ExifValue>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    /** As written by the camera, e.g. `2024:05:01 12:30:00` */
+    pub date_time_original: Option<String>,
+    /** In seconds */
+    pub exposure_time: Option<f64>,
+    pub f_number: Option<f64>,
+    pub iso: Option<i64>,
+    pub lens_model: Option<String>,
+    /** In decimal degrees, negative south of the equator */
+    pub gps_latitude: Option<f64>,
+    /** In decimal degrees, negative west of Greenwich */
+    pub gps_longitude: Option<f64>,
+    /** In metres, negative below sea level */
+    pub gps_altitude: Option<f64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metadata {
     pub format: String,
@@ -65,9 +128,14 @@ pub struct Metadata {
     pub has_alpha: bool,
     pub orientation: i32,
     pub exif: Vec<u8>,
+    pub exif_data: ExifData,
     pub icc: Vec<u8>,
     pub iptc: Vec<u8>,
+    /** Editorial fields of the IPTC metadata */
+    pub iptc_fields: EditorialFields,
     pub xmp: Vec<u8>,
+    /** Editorial fields of the XMP metadata */
+    pub xmp_fields: EditorialFields,
     pub tifftag_photoshop: Vec<u8>,
     pub comments: HashMap<String, String>,
     pub auto_orient: AutoOrient,
@@ -92,9 +160,12 @@ impl Default for Metadata {
             has_alpha: false,
             orientation: 0,
             exif: Vec::new(),
+            exif_data: ExifData::default(),
             icc: Vec::new(),
             iptc: Vec::new(),
+            iptc_fields: EditorialFields::default(),
             xmp: Vec::new(),
+            xmp_fields: EditorialFields::default(),
             tifftag_photoshop: Vec::new(),
             format: String::new(),
             space: String::new(),
@@ -157,9 +228,12 @@ fn bandformat_to_string(format: BandFormat) -> String {
     .to_string()
 }
 
-pub(crate) fn get_metadata(input: &InputDescriptor) -> Result<Metadata> {
+pub(crate) fn get_metadata(input: &InputDescriptor) -> core::result::Result<Metadata, SharpError> {
     let _guard = crate::util::VipsGuard;
+    read_metadata(input)
+}
 
+fn read_metadata(input: &InputDescriptor) -> core::result::Result<Metadata, SharpError> {
     let mut baton = Metadata::default();
     let (image, image_type) = open_input(input)?;
     if image_type != ImageType::UNKNOWN {
@@ -262,6 +336,12 @@ pub(crate) fn get_metadata(input: &InputDescriptor) -> Result<Metadata> {
         if image.get_typeof(VIPS_META_EXIF_NAME)? == unsafe { vips_blob_get_type() } {
             baton.exif = image.get_blob(VIPS_META_EXIF_NAME).unwrap_or_default();
         }
+        // EXIF tags and PNG comments
+        let mut fields = Box::new(MetadataFields::default());
+        let fields_ptr: *mut c_void = &mut *fields as *mut _ as *mut c_void;
+        unsafe { vips_image_map(image.as_mut_ptr(), Some(read_metadata_field), fields_ptr) };
+        baton.exif_data = parse_exif(fields.exif);
+        baton.comments = fields.comments;
         // ICC profile
         if image.get_typeof(VIPS_META_ICC_NAME)? == unsafe { vips_blob_get_type() } {
             baton.icc = image.get_blob(VIPS_META_ICC_NAME).unwrap_or_default();
@@ -269,20 +349,17 @@ pub(crate) fn get_metadata(input: &InputDescriptor) -> Result<Metadata> {
         // IPTC
         if image.get_typeof(VIPS_META_IPTC_NAME)? == unsafe { vips_blob_get_type() } {
             baton.iptc = image.get_blob(VIPS_META_IPTC_NAME).unwrap_or_default();
+            baton.iptc_fields = parse_iptc(&baton.iptc);
         }
         // XMP
         if image.get_typeof(VIPS_META_XMP_NAME)? == unsafe { vips_blob_get_type() } {
             baton.xmp = image.get_blob(VIPS_META_XMP_NAME).unwrap_or_default();
+            baton.xmp_fields = parse_xmp(&String::from_utf8_lossy(&baton.xmp));
         }
         // TIFFTAG_PHOTOSHOP
         if image.get_typeof(VIPS_META_PHOTOSHOP_NAME)? == unsafe { vips_blob_get_type() } {
             baton.tifftag_photoshop = image.get_blob(VIPS_META_PHOTOSHOP_NAME).unwrap_or_default();
         }
-        // PNG comments
-        let mut comments = Box::new(HashMap::new());
-        let comments_ptr: *mut c_void = &mut *comments as *mut _ as *mut c_void;
-        unsafe { vips_image_map(image.as_mut_ptr(), Some(read_pngcomment), comments_ptr) };
-        baton.comments = *comments;
 
         // AutoOrient
         if baton.orientation >= 5 {
@@ -301,17 +378,26 @@ pub(crate) fn get_metadata(input: &InputDescriptor) -> Result<Metadata> {
     Ok(baton)
 }
 
-unsafe extern "C" fn read_pngcomment(
+/*
+  Fields collected in a single pass over the metadata of the image
+*/
+#[derive(Default)]
+struct MetadataFields {
+    comments: HashMap<String, String>,
+    exif: HashMap<String, String>,
+}
+
+unsafe extern "C" fn read_metadata_field(
     image: *mut rs_vips::bindings::_VipsImage,
     field: *const c_char,
     _value: *mut GValue,
     data: *mut c_void,
 ) -> *mut c_void {
-    let comments: &mut HashMap<String, String> =
-        unsafe { &mut *(data as *mut HashMap<String, String>) };
+    let fields: &mut MetadataFields = unsafe { &mut *(data as *mut MetadataFields) };
 
     let png_comment_start = CString::new("png-comment-").unwrap();
     let png_comment_start_len = "png-comment-".len();
+    let exif_start = CString::new("exif-ifd").unwrap();
 
     if vips_isprefix(png_comment_start.as_ptr(), field) == 1 {
         let field_str = CStr::from_ptr(field).to_str().unwrap();
@@ -322,9 +408,144 @@ unsafe extern "C" fn read_pngcomment(
             // Skip the hyphen
             let keyword = &keyword.unwrap()[1..];
             let value = CStr::from_ptr(str).to_string_lossy().into_owned();
-            comments.insert(keyword.to_string(), value);
+            fields.comments.insert(keyword.to_string(), value);
+        }
+    } else if vips_isprefix(exif_start.as_ptr(), field) == 1 {
+        let mut str: *const c_char = std::ptr::null();
+        if vips_image_get_string(image, field, &mut str) == 0 {
+            let field = CStr::from_ptr(field).to_string_lossy().into_owned();
+            let value = CStr::from_ptr(str).to_string_lossy().into_owned();
+            fields.exif.insert(field, value);
         }
     }
 
     std::ptr::null_mut()
 }
+
+/*
+  libvips names each tag "exif-ifdN-Name" and formats its value as
+  "value (readable value, Format, N components, N bytes)"
+*/
+fn parse_exif(fields: HashMap<String, String>) -> ExifData {
+    let mut exif = ExifData::default();
+
+    for (field, value) in fields {
+        let (ifd, name) = match field["exif-ifd".len()..].split_once('-') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let tags = match ifd {
+            "0" => &mut exif.ifd0,
+            "1" => &mut exif.ifd1,
+            "2" => &mut exif.ifd2,
+            "3" => &mut exif.ifd3,
+            "4" => &mut exif.ifd4,
+            _ => continue,
+        };
+        tags.insert(name.to_string(), parse_exif_value(&value));
+    }
+
+    let text = |tags: &HashMap<String, ExifValue>, name: &str| {
+        tags.get(name).and_then(|value| value.as_text()).map(|text| text.trim().to_string())
+    };
+    let number = |tags: &HashMap<String, ExifValue>, name: &str| {
+        tags.get(name).and_then(|value| value.as_f64())
+    };
+
+    exif.make = text(&exif.ifd0, "Make");
+    exif.model = text(&exif.ifd0, "Model");
+    exif.date_time_original = text(&exif.ifd2, "DateTimeOriginal");
+    exif.exposure_time = number(&exif.ifd2, "ExposureTime");
+    exif.f_number = number(&exif.ifd2, "FNumber");
+    exif.iso = number(&exif.ifd2, "ISOSpeedRatings")
+        .or_else(|| number(&exif.ifd2, "PhotographicSensitivity"))
+        .map(|iso| iso as i64);
+    exif.lens_model = text(&exif.ifd2, "LensModel");
+
+    let coordinate = |name: &str, negative: &str| {
+        let degrees = match exif.ifd3.get(name) {
+            Some(ExifValue::Real(values)) if !values.is_empty() => values
+                .iter()
+                .zip([1.0, 60.0, 3600.0])
+                .map(|(value, divisor)| value / divisor)
+                .sum::<f64>(),
+            _ => return None,
+        };
+        match text(&exif.ifd3, &format!("{}Ref", name)) {
+            Some(reference) if reference.eq_ignore_ascii_case(negative) => Some(-degrees),
+            _ => Some(degrees),
+        }
+    };
+    exif.gps_latitude = coordinate("GPSLatitude", "S");
+    exif.gps_longitude = coordinate("GPSLongitude", "W");
+    exif.gps_altitude = number(&exif.ifd3, "GPSAltitude").map(|altitude| {
+        // Reference byte 1 is below sea level, read from the raw value before its description.
+        // libexif describes it as "Sea level reference", which is used when there is no raw number
+        let reference = match exif.ifd3.get("GPSAltitudeRef") {
+            Some(ExifValue::Integer(values)) => values.first().copied(),
+            Some(ExifValue::Text(text)) => {
+                let raw = text.split(" (").next().unwrap_or_default().trim();
+                match raw.parse::<i64>() {
+                    Ok(reference) => Some(reference),
+                    Err(_) if raw.eq_ignore_ascii_case("Sea level reference") => Some(1),
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        };
+        if reference == Some(1) {
+            -altitude
+        } else {
+            altitude
+        }
+    });
+
+    exif
+}
+
+fn parse_exif_value(value: &str) -> ExifValue {
+    // Split "value (readable value" from ", Format, N components, N bytes)"
+    let mut parts = value.trim_end_matches(')').rsplitn(4, ", ");
+    let (format, rest) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(_), Some(format), Some(rest)) => (format.to_lowercase(), rest),
+        _ => return ExifValue::Text(value.to_string()),
+    };
+
+    // ASCII values are repeated as their readable value, which may itself contain " ("
+    let split = rest
+        .match_indices(" (")
+        .map(|(index, _)| index)
+        .find(|index| rest[..*index] == rest[index + 2..])
+        .or_else(|| rest.find(" ("));
+    let raw = match split {
+        Some(index) => &rest[..index],
+        None => rest,
+    };
+
+    match format.as_str() {
+        "byte" | "short" | "long" | "sbyte" | "sshort" | "slong" => {
+            match raw.split_whitespace().map(|value| value.parse::<i64>()).collect() {
+                Ok(values) => ExifValue::Integer(values),
+                Err(_) => ExifValue::Text(raw.to_string()),
+            }
+        }
+        "rational" | "srational" | "float" | "double" => {
+            let values: Option<Vec<f64>> = raw
+                .split_whitespace()
+                .map(|value| match value.split_once('/') {
+                    Some((numerator, denominator)) => {
+                        let numerator = numerator.parse::<f64>().ok()?;
+                        let denominator = denominator.parse::<f64>().ok()?;
+                        (denominator != 0.0).then(|| numerator / denominator)
+                    }
+                    None => value.parse::<f64>().ok(),
+                })
+                .collect();
+            match values {
+                Some(values) => ExifValue::Real(values),
+                None => ExifValue::Text(raw.to_string()),
+            }
+        }
+        _ => ExifValue::Text(raw.to_string()),
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{
+    output::{FormatEnum, FormatOptions},
+    InvalidParameterError, Sharp, SharpError,
+};
+
+#[derive(Debug, Clone)]
+pub struct NegotiateOptions {
+    /** Formats the server is willing to produce, most preferred first. (optional, default avif, webp, jpeg, png) */
+    pub formats: Vec<FormatEnum>,
+    /** Options for the chosen format, see {@link to_format}. */
+    pub format_options: Option<FormatOptions>,
+}
+
+impl Default for NegotiateOptions {
+    fn default() -> Self {
+        Self {
+            formats: vec![FormatEnum::Avif, FormatEnum::Webp, FormatEnum::Jpeg, FormatEnum::Png],
+            format_options: None,
+        }
+    }
+}
+
+impl Sharp {
+    /**
+     * Choose the output format from the `Accept` header of an HTTP request and configure it.
+     *
+     * Formats are ranked by their q-value, then by the order of `formats`. Formats that libvips cannot write are skipped,
+     * as are formats without an alpha channel when the input has one and, for animated input, formats without animation,
+     * as long as a suitable format remains.
+     * When no format is acceptable, JPEG is used, or PNG for input with an alpha channel.
+     *
+     * Returns the configured pipeline and the MIME type to use as the `Content-Type` of the response.
+     *
+     * @example
+     * let (sharp, mime_type) = Sharp::new_from_file("input.png")?
+     *   .resize(320, 240)?
+     *   .negotiate_format("image/avif,image/webp;q=0.9,image/png;q=0.8", None)?;
+     * let data = sharp.to_buffer()?;
+     */
+    pub fn negotiate_format(
+        self,
+        accept: &str,
+        prefs: Option<NegotiateOptions>,
+    ) -> Result<(Self, String), SharpError> {
+        let prefs = prefs.unwrap_or_default();
+        if prefs.formats.is_empty() {
+            return Err(InvalidParameterError!("formats", "at least one format", prefs.formats));
+        }
+
+        let metadata = self.metadata()?;
+        let available = Sharp::available_formats();
+        let media_ranges = parse_accept(accept);
+
+        let mut candidates: Vec<(FormatEnum, f64)> = prefs
+            .formats
+            .iter()
+            .filter(|format| {
+                available
+                    .get(saver(**format))
+                    .map(|available| available.output.buffer)
+                    .unwrap_or(false)
+            })
+            .map(|format| (*format, quality(&media_ranges, format.mime_type())))
+            .filter(|(_, q)| *q > 0.0)
+            .collect();
+
+        if metadata.has_alpha && candidates.iter().any(|(format, _)| supports_alpha(*format)) {
+            candidates.retain(|(format, _)| supports_alpha(*format));
+        }
+        if metadata.pages > 1 && candidates.iter().any(|(format, _)| supports_animation(*format)) {
+            candidates.retain(|(format, _)| supports_animation(*format));
+        }
+
+        // Highest q-value wins, earlier preferences win ties
+        let mut chosen: Option<(FormatEnum, f64)> = None;
+        for (format, q) in candidates {
+            if chosen.map(|(_, best)| q > best).unwrap_or(true) {
+                chosen = Some((format, q));
+            }
+        }
+
+        let format = match chosen {
+            Some((format, _)) => format,
+            None if metadata.has_alpha => FormatEnum::Png,
+            None => FormatEnum::Jpeg,
+        };
+
+        let sharp = self.to_format(format, prefs.format_options)?;
+        Ok((sharp, format.mime_type().to_string()))
+    }
+}
+
+/*
+  Media ranges of an Accept header with their q-values
+*/
+fn parse_accept(accept: &str) -> Vec<(String, f64)> {
+    accept
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let media_type = parts.next()?.trim().to_lowercase();
+            if media_type.is_empty() {
+                return None;
+            }
+            let mut q = 1.0;
+            for parameter in parts {
+                if let Some((name, value)) = parameter.split_once('=') {
+                    if name.trim().eq_ignore_ascii_case("q") {
+                        q = value.trim().parse::<f64>().unwrap_or(0.0).clamp(0.0, 1.0);
+                    }
+                }
+            }
+            Some((media_type, q))
+        })
+        .collect()
+}
+
+/*
+  q-value of the most specific media range that matches the MIME type
+*/
+fn quality(media_ranges: &[(String, f64)], mime_type: &str) -> f64 {
+    let (main_type, _) = mime_type.split_once('/').unwrap_or((mime_type, ""));
+    let mut best: Option<(u8, f64)> = None;
+    for (range, q) in media_ranges {
+        let specificity = if range == mime_type {
+            2
+        } else if range.strip_suffix("/*") == Some(main_type) {
+            1
+        } else if range == "*/*" {
+            0
+        } else {
+            continue;
+        };
+        if best.map(|(current, _)| specificity > current).unwrap_or(true) {
+            best = Some((specificity, *q));
+        }
+    }
+    best.map(|(_, q)| q).unwrap_or(0.0)
+}
+
+/*
+  Name of the libvips saver reported by available_formats
+*/
+fn saver(format: FormatEnum) -> &'static str {
+    match format {
+        FormatEnum::Avif | FormatEnum::Heif => "heif",
+        FormatEnum::Jpeg | FormatEnum::Jpg => "jpeg",
+        FormatEnum::Jp2 => "jp2k",
+        FormatEnum::Tiff | FormatEnum::Tif => "tiff",
+        FormatEnum::Gif => "gif",
+        FormatEnum::Jxl => "jxl",
+        FormatEnum::Png => "png",
+        FormatEnum::Webp => "webp",
+        _ => "",
+    }
+}
+
+fn supports_alpha(format: FormatEnum) -> bool {
+    !matches!(format, FormatEnum::Jpeg | FormatEnum::Jpg)
+}
+
+fn supports_animation(format: FormatEnum) -> bool {
+    matches!(format, FormatEnum::Gif | FormatEnum::Webp)
+}
@@ -12,9 +12,11 @@ use rs_vips::{
     voption::{Setter, VOption},
     Result, VipsImage,
 };
+use serde::{Deserialize, Serialize};
 use strum_macros::Display;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AffineOptions {
     /** Parsed by the color module to extract values for red, green, blue and alpha. (optional, default "#000000") */
     pub background: Option<Colour>,
@@ -30,7 +32,8 @@ pub struct AffineOptions {
     pub interpolator: Option<Interpolators>,
 }
 
-#[derive(Display, Debug, Clone)]
+#[derive(Display, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Interpolators {
     /** [Nearest neighbour interpolation](http://en.wikipedia.org/wiki/Nearest-neighbor_interpolation). Suitable for image enlargement only. */
     #[strum(to_string = "nearest")]
@@ -55,7 +58,8 @@ pub enum Interpolators {
     VertexSplitQuadraticBasisSpline,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SharpenOptions {
     /** The sigma of the Gaussian mask, where sigma = 1 + radius / 2, between 0.000001 and 10000 */
     pub sigma: f64,
@@ -71,29 +75,34 @@ pub struct SharpenOptions {
     pub y3: Option<f64>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BlurOptions {
     /** A value between 0.3 and 1000 representing the sigma of the Gaussian mask, where `sigma = 1 + radius / 2` */
     pub sigma: f64,
     /** A value between 0.001 and 1. A smaller value will generate a larger, more accurate mask. */
     pub min_amplitude: Option<f64>,
     /** How accurate the operation should be, one of: integer, float, approximate. (optional, default "integer") */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub precision: Option<Precision>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FlattenOptions {
     /** background colour, parsed by the color module, defaults to black. (optional, default {r:0,g:0,b:0}) */
     pub background: Option<Colour>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NegateOptions {
     /** whether or not to negate any alpha channel. (optional, default true) */
     pub alpha: Option<bool>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NormaliseOptions {
     /** Percentile below which luminance values will be underexposed. */
     pub lower: Option<i32>,
@@ -101,7 +110,8 @@ pub struct NormaliseOptions {
     pub upper: Option<i32>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ClaheOptions {
     /** width of the region */
     pub width: i32,
@@ -111,7 +121,8 @@ pub struct ClaheOptions {
     pub max_slope: Option<i32>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KernelOptions {
     /** width of the kernel in pixels. */
     pub width: i32,
@@ -125,7 +136,8 @@ pub struct KernelOptions {
     pub offset: Option<f64>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ThresholdOptions {
     /** alternative spelling for greyscale. (optional, default true) */
     pub grayscale: Option<bool>,
@@ -143,7 +155,8 @@ pub struct BooleanOptions {
     pub raw: Raw,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ModulateOptions {
     pub brightness: Option<f64>,
     pub saturation: Option<f64>,
@@ -750,3 +763,54 @@ pub(crate) fn erode(image: VipsImage, width: i32) -> Result<VipsImage> {
     let mask = VipsImage::new_matrix(mask_width, mask_width)?;
     image.morph(&mask, OperationMorphology::Erode)?.invert()
 }
+
+/*
+ * Mean structural similarity (SSIM) of the luminance of two images of the same dimensions,
+ * using a Gaussian window with a sigma of 1.5
+ */
+pub(crate) fn ssim(reference: &VipsImage, candidate: &VipsImage) -> Result<f64> {
+    if reference.get_width() != candidate.get_width()
+        || reference.get_height() != candidate.get_height()
+    {
+        return Err(OperationError("Images must have the same dimensions".to_string()));
+    }
+
+    let x = luminance(reference)?;
+    let y = luminance(candidate)?;
+    let window = |image: &VipsImage| {
+        image.gaussblur_with_opts(1.5, VOption::new().set("precision", Precision::Float as i32))
+    };
+
+    let mu_x = window(&x)?;
+    let mu_y = window(&y)?;
+    let mu_xx = mu_x.multiply(&mu_x)?;
+    let mu_yy = mu_y.multiply(&mu_y)?;
+    let mu_xy = mu_x.multiply(&mu_y)?;
+    let sigma_xx = window(&x.multiply(&x)?)?.subtract(&mu_xx)?;
+    let sigma_yy = window(&y.multiply(&y)?)?.subtract(&mu_yy)?;
+    let sigma_xy = window(&x.multiply(&y)?)?.subtract(&mu_xy)?;
+
+    // Stabilising constants for a dynamic range of 255
+    let c1 = (0.01 * 255.0_f64).powi(2);
+    let c2 = (0.03 * 255.0_f64).powi(2);
+    let numerator = mu_xy.linear(&[2.0], &[c1])?.multiply(&sigma_xy.linear(&[2.0], &[c2])?)?;
+    let denominator = mu_xx
+        .add(&mu_yy)?
+        .linear(&[1.0], &[c1])?
+        .multiply(&sigma_xx.add(&sigma_yy)?.linear(&[1.0], &[c2])?)?;
+
+    numerator.divide(&denominator)?.avg()
+}
+
+/*
+ * Luminance as float in the range 0-255, ignoring any alpha channel
+ */
+pub(crate) fn luminance(image: &VipsImage) -> Result<VipsImage> {
+    let sixteen_bit = is16_bit(image.get_interpretation()?);
+    let grey = image.colourspace(Interpretation::BW)?.at(0).cast(BandFormat::Float)?;
+    if sixteen_bit {
+        grey.linear(&[1.0 / 257.0], &[0.0])
+    } else {
+        Ok(grey)
+    }
+}
@@ -1,9 +1,12 @@
 #![allow(clippy::unnecessary_unwrap)]
 use crate::{
+    editorial::EditorialFields,
     in_range,
     metadata::{get_metadata, Metadata},
     pipeline::{self, PipelineBaton},
-    Colour, InvalidParameterError, Sharp,
+    recipe::Operation,
+    task::{self, CancellationToken},
+    Colour, InvalidParameterError, Sharp, SharpError,
 };
 use num_derive::{FromPrimitive, ToPrimitive};
 use rs_vips::{
@@ -14,7 +17,7 @@ use rs_vips::{
     },
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use std::{collections::HashMap, io::Write, path::Path, time::Duration};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputInfo {
@@ -32,14 +35,31 @@ pub struct OutputInfo {
     pub trim_offset_top: i32,
     pub page_height: i32,
     pub pages: i32,
+    /** Structural similarity of the output to the processed image, when encoded with {@link to_buffer_min_ssim} */
+    pub ssim: Option<f64>,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    /** Percentage of the output image computed so far. */
+    pub percent: i32,
+    /** Number of pixels computed so far. */
+    pub pixels: u64,
+    /** Total number of pixels in the output image. */
+    pub total_pixels: u64,
+    /** Time since computation of the output image started. */
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WithIccProfileOptions {
     /**  Should the ICC profile be included in the output image metadata? (optional, default true) */
     pub attach: Option<bool>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Exif {
     pub ifd0: Option<HashMap<String, String>>,
     pub ifd1: Option<HashMap<String, String>>,
@@ -47,7 +67,39 @@ pub struct Exif {
     pub ifd3: Option<HashMap<String, String>>,
 }
 
-#[derive(Debug, Clone, Default)]
+/**
+ * Which EXIF tags and XMP properties to write to the output image.
+ *
+ * EXIF tags are named as in {@link Exif}, e.g. `Copyright` or `GPSLatitude`, or by their IFD, e.g. `ifd3` for all GPS tags.
+ * XMP properties are named with their prefix, e.g. `photoshop:City`, or without it, e.g. `City`.
+ * A name ending with `*` matches every name that starts with the rest of it, e.g. `GPS*` or `photoshop:*`.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataPolicy {
+    /** Names to keep, removing everything else, or empty to keep everything that is not denied */
+    pub allow: Vec<String>,
+    /** Names to remove, even when allowed */
+    pub deny: Vec<String>,
+}
+
+impl MetadataPolicy {
+    /*
+      Whether a tag or property known by any of these names should be kept
+    */
+    pub(crate) fn allows(&self, names: &[&str]) -> bool {
+        let matches = |pattern: &String| {
+            names.iter().any(|name| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => pattern == name,
+            })
+        };
+        (self.allow.is_empty() || self.allow.iter().any(matches)) && !self.deny.iter().any(matches)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WriteableMetadata {
     /** i32 of pixels per inch (DPI) */
     pub density: Option<f64>,
@@ -76,7 +128,7 @@ pub struct AvailableFormatOutput {
     pub stream: bool,
 }
 
-#[derive(strum_macros::Display)]
+#[derive(Debug, Clone, Copy, PartialEq, strum_macros::Display)]
 pub enum FormatEnum {
     #[strum(to_string = "avif")]
     Avif,
@@ -126,7 +178,26 @@ pub enum FormatEnum {
     Webp,
 }
 
-#[derive(Debug, Default)]
+impl FormatEnum {
+    /** Media type of images written in this format. */
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            FormatEnum::Avif => "image/avif",
+            FormatEnum::Gif => "image/gif",
+            FormatEnum::Heif => "image/heif",
+            FormatEnum::Jpeg | FormatEnum::Jpg => "image/jpeg",
+            FormatEnum::Jp2 => "image/jp2",
+            FormatEnum::Jxl => "image/jxl",
+            FormatEnum::Png => "image/png",
+            FormatEnum::Svg => "image/svg+xml",
+            FormatEnum::Tiff | FormatEnum::Tif => "image/tiff",
+            FormatEnum::Webp => "image/webp",
+            _ => "application/octet-stream",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct FormatOptions {
     pub output_options: Option<OutputOptions>,
     pub jpeg_options: Option<JpegOptions>,
@@ -142,22 +213,26 @@ pub struct FormatOptions {
     pub raw_options: Option<RawOptions>,
 }
 
-#[derive(Debug, strum_macros::Display)]
+#[derive(Debug, Clone, strum_macros::Display, Serialize, Deserialize)]
 pub enum ChromaSubsampling {
     #[strum(to_string = "4:4:4")]
+    #[serde(rename = "4:4:4")]
     None,
     #[strum(to_string = "4:2:0")]
+    #[serde(rename = "4:2:0")]
     Two,
 }
 
-#[derive(Debug, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Clone, FromPrimitive, ToPrimitive, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BitDepth {
     Eight = 8,
     Ten = 10,
     Twelve = 12,
 }
 
-#[derive(Debug, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Clone, FromPrimitive, ToPrimitive, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TiffBitDepth {
     One = 1,
     Two = 2,
@@ -165,7 +240,7 @@ pub enum TiffBitDepth {
     Eight = 8,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct OutputOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -179,7 +254,8 @@ pub struct AnimationOptions {
     pub delay: Option<u32>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct JpegOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -203,7 +279,8 @@ pub struct JpegOptions {
     pub mozjpeg: Option<bool>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Jp2Options {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -219,7 +296,8 @@ pub struct Jp2Options {
     pub chroma_subsampling: Option<ChromaSubsampling>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct JxlOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -239,7 +317,8 @@ pub struct JxlOptions {
     pub effort: Option<i32>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WebpOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -266,10 +345,12 @@ pub struct WebpOptions {
     /** Allow mixture of lossy and lossless animation frames (slow) (optional, default false) */
     pub mixed: Option<bool>,
     /* Preset options: one of default, photo, picture, drawing, icon, text (optional, default 'default') */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub preset: Option<ForeignWebpPreset>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AvifOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -293,13 +374,15 @@ pub enum HeifCompression {
     Hevc,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HeifOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
     /** quality, integer 1-100 (optional, default 50) */
     pub quality: Option<i32>,
     /** compression format: av1, hevc (optional, default 'av1') */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub compression: Option<ForeignHeifCompression>,
     /** use lossless compression (optional, default false) */
     pub lossless: Option<bool>,
@@ -311,7 +394,8 @@ pub struct HeifOptions {
     pub bitdepth: Option<BitDepth>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GifOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -335,15 +419,18 @@ pub struct GifOptions {
     pub inter_palette_max_error: Option<f64>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TiffOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
     /** Quality, integer 1-100 (optional, default 80) */
     pub quality: Option<i32>,
     /** Compression options: none, jpeg, deflate, packbits, ccittfax4, lzw, webp, zstd, jp2k (optional, default 'jpeg') */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub compression: Option<ForeignTiffCompression>,
     /** Compression predictor options: none, horizontal, float (optional, default 'horizontal') */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub predictor: Option<ForeignTiffPredictor>,
     /** Write an image pyramid (optional, default false) */
     pub pyramid: Option<bool>,
@@ -362,10 +449,12 @@ pub struct TiffOptions {
     /** Write 1-bit images as miniswhite (optional, default false) */
     pub miniswhite: Option<bool>,
     /* Resolution unit options: inch, cm (optional, default 'inch') */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub resolution_unit: Option<ForeignTiffResunit>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PngOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -405,14 +494,17 @@ impl PngOptions {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RawOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub depth: Option<BandFormat>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TileOptions {
     /** Force format output, otherwise attempt to use input format (optional, default true) */
     pub force: Option<bool>,
@@ -425,12 +517,15 @@ pub struct TileOptions {
     /** background colour, parsed by the colour module, defaults to white without transparency. (optional, default {r:255,g:255,b:255,alpha:1}) */
     pub background: Option<Colour>,
     /** How deep to make the pyramid, possible values are "onepixel", "onetile" or "one" (default based on layout) */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub depth: Option<ForeignDzDepth>,
     /** Threshold to skip tile generation, a value 0 - 255 for 8-bit images or 0 - 65535 for 16-bit images */
     pub skip_blanks: Option<i32>,
     /** Tile container, with value fs (filesystem) or zip (compressed file). (optional, default 'fs') */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub container: Option<ForeignDzContainer>,
     /** Filesystem layout, possible values are dz, iiif, iiif3, zoomify or google. (optional, default 'dz') */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub layout: Option<ForeignDzLayout>,
     /** Centre image in tile. (optional, default false) */
     pub centre: Option<bool>,
@@ -454,38 +549,38 @@ impl Sharp {
      * The caller is responsible for ensuring directory structures and permissions exist.
      *
      */
-    pub fn to_file<P: AsRef<Path>>(mut self, file_out: P) -> Result<(), String> {
+    pub fn to_file<P: AsRef<Path>>(mut self, file_out: P) -> Result<(), SharpError> {
         let file_out_string = file_out.as_ref().to_string_lossy().to_string();
         if self.options.input.file == file_out_string {
-            return Err("Cannot use same file for input and output".to_string());
+            return Err(SharpError::Io("Cannot use same file for input and output".to_string()));
         }
         self.options.file_out = file_out_string;
-        let _ = pipeline::pipline(self.options).map_err(|e| e.to_string())?;
+        let _ = pipeline::pipline(self.options)?;
 
         Ok(())
     }
 
-    pub fn to_file_with_info<P: AsRef<Path>>(mut self, file_out: P) -> Result<OutputInfo, String> {
+    pub fn to_file_with_info<P: AsRef<Path>>(
+        mut self,
+        file_out: P,
+    ) -> Result<OutputInfo, SharpError> {
         let file_out_string = file_out.as_ref().to_string_lossy().to_string();
         if self.options.input.file == file_out_string {
-            return Err("Cannot use same file for input and output".to_string());
+            return Err(SharpError::Io("Cannot use same file for input and output".to_string()));
         }
         self.options.file_out = file_out_string;
-        let baton = pipeline::pipline(self.options).map_err(|e| e.to_string())?;
+        let baton = pipeline::pipline(self.options)?;
 
         Ok(Self::create_output_info(baton))
     }
 
-    pub async fn to_file_async<P: AsRef<Path>>(mut self, file_out: P) -> Result<(), String> {
+    pub async fn to_file_async<P: AsRef<Path>>(mut self, file_out: P) -> Result<(), SharpError> {
         let file_out_string = file_out.as_ref().to_string_lossy().to_string();
         if self.options.input.file == file_out_string {
-            return Err("Cannot use same file for input and output".to_string());
+            return Err(SharpError::Io("Cannot use same file for input and output".to_string()));
         }
         self.options.file_out = file_out_string;
-        let _ = async_std::task::spawn(async move {
-            pipeline::pipline(self.options).map_err(|e| e.to_string())
-        })
-        .await?;
+        let _ = task::spawn(self.cancellation(), move || pipeline::pipline(self.options)).await?;
 
         Ok(())
     }
@@ -493,16 +588,14 @@ impl Sharp {
     pub async fn to_file_async_with_info<P: AsRef<Path>>(
         mut self,
         file_out: P,
-    ) -> Result<OutputInfo, String> {
+    ) -> Result<OutputInfo, SharpError> {
         let file_out_string = file_out.as_ref().to_string_lossy().to_string();
         if self.options.input.file == file_out_string {
-            return Err("Cannot use same file for input and output".to_string());
+            return Err(SharpError::Io("Cannot use same file for input and output".to_string()));
         }
         self.options.file_out = file_out_string;
-        let baton = async_std::task::spawn(async move {
-            pipeline::pipline(self.options).map_err(|e| e.to_string())
-        })
-        .await?;
+        let baton =
+            task::spawn(self.cancellation(), move || pipeline::pipline(self.options)).await?;
 
         Ok(Self::create_output_info(baton))
     }
@@ -519,38 +612,112 @@ impl Sharp {
      * See {@link #withmetadata|withMetadata} for control over self.
      *
      */
-    pub fn to_buffer(mut self) -> Result<Vec<u8>, String> {
+    pub fn to_buffer(mut self) -> Result<Vec<u8>, SharpError> {
         self.options.file_out = String::new();
-        let baton = pipeline::pipline(self.options).map_err(|e| e.to_string())?;
+        let baton = pipeline::pipline(self.options)?;
         Ok(baton.buffer_out)
     }
 
-    pub fn to_buffer_with_info(mut self) -> Result<(Vec<u8>, OutputInfo), String> {
+    pub fn to_buffer_with_info(mut self) -> Result<(Vec<u8>, OutputInfo), SharpError> {
         self.options.file_out = String::new();
-        let baton = pipeline::pipline(self.options).map_err(|e| e.to_string())?;
+        let baton = pipeline::pipline(self.options)?;
 
         Ok((baton.buffer_out.clone(), Self::create_output_info(baton)))
     }
 
-    pub async fn to_buffer_async(mut self) -> Result<Vec<u8>, String> {
+    /**
+     * Write output to a stream, encoded incrementally as the pipeline runs.
+     *
+     * The same formats as {@link to_buffer} are supported,
+     * see {@link available_formats} for those that can be encoded without buffering the whole output.
+     *
+     * @example
+     * let file = std::fs::File::create("output.webp")?;
+     * let info = Sharp::new_from_file("input.tiff")?.webp(None)?.to_writer(file)?;
+     */
+    pub fn to_writer<W: Write + Send>(mut self, mut writer: W) -> Result<OutputInfo, SharpError> {
         self.options.file_out = String::new();
-        let baton = async_std::task::spawn(async move {
-            pipeline::pipline(self.options).map_err(|e| e.to_string())
-        })
-        .await?;
+        let baton = pipeline::pipline_to_writer(self.options, &mut writer)?;
+
+        Ok(Self::create_output_info(baton))
+    }
+
+    pub async fn to_buffer_async(mut self) -> Result<Vec<u8>, SharpError> {
+        self.options.file_out = String::new();
+        let baton =
+            task::spawn(self.cancellation(), move || pipeline::pipline(self.options)).await?;
         Ok(baton.buffer_out)
     }
 
-    pub async fn to_buffer_with_info_async(mut self) -> Result<(Vec<u8>, OutputInfo), String> {
+    pub async fn to_buffer_with_info_async(mut self) -> Result<(Vec<u8>, OutputInfo), SharpError> {
         self.options.file_out = String::new();
-        let baton = async_std::task::spawn(async move {
-            pipeline::pipline(self.options).map_err(|e| e.to_string())
-        })
-        .await?;
+        let baton =
+            task::spawn(self.cancellation(), move || pipeline::pipline(self.options)).await?;
         Ok((baton.buffer_out.clone(), Self::create_output_info(baton)))
     }
 
-    fn create_output_info(baton: PipelineBaton) -> OutputInfo {
+    /**
+     * Create a branch of this pipeline for use with {@link to_many}.
+     *
+     * The branch inherits the output format and metadata settings configured so far,
+     * but none of the operations, which run once and are shared by every branch.
+     *
+     * @example
+     * let mut pipeline = Sharp::new_from_buffer(input)?.auto_orient()?;
+     * let thumbnail = pipeline.fork().resize(200, 200)?.webp(None)?;
+     * let large = pipeline.fork().resize(1600, 1200)?.jpeg(None)?;
+     * let outputs = pipeline.to_many(vec![thumbnail, large])?;
+     */
+    pub fn fork(&mut self) -> Self {
+        // Branches read the shared image, not the input
+        let input = std::mem::take(&mut self.options.input);
+        let join = std::mem::take(&mut self.options.join);
+        let steps = std::mem::take(&mut self.options.steps);
+        let mut options = self.options.clone();
+        self.options.input = input;
+        self.options.join = join;
+        self.options.steps = steps;
+
+        let _ = pipeline::take_step(&mut options);
+        Self {
+            options,
+        }
+    }
+
+    /**
+     * Write several outputs to Buffers from a single decode of the input.
+     *
+     * The input is opened and the operations of this pipeline applied once,
+     * then each branch created with {@link fork} adds its own operations, format and metadata settings.
+     *
+     * Returns one pair of output data and info per branch, in the same order.
+     */
+    pub fn to_many(self, branches: Vec<Sharp>) -> Result<Vec<(Vec<u8>, OutputInfo)>, SharpError> {
+        let branches = branches
+            .into_iter()
+            .map(|mut branch| {
+                branch.options.file_out = String::new();
+                branch.options
+            })
+            .collect();
+        let batons = pipeline::pipline_many(self.options, branches)?;
+
+        Ok(batons
+            .into_iter()
+            .map(|mut baton| {
+                (std::mem::take(&mut baton.buffer_out), Self::create_output_info(baton))
+            })
+            .collect())
+    }
+
+    /*
+      Token cancelling the job of an async output method, created when the caller supplied none
+    */
+    fn cancellation(&mut self) -> CancellationToken {
+        self.options.cancel.get_or_insert_with(CancellationToken::new).clone()
+    }
+
+    pub(crate) fn create_output_info(baton: PipelineBaton) -> OutputInfo {
         let mut width = baton.width;
         let mut height = baton.height;
         if baton.top_offset_pre != -1 && (baton.width == -1 || baton.height == -1) {
@@ -586,14 +753,27 @@ impl Sharp {
             trim_offset_top: baton.trim_offset_top,
             page_height: baton.page_height_out,
             pages: baton.pages_out,
+            ssim: None,
         }
     }
 
     /**
      * Fast access to (uncached) image metadata without decoding any compressed image data.
      */
-    pub fn metadata(&self) -> Result<Metadata, String> {
-        get_metadata(&self.options.input).map_err(|e| e.to_string())
+    pub fn metadata(&self) -> Result<Metadata, SharpError> {
+        get_metadata(&self.options.input)
+    }
+
+    /*
+      Dimensions of the input image after auto-orientation, if enabled
+    */
+    pub(crate) fn input_dimensions(&self) -> Result<(i32, i32), SharpError> {
+        let metadata = self.metadata()?;
+        if self.options.input.auto_orient {
+            Ok((metadata.auto_orient.width, metadata.auto_orient.height))
+        } else {
+            Ok((metadata.width, metadata.height))
+        }
     }
 
     /**
@@ -612,6 +792,7 @@ impl Sharp {
      */
     pub fn keep_exif(mut self) -> Self {
         self.options.keep_metadata |= 0b00001;
+        self.record(Operation::KeepExif);
         self
     }
 
@@ -640,12 +821,21 @@ impl Sharp {
      * @throws {Error} Invalid parameters
      */
     pub fn with_exif(mut self, exif: Exif) -> Self {
+        let operation = Operation::WithExif {
+            exif: exif.clone(),
+        };
+        self.set_exif(exif);
+        self.options.with_exif_merge = false;
+        self.record(operation);
+        self
+    }
+
+    fn set_exif(&mut self, exif: Exif) {
         self.apply_exif("ifd0", exif.ifd0.unwrap_or_default());
         self.apply_exif("ifd1", exif.ifd1.unwrap_or_default());
         self.apply_exif("ifd2", exif.ifd2.unwrap_or_default());
         self.apply_exif("ifd3", exif.ifd3.unwrap_or_default());
-        self.options.with_exif_merge = false;
-        self.keep_exif()
+        self.options.keep_metadata |= 0b00001;
     }
 
     fn apply_exif(&mut self, ifd: &str, values: HashMap<String, String>) {
@@ -673,8 +863,12 @@ impl Sharp {
      * @throws {Error} Invalid parameters
      */
     pub fn with_exif_merge(mut self, exif: Exif) -> Self {
-        self = self.with_exif(exif);
+        let operation = Operation::WithExifMerge {
+            exif: exif.clone(),
+        };
+        self.set_exif(exif);
         self.options.with_exif_merge = true;
+        self.record(operation);
         self
     }
 
@@ -694,6 +888,7 @@ impl Sharp {
      */
     pub fn keep_icc_profile(mut self) -> Self {
         self.options.keep_metadata |= 0b01000;
+        self.record(Operation::KeepIccProfile);
         self
     }
 
@@ -717,8 +912,12 @@ impl Sharp {
      * @throws {Error} Invalid parameters
      */
     pub fn with_icc_profile(mut self, icc: &str, options: Option<WithIccProfileOptions>) -> Self {
+        let operation = Operation::WithIccProfile {
+            icc: icc.to_string(),
+            options: options.clone(),
+        };
         self.options.with_icc_profile = icc.to_string();
-        self = self.keep_icc_profile();
+        self.options.keep_metadata |= 0b01000;
         if let Some(options) = options {
             if let Some(attach) = options.attach {
                 if !attach {
@@ -726,6 +925,7 @@ impl Sharp {
                 }
             }
         }
+        self.record(operation);
         self
     }
 
@@ -743,6 +943,7 @@ impl Sharp {
      */
     pub fn keep_xmp(mut self) -> Self {
         self.options.keep_metadata |= 0b00010;
+        self.record(Operation::KeepXmp);
         self
     }
 
@@ -773,8 +974,102 @@ impl Sharp {
      * @throws {Error} Invalid parameters
      */
     pub fn with_xmp(mut self, xmp: &str) -> Self {
+        let operation = Operation::WithXmp {
+            xmp: xmp.to_string(),
+        };
         self.options.with_xmp = xmp.to_string();
         self.options.keep_metadata |= 0b00010;
+        self.record(operation);
+        self
+    }
+
+    /**
+     * Set IPTC editorial fields in the output image, merged into the IPTC metadata of the input image.
+     *
+     * Only the fields that are set replace existing values, and other IPTC datasets are kept.
+     * Values are written as UTF-8, truncated to the maximum length of their IIM dataset:
+     * 32 bytes for creator and credit, 64 for each keyword, 128 for rights, 256 for headline and 2000 for description.
+     *
+     * Supported by JPEG and TIFF output.
+     *
+     * @example
+     * let data = Sharp::new_from_file("input.jpg")?
+     *   .resize(800, 600)?
+     *   .with_iptc_fields(EditorialFields {
+     *     creator: vec!["John Doe".to_string()],
+     *     headline: Some("Sunset".to_string()),
+     *     ..Default::default()
+     *   })
+     *   .to_buffer()?;
+     *
+     * @param {EditorialFields} fields Fields to set.
+     * @returns {Sharp}
+     */
+    pub fn with_iptc_fields(mut self, fields: EditorialFields) -> Self {
+        let operation = Operation::WithIptcFields {
+            fields: fields.clone(),
+        };
+        self.options.with_iptc_fields = Some(fields);
+        self.options.keep_metadata |= 0b00100;
+        self.record(operation);
+        self
+    }
+
+    /**
+     * Set XMP editorial fields in the output image, merged into the XMP metadata of the input image,
+     * or of {@link with_xmp} when used, creating an XMP packet when there is none.
+     *
+     * Only the fields that are set replace existing values, and other XMP properties are kept.
+     *
+     * Supported by PNG, JPEG, WebP, and TIFF output.
+     *
+     * @example
+     * let data = Sharp::new_from_file("input.jpg")?
+     *   .with_xmp_fields(EditorialFields {
+     *     rights: Some("© John Doe".to_string()),
+     *     keywords: vec!["sunset".to_string(), "beach".to_string()],
+     *     ..Default::default()
+     *   })
+     *   .to_buffer()?;
+     *
+     * @param {EditorialFields} fields Fields to set.
+     * @returns {Sharp}
+     */
+    pub fn with_xmp_fields(mut self, fields: EditorialFields) -> Self {
+        let operation = Operation::WithXmpFields {
+            fields: fields.clone(),
+        };
+        self.options.with_xmp_fields = Some(fields);
+        self.options.keep_metadata |= 0b00010;
+        self.record(operation);
+        self
+    }
+
+    /**
+     * Remove the EXIF tags and XMP properties that the policy does not allow from the metadata kept in the output image.
+     *
+     * The policy only filters metadata that is already kept, for example by {@link keep_metadata}, {@link keep_exif} or {@link keep_xmp},
+     * and also applies to EXIF and XMP metadata set with {@link with_exif}, {@link with_exif_merge} and {@link with_xmp}.
+     * It never keeps metadata by itself. IPTC metadata is unaffected.
+     *
+     * @example
+     * let data = Sharp::new_from_file("input.jpg")?
+     *   .keep_metadata()
+     *   .strip_metadata(MetadataPolicy {
+     *     deny: vec!["ifd3".to_string(), "BodySerialNumber".to_string(), "OwnerName".to_string(), "exif:GPS*".to_string()],
+     *     ..Default::default()
+     *   })
+     *   .to_buffer()?;
+     *
+     * @param {MetadataPolicy} policy Names of the tags and properties to allow and deny.
+     * @returns {Sharp}
+     */
+    pub fn strip_metadata(mut self, policy: MetadataPolicy) -> Self {
+        let operation = Operation::StripMetadata {
+            policy: policy.clone(),
+        };
+        self.options.metadata_policy = Some(policy);
+        self.record(operation);
         self
     }
 
@@ -795,6 +1090,7 @@ impl Sharp {
      */
     pub fn keep_metadata(mut self) -> Self {
         self.options.keep_metadata = 0b11111;
+        self.record(Operation::KeepMetadata);
         self
     }
 
@@ -822,9 +1118,12 @@ impl Sharp {
      * @returns {Sharp}
      * @throws {Error} Invalid parameters
      */
-    pub fn with_metadata(mut self, options: Option<WriteableMetadata>) -> Result<Self, String> {
-        self = self.keep_metadata();
-        self = self.with_icc_profile("srgb", None);
+    pub fn with_metadata(mut self, options: Option<WriteableMetadata>) -> Result<Self, SharpError> {
+        let operation = Operation::WithMetadata {
+            options: options.clone(),
+        };
+        self.options.keep_metadata = 0b11111;
+        self.options.with_icc_profile = "srgb".to_string();
         if let Some(options) = options {
             if let Some(orientation) = options.orientation {
                 if !in_range(orientation as _, 1.0, 8.0) {
@@ -844,6 +1143,7 @@ impl Sharp {
                 self.options.with_metadata_density = density;
             }
         }
+        self.record(operation);
         Ok(self)
     }
     /**
@@ -860,7 +1160,7 @@ impl Sharp {
         self,
         format: FormatEnum,
         options: Option<FormatOptions>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         let options = options.unwrap_or_default();
 
         match format {
@@ -898,7 +1198,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn jpeg(mut self, options: Option<JpegOptions>) -> Result<Self, String> {
+    pub fn jpeg(mut self, options: Option<JpegOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Jpeg {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -956,7 +1259,7 @@ impl Sharp {
                     return Err(InvalidParameterError!(
                         "quantisationTable",
                         "integer between 0 and 8",
-                        quantisationTable
+                        quantisation_table
                     ));
                 }
                 self.options.jpeg_quantisation_table = quantisation_table;
@@ -965,6 +1268,7 @@ impl Sharp {
 
         self.update_format_out("jpeg", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -1009,7 +1313,10 @@ impl Sharp {
      *  .toBuffer();
      *
      */
-    pub fn png(mut self, options: Option<PngOptions>) -> Result<Self, String> {
+    pub fn png(mut self, options: Option<PngOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Png {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -1025,7 +1332,7 @@ impl Sharp {
                     return Err(InvalidParameterError!(
                         "compressionLevel",
                         "integer between 0 and 9",
-                        compressionLevel
+                        compression_level
                     ));
                 }
                 self.options.png_compression_level = compression_level;
@@ -1082,6 +1389,7 @@ impl Sharp {
 
         self.update_format_out("png", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -1101,7 +1409,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn webp(mut self, options: Option<WebpOptions>) -> Result<Self, String> {
+    pub fn webp(mut self, options: Option<WebpOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Webp {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -1124,7 +1435,7 @@ impl Sharp {
                     return Err(InvalidParameterError!(
                         "alphaQuality",
                         "integer between 0 and 100",
-                        alphaQuality
+                        alpha_quality
                     ));
                 }
                 self.options.webp_alpha_quality = alpha_quality;
@@ -1181,6 +1492,7 @@ impl Sharp {
 
         self.update_format_out("webp", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -1218,7 +1530,10 @@ impl Sharp {
      *   .toFile("optim.gif");
      *
      */
-    pub fn gif(mut self, options: Option<GifOptions>) -> Result<Self, String> {
+    pub fn gif(mut self, options: Option<GifOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Gif {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -1288,6 +1603,7 @@ impl Sharp {
 
         self.update_format_out("gif", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -1316,7 +1632,10 @@ impl Sharp {
      * @since 0.29.1
      *
      */
-    pub fn jp2(mut self, options: Option<Jp2Options>) -> Result<Self, String> {
+    pub fn jp2(mut self, options: Option<Jp2Options>) -> Result<Self, SharpError> {
+        let operation = Operation::Jp2 {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -1364,6 +1683,7 @@ impl Sharp {
 
         self.update_format_out("jp2", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -1374,7 +1694,7 @@ impl Sharp {
         mut self,
         loop_: Option<i32>,
         delay: Option<Vec<i32>>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, SharpError> {
         if let Some(loop_) = loop_ {
             if !in_range(loop_ as _, 0.0, 65535.0) {
                 return Err(InvalidParameterError!("loop", "integer between 0 and 65535", loop_));
@@ -1414,7 +1734,10 @@ impl Sharp {
      *   .then(info => { ... });
      *
      */
-    pub fn tiff(mut self, options: Option<TiffOptions>) -> Result<Self, String> {
+    pub fn tiff(mut self, options: Option<TiffOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Tiff {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -1501,6 +1824,7 @@ impl Sharp {
 
         self.update_format_out("tiff", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -1526,7 +1850,10 @@ impl Sharp {
      * @since 0.27.0
      *
      */
-    pub fn avif(mut self, options: Option<AvifOptions>) -> Result<Self, String> {
+    pub fn avif(self, options: Option<AvifOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Avif {
+            options: options.clone(),
+        };
         let heif_options = if let Some(options) = options {
             Some(HeifOptions {
                 force: options.force,
@@ -1540,9 +1867,9 @@ impl Sharp {
         } else {
             None
         };
-        self = self.heif(heif_options)?;
-
-        Ok(self)
+        let mut sharp = self.heif_(heif_options)?;
+        sharp.record(operation);
+        Ok(sharp)
     }
 
     /**
@@ -1559,7 +1886,16 @@ impl Sharp {
      * @since 0.23.0
      *
      */
-    pub fn heif(mut self, options: Option<HeifOptions>) -> Result<Self, String> {
+    pub fn heif(self, options: Option<HeifOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Heif {
+            options: options.clone(),
+        };
+        let mut sharp = self.heif_(options)?;
+        sharp.record(operation);
+        Ok(sharp)
+    }
+
+    fn heif_(mut self, options: Option<HeifOptions>) -> Result<Self, SharpError> {
         let force = if options.is_none() {
             None
         } else {
@@ -1622,7 +1958,10 @@ impl Sharp {
      * @since 0.31.3
      *
      */
-    pub fn jxl(mut self, options: Option<JxlOptions>) -> Result<Self, String> {
+    pub fn jxl(mut self, options: Option<JxlOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Jxl {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -1684,6 +2023,7 @@ impl Sharp {
 
         self.update_format_out("jxl", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -1708,7 +2048,10 @@ impl Sharp {
      *   .toBuffer();
      *
      */
-    pub fn raw(mut self, options: Option<RawOptions>) -> Result<Self, String> {
+    pub fn raw(mut self, options: Option<RawOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Raw {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -1722,6 +2065,7 @@ impl Sharp {
         }
         self.update_format_out("raw", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -1756,7 +2100,10 @@ impl Sharp {
      *   .pipe(writeableStream);
      *
      */
-    pub fn tile(mut self, options: Option<TileOptions>) -> Result<Self, String> {
+    pub fn tile(mut self, options: Option<TileOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Tile {
+            options: options.clone(),
+        };
         let force = if options.is_none() {
             None
         } else {
@@ -1857,6 +2204,7 @@ impl Sharp {
 
         self.update_format_out("dz", force);
 
+        self.record(operation);
         Ok(self)
     }
 
@@ -0,0 +1,289 @@
+use crate::{
+    in_range,
+    placeholder::{linear_to_srgb, srgb_to_linear},
+    InvalidParameterError, Sharp, SharpError,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default)]
+pub struct PaletteOptions {
+    /** Ignore pixels that are more than half transparent (optional, default true) */
+    pub ignore_transparent: Option<bool>,
+    /** How much more the centre of the image counts than its corners, between 0 (evenly) and 1 (corners ignored) (optional, default 0) */
+    pub centre_weight: Option<f64>,
+}
+
+/** One colour of a palette. */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaletteColour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    /** CIELAB coordinates of the colour */
+    pub lab: [f64; 3],
+    /** Fraction of the considered pixels closest to this colour, between 0 and 1 */
+    pub coverage: f64,
+}
+
+impl PaletteColour {
+    /** The colour as `#rrggbb`. */
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/*
+  Pixel in Lab space with its weight
+*/
+#[derive(Clone, Copy)]
+struct Sample {
+    lab: [f64; 3],
+    weight: f64,
+}
+
+impl Sharp {
+    /**
+     * Find the `n` most representative colours of the input image.
+     *
+     * The input is auto-oriented and shrunk on load to at most 128x128 pixels,
+     * then its colours are split by median cut and refined by k-means, both in CIELAB space.
+     *
+     * Colours are ordered from most to least representative, taking `centre_weight` into account.
+     * Fewer than `n` colours are returned when the image has fewer distinct colours.
+     *
+     * @example
+     * let palette = Sharp::new_from_file("input.jpg")?.palette(5, None)?;
+     * for colour in palette.iter() {
+     *   println!("{} {:.1}%", colour.hex(), colour.coverage * 100.0);
+     * }
+     */
+    pub fn palette(
+        &self,
+        n: usize,
+        options: Option<PaletteOptions>,
+    ) -> Result<Vec<PaletteColour>, SharpError> {
+        if !(1..=64).contains(&n) {
+            return Err(InvalidParameterError!("n", "integer between 1 and 64", n));
+        }
+        let options = options.unwrap_or_default();
+        let ignore_transparent = options.ignore_transparent.unwrap_or(true);
+        let centre_weight = options.centre_weight.unwrap_or(0.0);
+        if !in_range(centre_weight, 0.0, 1.0) {
+            return Err(InvalidParameterError!(
+                "centreWeight",
+                "number between 0 and 1",
+                centre_weight
+            ));
+        }
+
+        let (rgba, width, height) = self.thumbnail_rgba(128)?;
+        let mut samples = Vec::with_capacity(width * height);
+        for (index, pixel) in rgba.chunks_exact(4).enumerate() {
+            if ignore_transparent && pixel[3] < 128 {
+                continue;
+            }
+            // Distance from the centre, 1 at the corners
+            let dx = (index % width) as f64 + 0.5 - width as f64 / 2.0;
+            let dy = (index / width) as f64 + 0.5 - height as f64 / 2.0;
+            let distance = (dx * dx + dy * dy).sqrt()
+                / ((width * width + height * height) as f64).sqrt()
+                * 2.0;
+            samples.push(Sample {
+                lab: srgb_to_lab(pixel[0], pixel[1], pixel[2]),
+                weight: 1.0 - centre_weight * distance.min(1.0),
+            });
+        }
+        if samples.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut centres = median_cut(&samples, n);
+        let assignments = k_means(&samples, &mut centres, 10);
+
+        let mut clusters: Vec<(f64, usize)> = vec![(0.0, 0); centres.len()];
+        for (sample, cluster) in samples.iter().zip(assignments.iter()) {
+            clusters[*cluster].0 += sample.weight;
+            clusters[*cluster].1 += 1;
+        }
+        let mut palette: Vec<(f64, PaletteColour)> = centres
+            .iter()
+            .zip(clusters)
+            .filter(|(_, (_, count))| *count > 0)
+            .map(|(lab, (weight, count))| {
+                let [r, g, b] = lab_to_srgb(*lab);
+                (
+                    weight,
+                    PaletteColour {
+                        r,
+                        g,
+                        b,
+                        lab: *lab,
+                        coverage: count as f64 / samples.len() as f64,
+                    },
+                )
+            })
+            .collect();
+        palette.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        Ok(palette.into_iter().map(|(_, colour)| colour).collect())
+    }
+}
+
+/*
+  Weighted mean of samples
+*/
+fn mean(samples: &[Sample]) -> [f64; 3] {
+    let mut sum = [0.0; 3];
+    let mut weight = 0.0;
+    for sample in samples {
+        for (sum, value) in sum.iter_mut().zip(sample.lab) {
+            *sum += value * sample.weight;
+        }
+        weight += sample.weight;
+    }
+    if weight > 0.0 {
+        sum.map(|sum| sum / weight)
+    } else {
+        samples[0].lab
+    }
+}
+
+/*
+  Split the samples into up to n boxes, each time halving the box with the largest weighted error
+  at the weighted median of its widest axis, and return the mean of each box
+*/
+fn median_cut(samples: &[Sample], n: usize) -> Vec<[f64; 3]> {
+    let mut boxes = vec![samples.to_vec()];
+    while boxes.len() < n {
+        let error = |samples: &Vec<Sample>| {
+            let centre = mean(samples);
+            samples.iter().map(|sample| distance(sample.lab, centre) * sample.weight).sum::<f64>()
+        };
+        let index = match boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, samples)| samples.len() > 1)
+            .map(|(index, samples)| (index, error(samples)))
+            .filter(|(_, error)| *error > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+        {
+            Some((index, _)) => index,
+            None => break,
+        };
+
+        let mut samples = boxes.swap_remove(index);
+        let axis = (0..3)
+            .max_by(|a, b| {
+                let range = |axis: usize| {
+                    let values = samples.iter().map(|sample| sample.lab[axis]);
+                    values.clone().fold(f64::MIN, f64::max) - values.fold(f64::MAX, f64::min)
+                };
+                range(*a).total_cmp(&range(*b))
+            })
+            .unwrap_or(0);
+        samples.sort_by(|a, b| a.lab[axis].total_cmp(&b.lab[axis]));
+
+        let total: f64 = samples.iter().map(|sample| sample.weight).sum();
+        let mut running = 0.0;
+        let mut split = samples.len() / 2;
+        for (index, sample) in samples.iter().enumerate() {
+            running += sample.weight;
+            if running >= total / 2.0 {
+                split = index + 1;
+                break;
+            }
+        }
+        let split = split.clamp(1, samples.len() - 1);
+        let upper = samples.split_off(split);
+        boxes.push(samples);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|samples| mean(samples)).collect()
+}
+
+/*
+  Refine the centres with weighted k-means, returning the centre each sample is assigned to
+*/
+fn k_means(samples: &[Sample], centres: &mut [[f64; 3]], iterations: usize) -> Vec<usize> {
+    let nearest = |centres: &[[f64; 3]], lab: [f64; 3]| {
+        centres
+            .iter()
+            .enumerate()
+            .min_by(|a, b| distance(*a.1, lab).total_cmp(&distance(*b.1, lab)))
+            .map(|(index, _)| index)
+            .unwrap_or(0)
+    };
+
+    let mut assignments = vec![0; samples.len()];
+    for _ in 0..iterations {
+        for (assignment, sample) in assignments.iter_mut().zip(samples) {
+            *assignment = nearest(centres, sample.lab);
+        }
+        let mut sums = vec![([0.0; 3], 0.0); centres.len()];
+        for (assignment, sample) in assignments.iter().zip(samples) {
+            let (sum, weight) = &mut sums[*assignment];
+            for (sum, value) in sum.iter_mut().zip(sample.lab) {
+                *sum += value * sample.weight;
+            }
+            *weight += sample.weight;
+        }
+        for (centre, (sum, weight)) in centres.iter_mut().zip(sums) {
+            if weight > 0.0 {
+                *centre = sum.map(|sum| sum / weight);
+            }
+        }
+    }
+    for (assignment, sample) in assignments.iter_mut().zip(samples) {
+        *assignment = nearest(centres, sample.lab);
+    }
+    assignments
+}
+
+/*
+  Squared Euclidean distance, i.e. squared CIE76 delta E
+*/
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+// D65 reference white
+const WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+const DELTA: f64 = 6.0 / 29.0;
+
+fn srgb_to_lab(r: u8, g: u8, b: u8) -> [f64; 3] {
+    let [r, g, b] = [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b)];
+    let xyz = [
+        0.4124564 * r + 0.3575761 * g + 0.1804375 * b,
+        0.2126729 * r + 0.7151522 * g + 0.0721750 * b,
+        0.0193339 * r + 0.1191920 * g + 0.9503041 * b,
+    ];
+    let [fx, fy, fz] = [0, 1, 2].map(|i| {
+        let t = xyz[i] / WHITE[i];
+        if t > DELTA.powi(3) {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    });
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn lab_to_srgb(lab: [f64; 3]) -> [u8; 3] {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let f = [fy + lab[1] / 500.0, fy, fy - lab[2] / 200.0];
+    let [x, y, z] = [0, 1, 2].map(|i| {
+        let t = if f[i] > DELTA {
+            f[i].powi(3)
+        } else {
+            3.0 * DELTA * DELTA * (f[i] - 4.0 / 29.0)
+        };
+        t * WHITE[i]
+    });
+    [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ]
+    .map(linear_to_srgb)
+}
@@ -5,16 +5,23 @@ use crate::{
         image_type_id, is16_bit, is_dz, is_dz_zip, is_gif, is_heif, is_jp2, is_jpeg, is_jxl,
         is_png, is_tiff, is_v, is_webp, remove_alpha, remove_animation_properties, remove_exif,
         remove_exif_orientation, remove_gif_palette, resolve_shrink, set_animation_properties,
-        set_density, set_exif_orientation, set_profile, set_timeout, stay_sequential, Canvas,
-        ImageType, InputDescriptor,
+        set_cancel, set_density, set_exif_orientation, set_profile, set_progress, set_timeout,
+        stay_sequential, strip_metadata, Canvas, ImageType, InputDescriptor, ProgressHandler,
     },
+    editorial::{set_iptc_fields, set_xmp_fields, EditorialFields},
+    error::vips_error,
     input::open_input,
     operation::{
         bandbool, blur, boolean, clahe, convolve, crop_multi_page, dilate, embed_multi_page,
         ensure_colourspace, erode, flatten, foreign_webp_preset_string, gamma, linear, modulate,
-        negate, normalise, recomb, sharpen, threshold, tint, trim, unflatten,
+        negate, normalise, recomb, sharpen, ssim, threshold, tint, trim, unflatten,
     },
+    output::MetadataPolicy,
+    recipe::Operation,
+    stream::{new_target, write_to_target},
+    task::CancellationToken,
     util::{get_g_type, VipsGuard, G_TYPE_INT},
+    SharpError,
 };
 use rs_vips::{
     bindings::{
@@ -24,15 +31,20 @@ use rs_vips::{
     },
     error::Error::OperationError,
     ops::{
-        Angle, BandFormat, BlendMode, Direction, Extend, ForeignDzContainer, ForeignDzDepth,
-        ForeignDzLayout, ForeignHeifCompression, ForeignPngFilter, ForeignSubsample,
-        ForeignTiffCompression, ForeignTiffPredictor, ForeignTiffResunit, ForeignWebpPreset,
-        Intent, Interesting, Interpretation, Kernel, OperationBoolean, Precision,
+        Access, Angle, BandFormat, BlendMode, Direction, Extend, ForeignDzContainer,
+        ForeignDzDepth, ForeignDzLayout, ForeignHeifCompression, ForeignPngFilter,
+        ForeignSubsample, ForeignTiffCompression, ForeignTiffPredictor, ForeignTiffResunit,
+        ForeignWebpPreset, Intent, Interesting, Interpretation, Kernel, OperationBoolean,
+        Precision,
     },
     voption::{Setter, VOption},
-    Result, VipsImage, VipsInterpolate,
+    Result, VipsImage, VipsInterpolate, VipsTarget,
+};
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
 };
-use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub(crate) struct Composite {
@@ -227,7 +239,12 @@ pub(crate) struct PipelineBaton {
     pub(crate) with_exif: HashMap<String, String>,
     pub(crate) with_exif_merge: bool,
     pub(crate) with_xmp: String,
+    pub(crate) metadata_policy: Option<MetadataPolicy>,
+    pub(crate) with_iptc_fields: Option<EditorialFields>,
+    pub(crate) with_xmp_fields: Option<EditorialFields>,
     pub(crate) timeout_seconds: u32,
+    pub(crate) cancel: Option<CancellationToken>,
+    pub(crate) progress: Option<ProgressHandler>,
     pub(crate) conv_kernel: Vec<f64>,
     pub(crate) conv_kernel_width: i32,
     pub(crate) conv_kernel_height: i32,
@@ -255,6 +272,9 @@ pub(crate) struct PipelineBaton {
     pub(crate) tile_id: String,
     pub(crate) tile_basename: String,
     pub(crate) recomb_matrix: Vec<f64>,
+    pub(crate) ordered: bool,
+    pub(crate) steps: Vec<PipelineBaton>,
+    pub(crate) recipe: Vec<Operation>,
 }
 
 impl Default for PipelineBaton {
@@ -400,7 +420,12 @@ impl Default for PipelineBaton {
             with_metadata_density: 0.0,
             with_exif_merge: true,
             with_xmp: String::new(),
+            metadata_policy: None,
+            with_iptc_fields: None,
+            with_xmp_fields: None,
             timeout_seconds: 0,
+            cancel: None,
+            progress: None,
             conv_kernel_width: 0,
             conv_kernel_height: 0,
             conv_kernel_scale: 0.0,
@@ -452,6 +477,9 @@ impl Default for PipelineBaton {
             tile_id: String::new(),
             tile_basename: String::new(),
             recomb_matrix: Vec::new(),
+            ordered: false,
+            steps: Vec::new(),
+            recipe: Vec::new(),
         }
     }
 }
@@ -631,9 +659,313 @@ pub(crate) fn init_options() -> PipelineBaton {
     }
 }
 
-pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
+/*
+ * Move the operations configured so far into a new step, leaving their defaults in the baton.
+ */
+pub(crate) fn take_step(baton: &mut PipelineBaton) -> PipelineBaton {
+    let mut step = init_options();
+    std::mem::swap(&mut step.composite, &mut baton.composite);
+    std::mem::swap(&mut step.join_channel_in, &mut baton.join_channel_in);
+    std::mem::swap(&mut step.top_offset_pre, &mut baton.top_offset_pre);
+    std::mem::swap(&mut step.left_offset_pre, &mut baton.left_offset_pre);
+    std::mem::swap(&mut step.width_pre, &mut baton.width_pre);
+    std::mem::swap(&mut step.height_pre, &mut baton.height_pre);
+    std::mem::swap(&mut step.top_offset_post, &mut baton.top_offset_post);
+    std::mem::swap(&mut step.left_offset_post, &mut baton.left_offset_post);
+    std::mem::swap(&mut step.width_post, &mut baton.width_post);
+    std::mem::swap(&mut step.height_post, &mut baton.height_post);
+    std::mem::swap(&mut step.width, &mut baton.width);
+    std::mem::swap(&mut step.height, &mut baton.height);
+    std::mem::swap(&mut step.kernel, &mut baton.kernel);
+    std::mem::swap(&mut step.canvas, &mut baton.canvas);
+    std::mem::swap(&mut step.position, &mut baton.position);
+    std::mem::swap(&mut step.resize_background, &mut baton.resize_background);
+    std::mem::swap(&mut step.fast_shrink_on_load, &mut baton.fast_shrink_on_load);
+    std::mem::swap(&mut step.tint, &mut baton.tint);
+    std::mem::swap(&mut step.flatten, &mut baton.flatten);
+    std::mem::swap(&mut step.flatten_background, &mut baton.flatten_background);
+    std::mem::swap(&mut step.unflatten, &mut baton.unflatten);
+    std::mem::swap(&mut step.blur_sigma, &mut baton.blur_sigma);
+    std::mem::swap(&mut step.precision, &mut baton.precision);
+    std::mem::swap(&mut step.min_ampl, &mut baton.min_ampl);
+    std::mem::swap(&mut step.brightness, &mut baton.brightness);
+    std::mem::swap(&mut step.saturation, &mut baton.saturation);
+    std::mem::swap(&mut step.hue, &mut baton.hue);
+    std::mem::swap(&mut step.lightness, &mut baton.lightness);
+    std::mem::swap(&mut step.median_size, &mut baton.median_size);
+    std::mem::swap(&mut step.sharpen_sigma, &mut baton.sharpen_sigma);
+    std::mem::swap(&mut step.sharpen_m1, &mut baton.sharpen_m1);
+    std::mem::swap(&mut step.sharpen_m2, &mut baton.sharpen_m2);
+    std::mem::swap(&mut step.sharpen_x1, &mut baton.sharpen_x1);
+    std::mem::swap(&mut step.sharpen_y2, &mut baton.sharpen_y2);
+    std::mem::swap(&mut step.sharpen_y3, &mut baton.sharpen_y3);
+    std::mem::swap(&mut step.threshold, &mut baton.threshold);
+    std::mem::swap(&mut step.threshold_grayscale, &mut baton.threshold_grayscale);
+    std::mem::swap(&mut step.trim_background, &mut baton.trim_background);
+    std::mem::swap(&mut step.trim_threshold, &mut baton.trim_threshold);
+    std::mem::swap(&mut step.trim_line_art, &mut baton.trim_line_art);
+    std::mem::swap(&mut step.linear_a, &mut baton.linear_a);
+    std::mem::swap(&mut step.linear_b, &mut baton.linear_b);
+    std::mem::swap(&mut step.dilate_width, &mut baton.dilate_width);
+    std::mem::swap(&mut step.erode_width, &mut baton.erode_width);
+    std::mem::swap(&mut step.gamma, &mut baton.gamma);
+    std::mem::swap(&mut step.gamma_out, &mut baton.gamma_out);
+    std::mem::swap(&mut step.normalise, &mut baton.normalise);
+    std::mem::swap(&mut step.normalise_lower, &mut baton.normalise_lower);
+    std::mem::swap(&mut step.normalise_upper, &mut baton.normalise_upper);
+    std::mem::swap(&mut step.clahe_width, &mut baton.clahe_width);
+    std::mem::swap(&mut step.clahe_height, &mut baton.clahe_height);
+    std::mem::swap(&mut step.clahe_max_slope, &mut baton.clahe_max_slope);
+    std::mem::swap(&mut step.angle, &mut baton.angle);
+    std::mem::swap(&mut step.rotation_angle, &mut baton.rotation_angle);
+    std::mem::swap(&mut step.rotation_background, &mut baton.rotation_background);
+    std::mem::swap(&mut step.rotate_before, &mut baton.rotate_before);
+    std::mem::swap(&mut step.orient_before, &mut baton.orient_before);
+    std::mem::swap(&mut step.flip, &mut baton.flip);
+    std::mem::swap(&mut step.flop, &mut baton.flop);
+    std::mem::swap(&mut step.extend_top, &mut baton.extend_top);
+    std::mem::swap(&mut step.extend_bottom, &mut baton.extend_bottom);
+    std::mem::swap(&mut step.extend_left, &mut baton.extend_left);
+    std::mem::swap(&mut step.extend_right, &mut baton.extend_right);
+    std::mem::swap(&mut step.extend_background, &mut baton.extend_background);
+    std::mem::swap(&mut step.extend_with, &mut baton.extend_with);
+    std::mem::swap(&mut step.without_enlargement, &mut baton.without_enlargement);
+    std::mem::swap(&mut step.without_reduction, &mut baton.without_reduction);
+    std::mem::swap(&mut step.affine_matrix, &mut baton.affine_matrix);
+    std::mem::swap(&mut step.affine_background, &mut baton.affine_background);
+    std::mem::swap(&mut step.affine_idx, &mut baton.affine_idx);
+    std::mem::swap(&mut step.affine_idy, &mut baton.affine_idy);
+    std::mem::swap(&mut step.affine_odx, &mut baton.affine_odx);
+    std::mem::swap(&mut step.affine_ody, &mut baton.affine_ody);
+    std::mem::swap(&mut step.affine_interpolator, &mut baton.affine_interpolator);
+    std::mem::swap(&mut step.conv_kernel, &mut baton.conv_kernel);
+    std::mem::swap(&mut step.conv_kernel_width, &mut baton.conv_kernel_width);
+    std::mem::swap(&mut step.conv_kernel_height, &mut baton.conv_kernel_height);
+    std::mem::swap(&mut step.conv_kernel_scale, &mut baton.conv_kernel_scale);
+    std::mem::swap(&mut step.conv_kernel_offset, &mut baton.conv_kernel_offset);
+    std::mem::swap(&mut step.boolean_descriptor, &mut baton.boolean_descriptor);
+    std::mem::swap(&mut step.boolean_op, &mut baton.boolean_op);
+    std::mem::swap(&mut step.band_bool_op, &mut baton.band_bool_op);
+    std::mem::swap(&mut step.remove_alpha, &mut baton.remove_alpha);
+    std::mem::swap(&mut step.ensure_alpha, &mut baton.ensure_alpha);
+    std::mem::swap(&mut step.recomb_matrix, &mut baton.recomb_matrix);
+    step
+}
+
+pub(crate) fn pipline(baton: PipelineBaton) -> core::result::Result<PipelineBaton, SharpError> {
     let _guard = VipsGuard;
+    process(baton, None)
+}
+
+pub(crate) fn pipline_to_writer(
+    baton: PipelineBaton,
+    writer: &mut (dyn Write + Send),
+) -> core::result::Result<PipelineBaton, SharpError> {
+    let _guard = VipsGuard;
+    process(baton, Some(writer))
+}
+
+fn process(
+    mut baton: PipelineBaton,
+    writer: Option<&mut (dyn Write + Send)>,
+) -> core::result::Result<PipelineBaton, SharpError> {
+    if baton.cancel.as_ref().is_some_and(CancellationToken::is_cancelled) {
+        return Err(SharpError::Cancelled("Processing was cancelled".to_string()));
+    }
+    let (image, input_image_type) = open(&mut baton)?;
+    let transformed = transform(image, input_image_type, &mut baton)?;
+    let transformed = apply_steps(transformed, &mut baton)?;
+
+    finish(transformed, input_image_type, baton, writer)
+}
+
+pub(crate) fn pipline_many(
+    baton: PipelineBaton,
+    branches: Vec<PipelineBaton>,
+) -> core::result::Result<Vec<PipelineBaton>, SharpError> {
+    let decoded = decode(baton)?;
+    branches.into_iter().map(|branch| decoded.finish(branch)).collect()
+}
+
+/*
+ * Input opened and processed once, held in memory so that any number of branches can be finished from it.
+ */
+pub(crate) struct Decoded {
+    shared: Transformed,
+    input_image_type: ImageType,
+    baton: PipelineBaton,
+    _guard: VipsGuard,
+}
+
+pub(crate) fn decode(baton: PipelineBaton) -> core::result::Result<Decoded, SharpError> {
+    let guard = VipsGuard;
+    let (shared, input_image_type, baton) = decode_shared(baton)?;
+
+    Ok(Decoded {
+        shared,
+        input_image_type,
+        baton,
+        _guard: guard,
+    })
+}
+
+fn decode_shared(
+    mut baton: PipelineBaton,
+) -> core::result::Result<(Transformed, ImageType, PipelineBaton), SharpError> {
+    let (image, input_image_type) = open(&mut baton)?;
+    let transformed = transform(image, input_image_type, &mut baton)?;
+    let mut shared = apply_steps(transformed, &mut baton)?;
+
+    // Decode once into memory so that every branch can read the shared image
+    let expired = set_timeout(&shared.image, baton.timeout_seconds);
+    set_cancel(&shared.image, &baton.cancel);
+    shared.image = VipsImage::copy_memory(shared.image)
+        .map_err(|e| stopped(SharpError::from(e), &expired, &baton.cancel))?;
+
+    Ok((shared, input_image_type, baton))
+}
+
+impl Decoded {
+    /*
+     * The shared image, after the operations of the pipeline
+     */
+    pub(crate) fn image(&self) -> &VipsImage {
+        &self.shared.image
+    }
+
+    /*
+     * Width of the shared image
+     */
+    pub(crate) fn width(&self) -> i32 {
+        self.shared.image.get_width()
+    }
+
+    /*
+     * Number of pages in the shared image
+     */
+    pub(crate) fn pages(&self) -> i32 {
+        self.shared.n_pages
+    }
+
+    /*
+     * Structural similarity between the output image and encoded output data of a single page
+     */
+    pub(crate) fn ssim(&self, data: &[u8]) -> core::result::Result<f64, SharpError> {
+        let reference = self.output_image()?;
+        VipsImage::new_from_buffer(data, "")
+            .and_then(|candidate| ssim(&reference, &candidate))
+            .map_err(SharpError::from)
+    }
+
+    /*
+     * Apply the operations, format and metadata settings of a branch to the shared image and write it out.
+     */
+    pub(crate) fn finish(
+        &self,
+        mut branch: PipelineBaton,
+    ) -> core::result::Result<PipelineBaton, SharpError> {
+        copy_output_info(&self.baton, &mut branch);
+        let transformed = transform_again(self.shared()?, &mut branch, self.baton.input.access)?;
+        let transformed = apply_steps(transformed, &mut branch)?;
+        finish(transformed, self.input_image_type, branch, None)
+    }
+
+    /*
+     * The shared image after the output colourspace, channel extraction, ICC profile and negation,
+     * as it would be encoded
+     */
+    pub(crate) fn output_image(&self) -> core::result::Result<VipsImage, SharpError> {
+        let mut baton = self.baton.clone();
+        self.shared()
+            .and_then(|transformed| prepare_output(transformed, self.input_image_type, &mut baton))
+            .map_err(SharpError::from)
+    }
+
+    fn shared(&self) -> Result<Transformed> {
+        Ok(Transformed {
+            image: self.shared.image.copy()?,
+            n_pages: self.shared.n_pages,
+            target_page_height: self.shared.target_page_height,
+            input_profile: self.shared.input_profile.clone(),
+            processing_profile: self.shared.processing_profile,
+        })
+    }
+}
+
+/*
+ * Ordered steps, each applied to the result of the previous one.
+ */
+fn apply_steps(
+    mut transformed: Transformed,
+    baton: &mut PipelineBaton,
+) -> core::result::Result<Transformed, SharpError> {
+    for mut step in std::mem::take(&mut baton.steps) {
+        step.colourspace_pipeline = baton.colourspace_pipeline;
+        step.colourspace = baton.colourspace;
+        transformed = transform_again(transformed, &mut step, baton.input.access)?;
+        copy_output_info(&step, baton);
+    }
+
+    Ok(transformed)
+}
+
+/*
+ * Apply the operations of `baton` to an image that has already been through `transform`.
+ */
+fn transform_again(
+    transformed: Transformed,
+    baton: &mut PipelineBaton,
+    access: Access,
+) -> core::result::Result<Transformed, SharpError> {
+    // Orientation, shrink-on-load and ICC import have already happened
+    baton.input = InputDescriptor {
+        access,
+        pages: transformed.n_pages,
+        ignore_icc: true,
+        ..Default::default()
+    };
+
+    let mut image = transformed.image;
+    if transformed.n_pages > 1 {
+        image = image.copy()?;
+        image.set_int(VIPS_META_PAGE_HEIGHT, transformed.target_page_height)?;
+    }
+    let result = transform(image, ImageType::UNKNOWN, baton)?;
 
+    Ok(Transformed {
+        image: result.image,
+        n_pages: result.n_pages,
+        target_page_height: result.target_page_height,
+        input_profile: transformed.input_profile,
+        processing_profile: transformed.processing_profile,
+    })
+}
+
+/*
+ * Carry trim, crop and premultiplication details reported in OutputInfo.
+ */
+fn copy_output_info(from: &PipelineBaton, to: &mut PipelineBaton) {
+    if from.trim_threshold >= 0.0 {
+        to.trim_offset_left = from.trim_offset_left;
+        to.trim_offset_top = from.trim_offset_top;
+    }
+    if from.has_crop_offset {
+        to.has_crop_offset = true;
+        to.crop_offset_left = from.crop_offset_left;
+        to.crop_offset_top = from.crop_offset_top;
+    }
+    if from.has_attention_center {
+        to.has_attention_center = true;
+        to.attention_x = from.attention_x;
+        to.attention_y = from.attention_y;
+    }
+    to.premultiplied |= from.premultiplied;
+}
+
+/*
+ * Open the input image, joining multiple inputs when required.
+ */
+fn open(baton: &mut PipelineBaton) -> core::result::Result<(VipsImage, ImageType), SharpError> {
     // Open input
     let (image, input_image_type) = if baton.join.is_empty() {
         open_input(&baton.input)?
@@ -682,8 +1014,31 @@ pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
         }
     };
 
+    let image = ensure_colourspace(image, baton.colourspace_pipeline)?;
+
+    Ok((image, input_image_type))
+}
+
+/*
+ * Processed image along with the state the output stage depends on.
+ */
+struct Transformed {
+    image: VipsImage,
+    n_pages: i32,
+    target_page_height: i32,
+    input_profile: Option<Vec<u8>>,
+    processing_profile: &'static str,
+}
+
+/*
+ * Apply the configured operations, from orientation through to alpha channel handling.
+ */
+fn transform(
+    mut image: VipsImage,
+    input_image_type: ImageType,
+    baton: &mut PipelineBaton,
+) -> core::result::Result<Transformed, SharpError> {
     let access = baton.input.access;
-    let mut image = ensure_colourspace(image, baton.colourspace_pipeline)?;
 
     let mut n_pages = baton.input.pages;
     if n_pages == -1 {
@@ -837,12 +1192,14 @@ pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
     //  - gamma correction doesn't need to be applied;
     //  - trimming or pre-resize extract isn't required;
     //  - input colourspace is not specified;
+    //  - input is not a stream, which cannot be read a second time;
     let should_pre_shrink = (target_resize_width > 0 || target_resize_height > 0)
         && baton.gamma == 0.0
         && baton.top_offset_pre == -1
         && baton.trim_threshold < 0.0
         && baton.colourspace_pipeline == Interpretation::Last
-        && !(should_orient_before || should_rotate_before);
+        && !(should_orient_before || should_rotate_before)
+        && baton.input.reader.is_none();
 
     if should_pre_shrink {
         // The common part of the shrink: the bit by which both axes must be shrunk
@@ -925,7 +1282,7 @@ pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
             };
             let image = set_density(image, baton.input.density)?;
             if image.get_width() > 32767 || image.get_height() > 32767 {
-                return Err(OperationError(
+                return Err(SharpError::Operation(
                     "Input SVG image will exceed 32767x32767 pixel limit when scaled".to_string(),
                 ));
             }
@@ -953,7 +1310,7 @@ pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
         if input_image_type == ImageType::SVG
             && (image.get_width() > 32767 || image.get_height() > 32767)
         {
-            return Err(OperationError(
+            return Err(SharpError::Operation(
                 "Input SVG image exceeds 32767x32767 pixel limit".to_string(),
             ));
         }
@@ -1483,7 +1840,7 @@ pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
             if composite_image.get_width() > image.get_width()
                 || composite_image.get_height() > image.get_height()
             {
-                return Err(OperationError(
+                return Err(SharpError::Operation(
                     "Image to composite must have same dimensions or smaller".to_string(),
                 ));
             }
@@ -1648,6 +2005,77 @@ pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
         image = ensure_alpha(image, baton.ensure_alpha)?;
     }
 
+    Ok(Transformed {
+        image,
+        n_pages,
+        target_page_height,
+        input_profile,
+        processing_profile,
+    })
+}
+
+/*
+ * Prepare the processed image for output and write the result.
+ */
+fn finish(
+    transformed: Transformed,
+    input_image_type: ImageType,
+    mut baton: PipelineBaton,
+    writer: Option<&mut (dyn Write + Send)>,
+) -> core::result::Result<PipelineBaton, SharpError> {
+    let image = prepare_output(transformed, input_image_type, &mut baton)?;
+
+    let expired = set_timeout(&image, baton.timeout_seconds);
+    set_cancel(&image, &baton.cancel);
+    set_progress(&image, &baton.progress);
+    let cancel = baton.cancel.clone();
+
+    let result = if let Some(writer) = writer {
+        // The target is dropped before returning, disconnecting the borrowed writer
+        let target = new_target(writer)?;
+        write_to_buffer(image, input_image_type, baton, Some(&*target))
+    } else if baton.file_out.is_empty() {
+        write_to_buffer(image, input_image_type, baton, None)
+    } else {
+        write_to_file(image, input_image_type, baton)
+    };
+    result.map_err(|error| stopped(error, &expired, &cancel))
+}
+
+/*
+ * Report a failure of an image stopped by its timeout or cancellation listener as such,
+ * as the listeners run on the libvips worker threads.
+ */
+fn stopped(
+    error: SharpError,
+    expired: &AtomicBool,
+    cancel: &Option<CancellationToken>,
+) -> SharpError {
+    if expired.load(Ordering::Relaxed) {
+        SharpError::Timeout(error.message())
+    } else if cancel.as_ref().is_some_and(CancellationToken::is_cancelled) {
+        SharpError::Cancelled(error.message())
+    } else {
+        error
+    }
+}
+
+/*
+ * Convert the processed image to the output colourspace and attach metadata, ready to be written.
+ */
+fn prepare_output(
+    transformed: Transformed,
+    input_image_type: ImageType,
+    baton: &mut PipelineBaton,
+) -> Result<VipsImage> {
+    let Transformed {
+        mut image,
+        n_pages,
+        target_page_height,
+        input_profile,
+        processing_profile,
+    } = transformed;
+
     // Convert image to sRGB, if not already
     if is16_bit(image.get_interpretation()?) {
         image = image.cast(BandFormat::Ushort)?;
@@ -1768,6 +2196,23 @@ pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
         image.set_blob(VIPS_META_XMP_NAME, baton.with_xmp.as_bytes())?;
     }
 
+    // IPTC and XMP editorial fields, merged into the existing metadata
+    if let Some(fields) = &baton.with_iptc_fields {
+        // TIFF holds raw IIM rather than Photoshop image resources
+        let raw = baton.format_out == "tiff"
+            || is_tiff(&baton.file_out)
+            || (baton.format_out == "input" && input_image_type == ImageType::Tiff);
+        image = set_iptc_fields(image, fields, raw)?;
+    }
+    if let Some(fields) = &baton.with_xmp_fields {
+        image = set_xmp_fields(image, fields)?;
+    }
+
+    // Remove EXIF tags and XMP properties not allowed by the policy
+    if let Some(policy) = &baton.metadata_policy {
+        image = strip_metadata(image, policy)?;
+    }
+
     // Number of channels used in output image
     baton.channels = image.get_bands();
     baton.width = image.get_width();
@@ -1781,26 +2226,42 @@ pub(crate) fn pipline(mut baton: PipelineBaton) -> Result<PipelineBaton> {
         baton.pages_out = image.get_int(VIPS_META_N_PAGES)?;
     }
 
-    if baton.file_out.is_empty() {
-        write_to_buffer(image, input_image_type, baton)
-    } else {
-        write_to_file(image, input_image_type, baton)
-    }
+    Ok(image)
+}
+
+/*
+  Save to the target when streaming, otherwise to a buffer
+*/
+macro_rules! save {
+    ($image:expr, $target:expr, $to_buffer:ident, $to_target:ident, $options:expr $(,)?) => {
+        match $target {
+            Some(target) => {
+                $image
+                    .$to_target(target, $options)
+                    .map_err(|e| vips_error(SharpError::Encode, e))?;
+                Vec::new()
+            }
+            None => $image.$to_buffer($options).map_err(|e| vips_error(SharpError::Encode, e))?,
+        }
+    };
 }
 
 fn write_to_buffer(
     mut image: VipsImage,
     input_image_type: ImageType,
     mut baton: PipelineBaton,
-) -> Result<PipelineBaton> {
-    // Buffer output
-    set_timeout(&image, baton.timeout_seconds);
-
+    target: Option<&VipsTarget>,
+) -> core::result::Result<PipelineBaton, SharpError> {
+    // Buffer or stream output
     if baton.format_out == "jpeg"
         || (baton.format_out == "input" && input_image_type == ImageType::Jpeg)
     {
         assert_image_type_dimensions(&image, ImageType::Jpeg)?;
-        let area = image.jpegsave_buffer_with_opts(
+        let area = save!(
+            image,
+            target,
+            jpegsave_buffer_with_opts,
+            jpegsave_target_with_opts,
             VOption::new()
                 .set("keep", baton.keep_metadata)
                 .set("Q", baton.jpeg_quality)
@@ -1818,7 +2279,7 @@ fn write_to_buffer(
                 .set("overshoot_deringing", baton.jpeg_overshoot_deringing)
                 .set("optimize_scans", baton.jpeg_optimise_scans)
                 .set("optimize_coding", baton.jpeg_optimise_coding),
-        )?;
+        );
 
         baton.buffer_out = area;
         baton.format_out = "jpeg".to_string();
@@ -1832,7 +2293,11 @@ fn write_to_buffer(
     {
         // Write JP2 to Buffer
         assert_image_type_dimensions(&image, ImageType::JP2)?;
-        let area = image.jp2ksave_buffer_with_opts(
+        let area = save!(
+            image,
+            target,
+            jp2ksave_buffer_with_opts,
+            jp2ksave_target_with_opts,
             VOption::new()
                 .set("Q", baton.jp2_quality)
                 .set("lossless", baton.jp2_lossless)
@@ -1846,7 +2311,7 @@ fn write_to_buffer(
                 )
                 .set("tile_height", baton.jp2_tile_height)
                 .set("tile_width", baton.jp2_tile_width),
-        )?;
+        );
 
         baton.buffer_out = area;
         baton.format_out = "jp2".to_string();
@@ -1856,7 +2321,11 @@ fn write_to_buffer(
     {
         // Write PNG to buffer
         assert_image_type_dimensions(&image, ImageType::Png)?;
-        let area = image.pngsave_buffer_with_opts(
+        let area = save!(
+            image,
+            target,
+            pngsave_buffer_with_opts,
+            pngsave_target_with_opts,
             VOption::new()
                 .set("keep", baton.keep_metadata)
                 .set("interlace", baton.png_progressive)
@@ -1881,7 +2350,7 @@ fn write_to_buffer(
                     },
                 )
                 .set("dither", baton.png_dither),
-        )?;
+        );
 
         baton.buffer_out = area;
         baton.format_out = "png".to_string();
@@ -1890,7 +2359,11 @@ fn write_to_buffer(
     {
         // Write WEBP to buffer
         assert_image_type_dimensions(&image, ImageType::Webp)?;
-        let area = image.webpsave_buffer_with_opts(
+        let area = save!(
+            image,
+            target,
+            webpsave_buffer_with_opts,
+            webpsave_target_with_opts,
             VOption::new()
                 .set("keep", baton.keep_metadata)
                 .set("Q", baton.webp_quality)
@@ -1903,7 +2376,7 @@ fn write_to_buffer(
                 .set("min_size", baton.webp_min_size)
                 .set("mixed", baton.webp_mixed)
                 .set("alpha_q", baton.webp_alpha_quality),
-        )?;
+        );
         baton.buffer_out = area;
         baton.format_out = "webp".to_string();
     } else if baton.format_out == "gif"
@@ -1911,7 +2384,11 @@ fn write_to_buffer(
     {
         // Write GIF to buffer
         assert_image_type_dimensions(&image, ImageType::GIF)?;
-        let area = image.gifsave_buffer_with_opts(
+        let area = save!(
+            image,
+            target,
+            gifsave_buffer_with_opts,
+            gifsave_target_with_opts,
             VOption::new()
                 .set("keep", baton.keep_metadata)
                 .set("bitdepth", baton.gif_bitdepth)
@@ -1921,7 +2398,7 @@ fn write_to_buffer(
                 .set("interframe_maxerror", baton.gif_inter_frame_max_error)
                 .set("interpalette_maxerror", baton.gif_inter_palette_max_error)
                 .set("dither", baton.gif_dither),
-        )?;
+        );
         baton.buffer_out = area;
         baton.format_out = "gif".to_string();
     } else if baton.format_out == "tiff"
@@ -1936,7 +2413,11 @@ fn write_to_buffer(
         if baton.tiff_predictor == ForeignTiffPredictor::Float {
             image = image.cast(BandFormat::Float)?;
         }
-        let area = image.tiffsave_buffer_with_opts(
+        let area = save!(
+            image,
+            target,
+            tiffsave_buffer_with_opts,
+            tiffsave_target_with_opts,
             VOption::new()
                 .set("keep", baton.keep_metadata)
                 .set("Q", baton.tiff_quality)
@@ -1951,7 +2432,7 @@ fn write_to_buffer(
                 .set("xres", baton.tiff_xres)
                 .set("yres", baton.tiff_yres)
                 .set("resunit", baton.tiff_resolution_unit as i32),
-        )?;
+        );
         baton.buffer_out = area;
         baton.format_out = "tiff".to_string();
     } else if baton.format_out == "heif"
@@ -1960,7 +2441,11 @@ fn write_to_buffer(
         // Write HEIF to buffer
         assert_image_type_dimensions(&image, ImageType::HEIF)?;
         image = remove_animation_properties(image)?;
-        let area = image.heifsave_buffer_with_opts(
+        let area = save!(
+            image,
+            target,
+            heifsave_buffer_with_opts,
+            heifsave_target_with_opts,
             VOption::new()
                 .set("keep", baton.keep_metadata)
                 .set("Q", baton.heif_quality)
@@ -1976,7 +2461,7 @@ fn write_to_buffer(
                     } as i32,
                 )
                 .set("lossless", baton.heif_lossless),
-        )?;
+        );
         baton.buffer_out = area;
         baton.format_out = "heif".to_string();
     } else if baton.format_out == "dz" {
@@ -2006,7 +2491,7 @@ fn write_to_buffer(
         if !baton.tile_basename.is_empty() {
             options.add("basename", &baton.tile_basename);
         }
-        let area = image.dzsave_buffer_with_opts(options)?;
+        let area = save!(image, target, dzsave_buffer_with_opts, dzsave_target_with_opts, options);
         baton.buffer_out = area;
         baton.format_out = "dz".to_string();
     } else if baton.format_out == "jxl"
@@ -2014,14 +2499,18 @@ fn write_to_buffer(
     {
         // Write JXL to buffer
         image = remove_animation_properties(image)?;
-        let area = image.jxlsave_buffer_with_opts(
+        let area = save!(
+            image,
+            target,
+            jxlsave_buffer_with_opts,
+            jxlsave_target_with_opts,
             VOption::new()
                 .set("keep", baton.keep_metadata)
                 .set("distance", baton.jxl_distance)
                 .set("tier", baton.jxl_decoding_tier)
                 .set("effort", baton.jxl_effort)
                 .set("lossless", baton.jxl_lossless),
-        )?;
+        );
         baton.buffer_out = area;
         baton.format_out = "jxl".to_string();
     } else if baton.format_out == "raw"
@@ -2039,10 +2528,13 @@ fn write_to_buffer(
         }
         // Get raw image data
         let area = image.write_to_memory();
-        baton.buffer_out = area;
-        if baton.buffer_out.is_empty() {
+        if area.is_empty() {
             baton.err.push_str("Could not allocate enough memory for raw output");
-            return Err(OperationError(baton.err.clone()));
+            return Err(SharpError::Operation(baton.err.clone()));
+        }
+        match target {
+            Some(target) => write_to_target(target, &area)?,
+            None => baton.buffer_out = area,
         }
         baton.format_out = "raw".to_string();
     } else {
@@ -2054,7 +2546,7 @@ fn write_to_buffer(
         } else {
             baton.err.push_str(&baton.format_out);
         }
-        return Err(OperationError(baton.err.clone()));
+        return Err(SharpError::UnsupportedFormat(baton.err.clone()));
     }
 
     Ok(baton)
@@ -2064,10 +2556,8 @@ fn write_to_file(
     mut image: VipsImage,
     input_image_type: ImageType,
     mut baton: PipelineBaton,
-) -> Result<PipelineBaton> {
+) -> core::result::Result<PipelineBaton, SharpError> {
     // File output
-    set_timeout(&image, baton.timeout_seconds);
-
     let is_jpeg = is_jpeg(&baton.file_out);
     let is_png = is_png(&baton.file_out);
     let is_webp = is_webp(&baton.file_out);
@@ -2098,26 +2588,28 @@ fn write_to_file(
     {
         // Write JPEG to file
         assert_image_type_dimensions(&image, ImageType::Jpeg)?;
-        image.jpegsave_with_opts(
-            &baton.file_out,
-            VOption::new()
-                .set("keep", baton.keep_metadata)
-                .set("Q", baton.jpeg_quality)
-                .set("interlace", baton.jpeg_progressive)
-                .set(
-                    "subsample_mode",
-                    if baton.jpeg_chroma_subsampling == "4:4:4" {
-                        ForeignSubsample::Off
-                    } else {
-                        ForeignSubsample::On
-                    } as i32,
-                )
-                .set("trellis_quant", baton.jpeg_trellis_quantisation)
-                .set("quant_table", baton.jpeg_quantisation_table)
-                .set("overshoot_deringing", baton.jpeg_overshoot_deringing)
-                .set("optimize_scans", baton.jpeg_optimise_scans)
-                .set("optimize_coding", baton.jpeg_optimise_coding),
-        )?;
+        image
+            .jpegsave_with_opts(
+                &baton.file_out,
+                VOption::new()
+                    .set("keep", baton.keep_metadata)
+                    .set("Q", baton.jpeg_quality)
+                    .set("interlace", baton.jpeg_progressive)
+                    .set(
+                        "subsample_mode",
+                        if baton.jpeg_chroma_subsampling == "4:4:4" {
+                            ForeignSubsample::Off
+                        } else {
+                            ForeignSubsample::On
+                        } as i32,
+                    )
+                    .set("trellis_quant", baton.jpeg_trellis_quantisation)
+                    .set("quant_table", baton.jpeg_quantisation_table)
+                    .set("overshoot_deringing", baton.jpeg_overshoot_deringing)
+                    .set("optimize_scans", baton.jpeg_optimise_scans)
+                    .set("optimize_coding", baton.jpeg_optimise_coding),
+            )
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "jpeg".to_string();
         baton.channels = std::cmp::min(baton.channels, 3);
     } else if baton.format_out == "jp2"
@@ -2126,22 +2618,24 @@ fn write_to_file(
     {
         // Write JP2 to file
         assert_image_type_dimensions(&image, ImageType::JP2)?;
-        image.jp2ksave_with_opts(
-            &baton.file_out,
-            VOption::new()
-                .set("Q", baton.jp2_quality)
-                .set("lossless", baton.jp2_lossless)
-                .set(
-                    "subsample_mode",
-                    if baton.jp2_chroma_subsampling == "4:4:4" {
-                        ForeignSubsample::Off
-                    } else {
-                        ForeignSubsample::On
-                    } as i32,
-                )
-                .set("tile_height", baton.jp2_tile_height)
-                .set("tile_width", baton.jp2_tile_width),
-        )?;
+        image
+            .jp2ksave_with_opts(
+                &baton.file_out,
+                VOption::new()
+                    .set("Q", baton.jp2_quality)
+                    .set("lossless", baton.jp2_lossless)
+                    .set(
+                        "subsample_mode",
+                        if baton.jp2_chroma_subsampling == "4:4:4" {
+                            ForeignSubsample::Off
+                        } else {
+                            ForeignSubsample::On
+                        } as i32,
+                    )
+                    .set("tile_height", baton.jp2_tile_height)
+                    .set("tile_width", baton.jp2_tile_width),
+            )
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "jp2".to_string();
     } else if baton.format_out == "png"
         || (might_match_input && is_png)
@@ -2150,33 +2644,35 @@ fn write_to_file(
     {
         // Write PNG to file
         assert_image_type_dimensions(&image, ImageType::Png)?;
-        image.pngsave_with_opts(
-            &baton.file_out,
-            VOption::new()
-                .set("keep", baton.keep_metadata)
-                .set("interlace", baton.png_progressive)
-                .set("compression", baton.png_compression_level)
-                .set(
-                    "filter",
-                    if baton.png_adaptive_filtering {
-                        ForeignPngFilter::All
-                    } else {
-                        ForeignPngFilter::None
-                    } as i32,
-                )
-                .set("palette", baton.png_palette)
-                .set("Q", baton.png_quality)
-                .set(
-                    "bitdepth",
-                    if is16_bit(image.get_interpretation()?) {
-                        16
-                    } else {
-                        baton.png_bitdepth
-                    },
-                )
-                .set("effort", baton.png_effort)
-                .set("dither", baton.png_dither),
-        )?;
+        image
+            .pngsave_with_opts(
+                &baton.file_out,
+                VOption::new()
+                    .set("keep", baton.keep_metadata)
+                    .set("interlace", baton.png_progressive)
+                    .set("compression", baton.png_compression_level)
+                    .set(
+                        "filter",
+                        if baton.png_adaptive_filtering {
+                            ForeignPngFilter::All
+                        } else {
+                            ForeignPngFilter::None
+                        } as i32,
+                    )
+                    .set("palette", baton.png_palette)
+                    .set("Q", baton.png_quality)
+                    .set(
+                        "bitdepth",
+                        if is16_bit(image.get_interpretation()?) {
+                            16
+                        } else {
+                            baton.png_bitdepth
+                        },
+                    )
+                    .set("effort", baton.png_effort)
+                    .set("dither", baton.png_dither),
+            )
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "png".to_string();
     } else if baton.format_out == "webp"
         || (might_match_input && is_webp)
@@ -2184,21 +2680,23 @@ fn write_to_file(
     {
         // Write WEBP to file
         assert_image_type_dimensions(&image, ImageType::Webp)?;
-        image.webpsave_with_opts(
-            &baton.file_out,
-            VOption::new()
-                .set("keep", baton.keep_metadata)
-                .set("Q", baton.webp_quality)
-                .set("lossless", baton.webp_lossless)
-                .set("near_lossless", baton.webp_near_lossless)
-                .set("smart_subsample", baton.webp_smart_subsample)
-                .set("smart_deblock", baton.webp_smart_deblock)
-                .set("preset", baton.webp_preset as i32)
-                .set("effort", baton.webp_effort)
-                .set("min_size", baton.webp_min_size)
-                .set("mixed", baton.webp_mixed)
-                .set("alpha_q", baton.webp_alpha_quality),
-        )?;
+        image
+            .webpsave_with_opts(
+                &baton.file_out,
+                VOption::new()
+                    .set("keep", baton.keep_metadata)
+                    .set("Q", baton.webp_quality)
+                    .set("lossless", baton.webp_lossless)
+                    .set("near_lossless", baton.webp_near_lossless)
+                    .set("smart_subsample", baton.webp_smart_subsample)
+                    .set("smart_deblock", baton.webp_smart_deblock)
+                    .set("preset", baton.webp_preset as i32)
+                    .set("effort", baton.webp_effort)
+                    .set("min_size", baton.webp_min_size)
+                    .set("mixed", baton.webp_mixed)
+                    .set("alpha_q", baton.webp_alpha_quality),
+            )
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "webp".to_string();
     } else if baton.format_out == "gif"
         || (might_match_input && is_gif)
@@ -2206,16 +2704,18 @@ fn write_to_file(
     {
         // Write GIF to file
         assert_image_type_dimensions(&image, ImageType::GIF)?;
-        image.gifsave_with_opts(
-            &baton.file_out,
-            VOption::new()
-                .set("keep", baton.keep_metadata)
-                .set("bitdepth", baton.gif_bitdepth)
-                .set("effort", baton.gif_effort)
-                .set("reuse", baton.gif_reuse)
-                .set("interlace", baton.gif_progressive)
-                .set("dither", baton.gif_dither),
-        )?;
+        image
+            .gifsave_with_opts(
+                &baton.file_out,
+                VOption::new()
+                    .set("keep", baton.keep_metadata)
+                    .set("bitdepth", baton.gif_bitdepth)
+                    .set("effort", baton.gif_effort)
+                    .set("reuse", baton.gif_reuse)
+                    .set("interlace", baton.gif_progressive)
+                    .set("dither", baton.gif_dither),
+            )
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "gif".to_string();
     } else if baton.format_out == "tiff"
         || (might_match_input && is_tiff)
@@ -2230,23 +2730,25 @@ fn write_to_file(
         if baton.tiff_predictor == ForeignTiffPredictor::Float {
             image = image.cast(BandFormat::Float)?;
         }
-        image.tiffsave_with_opts(
-            &baton.file_out,
-            VOption::new()
-                .set("keep", baton.keep_metadata)
-                .set("Q", baton.tiff_quality)
-                .set("bitdepth", baton.tiff_bitdepth)
-                .set("compression", baton.tiff_compression as i32)
-                .set("miniswhite", baton.tiff_miniswhite)
-                .set("predictor", baton.tiff_predictor as i32)
-                .set("pyramid", baton.tiff_pyramid)
-                .set("tile", baton.tiff_tile)
-                .set("tile_height", baton.tiff_tile_height)
-                .set("tile_width", baton.tiff_tile_width)
-                .set("xres", baton.tiff_xres)
-                .set("yres", baton.tiff_yres)
-                .set("resunit", baton.tiff_resolution_unit as i32),
-        )?;
+        image
+            .tiffsave_with_opts(
+                &baton.file_out,
+                VOption::new()
+                    .set("keep", baton.keep_metadata)
+                    .set("Q", baton.tiff_quality)
+                    .set("bitdepth", baton.tiff_bitdepth)
+                    .set("compression", baton.tiff_compression as i32)
+                    .set("miniswhite", baton.tiff_miniswhite)
+                    .set("predictor", baton.tiff_predictor as i32)
+                    .set("pyramid", baton.tiff_pyramid)
+                    .set("tile", baton.tiff_tile)
+                    .set("tile_height", baton.tiff_tile_height)
+                    .set("tile_width", baton.tiff_tile_width)
+                    .set("xres", baton.tiff_xres)
+                    .set("yres", baton.tiff_yres)
+                    .set("resunit", baton.tiff_resolution_unit as i32),
+            )
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "tiff".to_string();
     } else if baton.format_out == "heif"
         || (might_match_input && is_heif)
@@ -2255,24 +2757,26 @@ fn write_to_file(
         // Write HEIF to file
         assert_image_type_dimensions(&image, ImageType::HEIF)?;
         image = remove_animation_properties(image)?;
-        image.heifsave_with_opts(
-            &baton.file_out,
-            VOption::new()
-                .set("keep", baton.keep_metadata)
-                .set("Q", baton.heif_quality)
-                .set("compression", baton.heif_compression as i32)
-                .set("effort", baton.heif_effort)
-                .set("bitdepth", baton.heif_bitdepth)
-                .set(
-                    "subsample_mode",
-                    if baton.heif_chroma_subsampling == *"4:4:4" {
-                        ForeignSubsample::Off
-                    } else {
-                        ForeignSubsample::On
-                    } as i32,
-                )
-                .set("lossless", baton.heif_lossless),
-        )?;
+        image
+            .heifsave_with_opts(
+                &baton.file_out,
+                VOption::new()
+                    .set("keep", baton.keep_metadata)
+                    .set("Q", baton.heif_quality)
+                    .set("compression", baton.heif_compression as i32)
+                    .set("effort", baton.heif_effort)
+                    .set("bitdepth", baton.heif_bitdepth)
+                    .set(
+                        "subsample_mode",
+                        if baton.heif_chroma_subsampling == *"4:4:4" {
+                            ForeignSubsample::Off
+                        } else {
+                            ForeignSubsample::On
+                        } as i32,
+                    )
+                    .set("lossless", baton.heif_lossless),
+            )
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "heif".to_string();
     } else if baton.format_out == "jxl"
         || (might_match_input && is_jxl)
@@ -2280,15 +2784,17 @@ fn write_to_file(
     {
         // Write JXL to file
         image = remove_animation_properties(image)?;
-        image.jxlsave_with_opts(
-            &baton.file_out,
-            VOption::new()
-                .set("keep", baton.keep_metadata)
-                .set("distance", baton.jxl_distance)
-                .set("tier", baton.jxl_decoding_tier)
-                .set("effort", baton.jxl_effort)
-                .set("lossless", baton.jxl_lossless),
-        )?;
+        image
+            .jxlsave_with_opts(
+                &baton.file_out,
+                VOption::new()
+                    .set("keep", baton.keep_metadata)
+                    .set("distance", baton.jxl_distance)
+                    .set("tier", baton.jxl_decoding_tier)
+                    .set("effort", baton.jxl_effort)
+                    .set("lossless", baton.jxl_lossless),
+            )
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "jxl".to_string();
     } else if baton.format_out == "dz" || is_dz || is_dz_zip {
         // Write DZ to file
@@ -2319,7 +2825,9 @@ fn write_to_file(
         if !baton.tile_basename.is_empty() {
             options.add("basename", &baton.tile_basename);
         }
-        image.dzsave_with_opts(&baton.file_out, options)?;
+        image
+            .dzsave_with_opts(&baton.file_out, options)
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "dz".to_string();
     } else if baton.format_out == "v"
         || (might_match_input && is_v)
@@ -2327,12 +2835,13 @@ fn write_to_file(
     {
         // Write V to file
         image
-            .vipssave_with_opts(&baton.file_out, VOption::new().set("keep", baton.keep_metadata))?;
+            .vipssave_with_opts(&baton.file_out, VOption::new().set("keep", baton.keep_metadata))
+            .map_err(|e| vips_error(SharpError::Encode, e))?;
         baton.format_out = "v".to_string();
     } else {
         // Unsupported output format
         baton.err.push_str(&format!("Unsupported output format {}", baton.file_out));
-        return Err(OperationError(baton.err.clone()));
+        return Err(SharpError::UnsupportedFormat(baton.err.clone()));
     }
 
     Ok(baton)
@@ -2342,7 +2851,7 @@ fn write_to_file(
     Calculate the angle of rotation and need-to-flip for the given Exif orientation
     By default, returns zero, i.e. no rotation.
 */
-fn calculate_exif_rotation_and_flop(exif_orientation: i32) -> (Angle, bool) {
+pub(crate) fn calculate_exif_rotation_and_flop(exif_orientation: i32) -> (Angle, bool) {
     let mut rotate = Angle::D0;
     let mut flop = false;
     match exif_orientation {
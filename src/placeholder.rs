@@ -0,0 +1,617 @@
+use crate::{
+    input::{CreateRaw, SharpOptions},
+    pipeline::init_options,
+    resize::{Fit, ResizeOptions},
+    Interpretation, InvalidParameterError, Sharp, SharpError,
+};
+use std::f64::consts::PI;
+
+// Every pixel sums all components, so larger placeholders are upscaled with resize instead
+const BLURHASH_MAX_SIZE: i32 = 256;
+
+const BASE83: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+impl Sharp {
+    /**
+     * Compute the BlurHash of the input image, a short string that decodes to a blurred placeholder.
+     *
+     * The input is auto-oriented and shrunk on load to at most 32x32 pixels,
+     * without processing the operations of the pipeline or encoding any output.
+     *
+     * `x_components` and `y_components` set the level of detail, each an integer between 1 and 9.
+     *
+     * @example
+     * let hash = Sharp::new_from_file("input.jpg")?.blurhash(4, 3)?;
+     */
+    pub fn blurhash(&self, x_components: u32, y_components: u32) -> Result<String, SharpError> {
+        if !(1..=9).contains(&x_components) {
+            return Err(InvalidParameterError!(
+                "xComponents",
+                "integer between 1 and 9",
+                x_components
+            ));
+        }
+        if !(1..=9).contains(&y_components) {
+            return Err(InvalidParameterError!(
+                "yComponents",
+                "integer between 1 and 9",
+                y_components
+            ));
+        }
+
+        let (rgba, width, height) = self.thumbnail_rgba(32)?;
+        Ok(blurhash_encode(&rgba, width, height, x_components as usize, y_components as usize))
+    }
+
+    /**
+     * Compute the ThumbHash of the input image, a few bytes that decode to a blurred placeholder
+     * with its aspect ratio and alpha channel.
+     *
+     * The input is auto-oriented and shrunk on load to at most 100x100 pixels,
+     * without processing the operations of the pipeline or encoding any output.
+     *
+     * @example
+     * let hash = Sharp::new_from_file("input.png")?.thumbhash()?;
+     */
+    pub fn thumbhash(&self) -> Result<Vec<u8>, SharpError> {
+        let (rgba, width, height) = self.thumbnail_rgba(100)?;
+        Ok(thumbhash_encode(&rgba, width, height))
+    }
+
+    /**
+     * Create a pipeline whose input is the image of a BlurHash, rendered at `width` x `height` pixels.
+     *
+     * `width` and `height` are each an integer between 1 and 256, use {@link resize} for a larger image.
+     *
+     * @example
+     * Sharp::new_from_blurhash("LEHV6nWB2yk8pyo0adR*.7kCMdnj", 32, 32)?
+     *   .resize(320, 320)?
+     *   .to_file("placeholder.png")?;
+     */
+    pub fn new_from_blurhash(hash: &str, width: i32, height: i32) -> Result<Self, SharpError> {
+        if !(1..=BLURHASH_MAX_SIZE).contains(&width) {
+            return Err(InvalidParameterError!("width", "integer between 1 and 256", width));
+        }
+        if !(1..=BLURHASH_MAX_SIZE).contains(&height) {
+            return Err(InvalidParameterError!("height", "integer between 1 and 256", height));
+        }
+        let rgb = blurhash_decode(hash, width as usize, height as usize)
+            .ok_or_else(|| InvalidParameterError!("hash", "valid BlurHash", hash))?;
+        Self::new_from_placeholder(rgb, width, height, 3)
+    }
+
+    /**
+     * Create a pipeline whose input is the image of a ThumbHash, rendered at most 32 pixels wide and high.
+     *
+     * @example
+     * Sharp::new_from_thumbhash(&hash)?.resize(320, 320)?.to_file("placeholder.png")?;
+     */
+    pub fn new_from_thumbhash(hash: &[u8]) -> Result<Self, SharpError> {
+        let (rgba, width, height) = thumbhash_decode(hash)
+            .ok_or_else(|| InvalidParameterError!("hash", "valid ThumbHash", hash))?;
+        Self::new_from_placeholder(rgba, width as i32, height as i32, 4)
+    }
+
+    fn new_from_placeholder(
+        pixels: Vec<u8>,
+        width: i32,
+        height: i32,
+        channels: i32,
+    ) -> Result<Self, SharpError> {
+        Self::new_from_buffer_with_opts(
+            pixels,
+            SharpOptions {
+                raw: Some(CreateRaw {
+                    width,
+                    height,
+                    channels,
+                    premultiplied: false,
+                    page_height: None,
+                }),
+                ..Default::default()
+            },
+        )
+    }
+
+    /*
+      Auto-oriented sRGB pixels with alpha, shrunk on load to fit within size x size, never enlarged
+    */
+    pub(crate) fn thumbnail_rgba(&self, size: i32) -> Result<(Vec<u8>, usize, usize), SharpError> {
+        let mut options = init_options();
+        options.input = self.options.input.clone();
+        options.input.auto_orient = true;
+        options.timeout_seconds = self.options.timeout_seconds;
+
+        let (data, info) = Sharp {
+            options,
+        }
+        .resize_with_opts(ResizeOptions {
+            width: Some(size),
+            height: Some(size),
+            fit: Some(Fit::Inside),
+            without_enlargement: Some(true),
+            ..Default::default()
+        })?
+        .to_colourspace(Interpretation::Srgb)
+        .ensure_alpha(1.0)?
+        .raw(None)?
+        .to_buffer_with_info()?;
+
+        Ok((data, info.width as usize, info.height as usize))
+    }
+}
+
+pub(crate) fn srgb_to_linear(value: u8) -> f64 {
+    let value = value as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(value: f64) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.0031308 {
+        (value * 12.92 * 255.0 + 0.5) as u8
+    } else {
+        ((1.055 * value.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as u8
+    }
+}
+
+fn sign_pow(value: f64, exponent: f64) -> f64 {
+    value.abs().powf(exponent).copysign(value)
+}
+
+fn encode83(value: usize, length: usize, hash: &mut String) {
+    for i in 1..=length {
+        let digit = (value / 83usize.pow((length - i) as u32)) % 83;
+        hash.push(BASE83[digit] as char);
+    }
+}
+
+fn decode83(value: &str) -> Option<usize> {
+    value.bytes().try_fold(0, |acc, byte| {
+        BASE83.iter().position(|digit| *digit == byte).map(|digit| acc * 83 + digit)
+    })
+}
+
+/*
+  https://github.com/woltapp/blurhash/blob/master/Algorithm.md
+*/
+fn blurhash_encode(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    x_components: usize,
+    y_components: usize,
+) -> String {
+    let mut factors = Vec::with_capacity(x_components * y_components);
+    for j in 0..y_components {
+        for i in 0..x_components {
+            let normalisation = if i == 0 && j == 0 {
+                1.0
+            } else {
+                2.0
+            };
+            let mut sums = [0.0; 3];
+            for y in 0..height {
+                let basis_y = (PI * j as f64 * y as f64 / height as f64).cos();
+                for x in 0..width {
+                    let basis = basis_y * (PI * i as f64 * x as f64 / width as f64).cos();
+                    let pixel = &rgba[(y * width + x) * 4..(y * width + x) * 4 + 3];
+                    for (sum, value) in sums.iter_mut().zip(pixel) {
+                        *sum += basis * srgb_to_linear(*value);
+                    }
+                }
+            }
+            let scale = normalisation / (width * height) as f64;
+            factors.push(sums.map(|sum| sum * scale));
+        }
+    }
+
+    let mut hash = String::new();
+    encode83((x_components - 1) + (y_components - 1) * 9, 1, &mut hash);
+
+    let ac = &factors[1..];
+    let maximum_value = if ac.is_empty() {
+        encode83(0, 1, &mut hash);
+        1.0
+    } else {
+        let actual_maximum = ac
+            .iter()
+            .flat_map(|factor| factor.iter())
+            .fold(0.0_f64, |max, value| max.max(value.abs()));
+        let quantised_maximum = (actual_maximum * 166.0 - 0.5).floor().clamp(0.0, 82.0) as usize;
+        encode83(quantised_maximum, 1, &mut hash);
+        (quantised_maximum + 1) as f64 / 166.0
+    };
+
+    let dc = factors[0];
+    let dc_value = ((linear_to_srgb(dc[0]) as usize) << 16)
+        + ((linear_to_srgb(dc[1]) as usize) << 8)
+        + linear_to_srgb(dc[2]) as usize;
+    encode83(dc_value, 4, &mut hash);
+
+    for factor in ac {
+        let quantised = factor.map(|value| {
+            (sign_pow(value / maximum_value, 0.5) * 9.0 + 9.5).floor().clamp(0.0, 18.0) as usize
+        });
+        encode83(quantised[0] * 19 * 19 + quantised[1] * 19 + quantised[2], 2, &mut hash);
+    }
+
+    hash
+}
+
+fn blurhash_decode(hash: &str, width: usize, height: usize) -> Option<Vec<u8>> {
+    if hash.len() < 6 || !hash.is_ascii() {
+        return None;
+    }
+    let size_flag = decode83(&hash[0..1])?;
+    let x_components = size_flag % 9 + 1;
+    let y_components = size_flag / 9 + 1;
+    if hash.len() != 4 + 2 * x_components * y_components {
+        return None;
+    }
+    let maximum_value = (decode83(&hash[1..2])? + 1) as f64 / 166.0;
+
+    let mut colours = Vec::with_capacity(x_components * y_components);
+    let dc = decode83(&hash[2..6])?;
+    colours.push([
+        srgb_to_linear((dc >> 16) as u8),
+        srgb_to_linear((dc >> 8) as u8),
+        srgb_to_linear(dc as u8),
+    ]);
+    for i in 1..x_components * y_components {
+        let value = decode83(&hash[4 + i * 2..6 + i * 2])?;
+        let quantised = [value / (19 * 19), (value / 19) % 19, value % 19];
+        colours.push(quantised.map(|q| sign_pow((q as f64 - 9.0) / 9.0, 2.0) * maximum_value));
+    }
+
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let mut pixel = [0.0; 3];
+            for j in 0..y_components {
+                for i in 0..x_components {
+                    let basis = (PI * x as f64 * i as f64 / width as f64).cos()
+                        * (PI * y as f64 * j as f64 / height as f64).cos();
+                    for (sum, value) in pixel.iter_mut().zip(colours[i + j * x_components]) {
+                        *sum += value * basis;
+                    }
+                }
+            }
+            rgb.extend(pixel.map(linear_to_srgb));
+        }
+    }
+
+    Some(rgb)
+}
+
+/*
+  DC term, normalised AC terms and scale of a channel, see thumbhash_encode
+*/
+fn thumbhash_encode_channel(
+    channel: &[f64],
+    width: usize,
+    height: usize,
+    nx: usize,
+    ny: usize,
+) -> (f64, Vec<f64>, f64) {
+    let mut dc = 0.0;
+    let mut ac = Vec::new();
+    let mut scale = 0.0_f64;
+    for cy in 0..ny {
+        let mut cx = 0;
+        while cx * ny < nx * (ny - cy) {
+            let fx: Vec<f64> = (0..width)
+                .map(|x| (PI / width as f64 * cx as f64 * (x as f64 + 0.5)).cos())
+                .collect();
+            let mut f = 0.0;
+            for y in 0..height {
+                let fy = (PI / height as f64 * cy as f64 * (y as f64 + 0.5)).cos();
+                let row = &channel[y * width..(y + 1) * width];
+                f += row.iter().zip(fx.iter()).map(|(value, fx)| value * fx * fy).sum::<f64>();
+            }
+            f /= (width * height) as f64;
+            if cx > 0 || cy > 0 {
+                ac.push(f);
+                scale = scale.max(f.abs());
+            } else {
+                dc = f;
+            }
+            cx += 1;
+        }
+    }
+    if scale > 0.0 {
+        for value in ac.iter_mut() {
+            *value = 0.5 + 0.5 / scale * *value;
+        }
+    }
+    (dc, ac, scale)
+}
+
+/*
+  https://github.com/evanw/thumbhash
+*/
+fn thumbhash_encode(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    // Average colour
+    let (mut avg_r, mut avg_g, mut avg_b, mut avg_a) = (0.0, 0.0, 0.0, 0.0);
+    for pixel in rgba.chunks_exact(4) {
+        let alpha = pixel[3] as f64 / 255.0;
+        avg_r += alpha / 255.0 * pixel[0] as f64;
+        avg_g += alpha / 255.0 * pixel[1] as f64;
+        avg_b += alpha / 255.0 * pixel[2] as f64;
+        avg_a += alpha;
+    }
+    if avg_a > 0.0 {
+        avg_r /= avg_a;
+        avg_g /= avg_a;
+        avg_b /= avg_a;
+    }
+
+    let has_alpha = avg_a < (width * height) as f64;
+    // Fewer luminance bits when there is alpha
+    let l_limit = if has_alpha {
+        5.0
+    } else {
+        7.0
+    };
+    let longest = width.max(height) as f64;
+    let lx = ((l_limit * width as f64 / longest).round() as usize).max(1);
+    let ly = ((l_limit * height as f64 / longest).round() as usize).max(1);
+
+    // RGBA to LPQA, composited atop the average colour
+    let mut l = Vec::with_capacity(width * height);
+    let mut p = Vec::with_capacity(width * height);
+    let mut q = Vec::with_capacity(width * height);
+    let mut a = Vec::with_capacity(width * height);
+    for pixel in rgba.chunks_exact(4) {
+        let alpha = pixel[3] as f64 / 255.0;
+        let r = avg_r * (1.0 - alpha) + alpha / 255.0 * pixel[0] as f64;
+        let g = avg_g * (1.0 - alpha) + alpha / 255.0 * pixel[1] as f64;
+        let b = avg_b * (1.0 - alpha) + alpha / 255.0 * pixel[2] as f64;
+        l.push((r + g + b) / 3.0);
+        p.push((r + g) / 2.0 - b);
+        q.push(r - g);
+        a.push(alpha);
+    }
+
+    let (l_dc, l_ac, l_scale) = thumbhash_encode_channel(&l, width, height, lx.max(3), ly.max(3));
+    let (p_dc, p_ac, p_scale) = thumbhash_encode_channel(&p, width, height, 3, 3);
+    let (q_dc, q_ac, q_scale) = thumbhash_encode_channel(&q, width, height, 3, 3);
+    let (a_dc, a_ac, a_scale) = if has_alpha {
+        thumbhash_encode_channel(&a, width, height, 5, 5)
+    } else {
+        (1.0, Vec::new(), 1.0)
+    };
+
+    // Constants
+    let is_landscape = width > height;
+    let header24 = (63.0 * l_dc).round() as u32
+        | ((31.5 + 31.5 * p_dc).round() as u32) << 6
+        | ((31.5 + 31.5 * q_dc).round() as u32) << 12
+        | ((31.0 * l_scale).round() as u32) << 18
+        | (has_alpha as u32) << 23;
+    let header16 = (if is_landscape {
+        ly
+    } else {
+        lx
+    }) as u32
+        | ((63.0 * p_scale).round() as u32) << 3
+        | ((63.0 * q_scale).round() as u32) << 9
+        | (is_landscape as u32) << 15;
+    let mut hash = vec![
+        (header24 & 255) as u8,
+        ((header24 >> 8) & 255) as u8,
+        (header24 >> 16) as u8,
+        (header16 & 255) as u8,
+        (header16 >> 8) as u8,
+    ];
+    if has_alpha {
+        hash.push((15.0 * a_dc).round() as u8 | ((15.0 * a_scale).round() as u8) << 4);
+    }
+
+    // Varying factors, two per byte
+    let mut channels = vec![l_ac, p_ac, q_ac];
+    if has_alpha {
+        channels.push(a_ac);
+    }
+    let ac_start = hash.len();
+    for (index, factor) in channels.iter().flatten().enumerate() {
+        if ac_start + (index >> 1) >= hash.len() {
+            hash.push(0);
+        }
+        hash[ac_start + (index >> 1)] |= ((15.0 * factor).round() as u8) << ((index & 1) << 2);
+    }
+
+    hash
+}
+
+/*
+  Aspect ratio of a ThumbHash
+*/
+fn thumbhash_aspect_ratio(hash: &[u8]) -> f64 {
+    let has_alpha = hash[2] & 0x80 != 0;
+    let is_landscape = hash[4] & 0x80 != 0;
+    let header = (hash[3] & 7) as f64;
+    let limit = if has_alpha {
+        5.0
+    } else {
+        7.0
+    };
+    if is_landscape {
+        limit / header
+    } else {
+        header / limit
+    }
+}
+
+fn thumbhash_decode(hash: &[u8]) -> Option<(Vec<u8>, usize, usize)> {
+    if hash.len() < 5 {
+        return None;
+    }
+
+    // Constants
+    let header24 = hash[0] as u32 | (hash[1] as u32) << 8 | (hash[2] as u32) << 16;
+    let header16 = hash[3] as u32 | (hash[4] as u32) << 8;
+    let l_dc = (header24 & 63) as f64 / 63.0;
+    let p_dc = ((header24 >> 6) & 63) as f64 / 31.5 - 1.0;
+    let q_dc = ((header24 >> 12) & 63) as f64 / 31.5 - 1.0;
+    let l_scale = ((header24 >> 18) & 31) as f64 / 31.0;
+    let has_alpha = (header24 >> 23) != 0;
+    let p_scale = ((header16 >> 3) & 63) as f64 / 63.0;
+    let q_scale = ((header16 >> 9) & 63) as f64 / 63.0;
+    let is_landscape = (header16 >> 15) != 0;
+    let limit = if has_alpha {
+        5
+    } else {
+        7
+    };
+    let lx = (if is_landscape {
+        limit
+    } else {
+        (header16 & 7) as usize
+    })
+    .max(3);
+    let ly = (if is_landscape {
+        (header16 & 7) as usize
+    } else {
+        limit
+    })
+    .max(3);
+    if (header16 & 7) == 0 || (has_alpha && hash.len() < 6) {
+        return None;
+    }
+    let a_dc = if has_alpha {
+        (hash[5] & 15) as f64 / 15.0
+    } else {
+        1.0
+    };
+    let a_scale = if has_alpha {
+        (hash[5] >> 4) as f64 / 15.0
+    } else {
+        0.0
+    };
+
+    // Varying factors, with saturation boosted by 1.25 to compensate for quantisation
+    let ac_start = if has_alpha {
+        6
+    } else {
+        5
+    };
+    let mut ac_index = 0;
+    let mut decode_channel = |nx: usize, ny: usize, scale: f64| -> Option<Vec<f64>> {
+        let mut ac = Vec::new();
+        for cy in 0..ny {
+            let mut cx = if cy > 0 {
+                0
+            } else {
+                1
+            };
+            while cx * ny < nx * (ny - cy) {
+                let byte = *hash.get(ac_start + (ac_index >> 1))?;
+                let nibble = (byte >> ((ac_index & 1) << 2)) & 15;
+                ac.push((nibble as f64 / 7.5 - 1.0) * scale);
+                ac_index += 1;
+                cx += 1;
+            }
+        }
+        Some(ac)
+    };
+    let l_ac = decode_channel(lx, ly, l_scale)?;
+    let p_ac = decode_channel(3, 3, p_scale * 1.25)?;
+    let q_ac = decode_channel(3, 3, q_scale * 1.25)?;
+    let a_ac = if has_alpha {
+        decode_channel(5, 5, a_scale)?
+    } else {
+        Vec::new()
+    };
+
+    // Inverse DCT to RGBA
+    let ratio = thumbhash_aspect_ratio(hash);
+    let width = (if ratio > 1.0 {
+        32.0
+    } else {
+        32.0 * ratio
+    })
+    .round() as usize;
+    let height = (if ratio > 1.0 {
+        32.0 / ratio
+    } else {
+        32.0
+    })
+    .round() as usize;
+    let alpha_components = if has_alpha {
+        5
+    } else {
+        3
+    };
+    let fx_len = lx.max(alpha_components);
+    let fy_len = ly.max(alpha_components);
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let (mut l, mut p, mut q, mut a) = (l_dc, p_dc, q_dc, a_dc);
+            let fx: Vec<f64> = (0..fx_len)
+                .map(|cx| (PI / width as f64 * (x as f64 + 0.5) * cx as f64).cos())
+                .collect();
+            let fy: Vec<f64> = (0..fy_len)
+                .map(|cy| (PI / height as f64 * (y as f64 + 0.5) * cy as f64).cos())
+                .collect();
+
+            let mut j = 0;
+            for cy in 0..ly {
+                let mut cx = if cy > 0 {
+                    0
+                } else {
+                    1
+                };
+                while cx * ly < lx * (ly - cy) {
+                    l += l_ac[j] * fx[cx] * fy[cy] * 2.0;
+                    j += 1;
+                    cx += 1;
+                }
+            }
+
+            let mut j = 0;
+            for cy in 0..3 {
+                let start = if cy > 0 {
+                    0
+                } else {
+                    1
+                };
+                for fx in &fx[start..3 - cy] {
+                    let f = fx * fy[cy] * 2.0;
+                    p += p_ac[j] * f;
+                    q += q_ac[j] * f;
+                    j += 1;
+                }
+            }
+
+            if has_alpha {
+                let mut j = 0;
+                for cy in 0..5 {
+                    let start = if cy > 0 {
+                        0
+                    } else {
+                        1
+                    };
+                    for fx in &fx[start..5 - cy] {
+                        a += a_ac[j] * fx * fy[cy] * 2.0;
+                        j += 1;
+                    }
+                }
+            }
+
+            let b = l - 2.0 / 3.0 * p;
+            let r = (3.0 * l - b + q) / 2.0;
+            let g = r - q;
+            rgba.extend([r, g, b, a].map(|value| (255.0 * value.clamp(0.0, 1.0)) as u8));
+        }
+    }
+
+    Some((rgba, width, height))
+}
@@ -0,0 +1,552 @@
+use crate::{
+    editorial::EditorialFields,
+    input::RotateOptions,
+    operation::{
+        AffineOptions, BlurOptions, ClaheOptions, FlattenOptions, KernelOptions, ModulateOptions,
+        NegateOptions, NormaliseOptions, SharpenOptions, ThresholdOptions,
+    },
+    output::{
+        AvifOptions, Exif, GifOptions, HeifOptions, Jp2Options, JpegOptions, JxlOptions,
+        MetadataPolicy, PngOptions, RawOptions, TiffOptions, TileOptions, WebpOptions,
+        WithIccProfileOptions, WriteableMetadata,
+    },
+    resize::{ExtendOptions, Region, ResizeOptions, TrimOptions},
+    Colour, Sharp, SharpError,
+};
+use num_traits::FromPrimitive;
+use rs_vips::{
+    bindings::{
+        vips_band_format_get_type, vips_enum_from_nick, vips_enum_nick, vips_extend_get_type,
+        vips_foreign_dz_container_get_type, vips_foreign_dz_depth_get_type,
+        vips_foreign_dz_layout_get_type, vips_foreign_heif_compression_get_type,
+        vips_foreign_tiff_compression_get_type, vips_foreign_tiff_predictor_get_type,
+        vips_foreign_tiff_resunit_get_type, vips_foreign_webp_preset_get_type,
+        vips_interpretation_get_type, vips_kernel_get_type, vips_operation_boolean_get_type,
+        vips_precision_get_type, GType,
+    },
+    ops::{
+        BandFormat, Extend, ForeignDzContainer, ForeignDzDepth, ForeignDzLayout,
+        ForeignHeifCompression, ForeignTiffCompression, ForeignTiffPredictor, ForeignTiffResunit,
+        ForeignWebpPreset, Interpretation, Kernel, OperationBoolean, Precision,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::ffi::{CStr, CString};
+
+/**
+ * Declarative description of a pipeline, serializable with any serde format such as JSON or TOML.
+ *
+ * Operations are replayed in order through the same builder methods, and therefore the same validation,
+ * as when the pipeline is written in code.
+ * Operations that reference other images, such as `composite`, `boolean` and `join_channel`, are not recorded.
+ *
+ * @example
+ * let recipe: Recipe = serde_json::from_str(r#"{
+ *   "operations": [
+ *     { "op": "resize", "options": { "width": 300, "height": 200, "fit": "inside" } },
+ *     { "op": "sharpen", "options": { "sigma": 1.0 } },
+ *     { "op": "webp", "options": { "quality": 80 } }
+ *   ]
+ * }"#)?;
+ * let data = Sharp::new_from_file("input.jpg")?.apply_recipe(&recipe)?.to_buffer()?;
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recipe {
+    #[serde(default)]
+    pub operations: Vec<Operation>,
+}
+
+/** A single builder call, named after the method and holding its arguments. */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Timeout {
+        seconds: u32,
+    },
+    Ordered {
+        ordered: bool,
+    },
+    Rotate {
+        angle: i32,
+        options: Option<RotateOptions>,
+    },
+    AutoOrient,
+    Flip {
+        flip: bool,
+    },
+    Flop {
+        flop: bool,
+    },
+    Affine {
+        matrix: Vec<Vec<f64>>,
+        options: Option<AffineOptions>,
+    },
+    Sharpen {
+        options: Option<SharpenOptions>,
+    },
+    Median {
+        size: Option<i32>,
+    },
+    Blur {
+        options: Option<BlurOptions>,
+    },
+    Dilate {
+        width: Option<i32>,
+    },
+    Erode {
+        width: Option<i32>,
+    },
+    Flatten {
+        options: Option<FlattenOptions>,
+    },
+    Unflatten,
+    Gamma {
+        gamma: Option<f64>,
+        gamma_out: Option<f64>,
+    },
+    Negate {
+        negate: bool,
+        options: Option<NegateOptions>,
+    },
+    Normalise {
+        options: Option<NormaliseOptions>,
+    },
+    Clahe {
+        options: Option<ClaheOptions>,
+    },
+    Convolve {
+        kernel: KernelOptions,
+    },
+    Threshold {
+        threshold: Option<i32>,
+        options: Option<ThresholdOptions>,
+    },
+    Linear {
+        a: Option<Vec<f64>>,
+        b: Option<Vec<f64>>,
+    },
+    Recomb {
+        matrix: Vec<Vec<f64>>,
+    },
+    Modulate {
+        options: Option<ModulateOptions>,
+    },
+    Resize {
+        options: ResizeOptions,
+    },
+    Extend {
+        options: ExtendOptions,
+    },
+    Extract {
+        region: Region,
+    },
+    Trim {
+        options: Option<TrimOptions>,
+    },
+    RemoveAlpha,
+    EnsureAlpha {
+        alpha: f64,
+    },
+    ExtractChannel {
+        channel: u32,
+    },
+    Bandbool {
+        #[serde(with = "vips_enum")]
+        operator: OperationBoolean,
+    },
+    Tint {
+        colour: Colour,
+    },
+    Greyscale {
+        greyscale: bool,
+    },
+    PipelineColourspace {
+        #[serde(with = "vips_enum")]
+        colourspace: Interpretation,
+    },
+    ToColourspace {
+        #[serde(with = "vips_enum")]
+        colourspace: Interpretation,
+    },
+    KeepExif,
+    WithExif {
+        exif: Exif,
+    },
+    WithExifMerge {
+        exif: Exif,
+    },
+    KeepIccProfile,
+    WithIccProfile {
+        icc: String,
+        options: Option<WithIccProfileOptions>,
+    },
+    KeepXmp,
+    WithXmp {
+        xmp: String,
+    },
+    KeepMetadata,
+    StripMetadata {
+        policy: MetadataPolicy,
+    },
+    WithIptcFields {
+        fields: EditorialFields,
+    },
+    WithXmpFields {
+        fields: EditorialFields,
+    },
+    WithMetadata {
+        options: Option<WriteableMetadata>,
+    },
+    Jpeg {
+        options: Option<JpegOptions>,
+    },
+    Png {
+        options: Option<PngOptions>,
+    },
+    Webp {
+        options: Option<WebpOptions>,
+    },
+    Gif {
+        options: Option<GifOptions>,
+    },
+    Jp2 {
+        options: Option<Jp2Options>,
+    },
+    Tiff {
+        options: Option<TiffOptions>,
+    },
+    Avif {
+        options: Option<AvifOptions>,
+    },
+    Heif {
+        options: Option<HeifOptions>,
+    },
+    Jxl {
+        options: Option<JxlOptions>,
+    },
+    Raw {
+        options: Option<RawOptions>,
+    },
+    Tile {
+        options: Option<TileOptions>,
+    },
+}
+
+impl Sharp {
+    /**
+     * Apply the operations of a recipe, in order, as if the matching builder methods were called.
+     * Each operation is validated by its builder method, so an invalid recipe returns the same error.
+     *
+     * @example
+     * let recipe: Recipe = toml::from_str(&std::fs::read_to_string("thumbnail.toml")?)?;
+     * let data = Sharp::new_from_file("input.jpg")?.apply_recipe(&recipe)?.to_buffer()?;
+     */
+    pub fn apply_recipe(mut self, recipe: &Recipe) -> Result<Self, SharpError> {
+        for operation in recipe.operations.iter().cloned() {
+            self = match operation {
+                Operation::Timeout {
+                    seconds,
+                } => self.timeout(seconds),
+                Operation::Ordered {
+                    ordered,
+                } => self.ordered(ordered),
+                Operation::Rotate {
+                    angle,
+                    options,
+                } => self.rotate(angle, options)?,
+                Operation::AutoOrient => self.auto_orient()?,
+                Operation::Flip {
+                    flip,
+                } => self.flip(flip)?,
+                Operation::Flop {
+                    flop,
+                } => self.flop(flop)?,
+                Operation::Affine {
+                    matrix,
+                    options,
+                } => self.affine(matrix, options)?,
+                Operation::Sharpen {
+                    options,
+                } => self.sharpen(options)?,
+                Operation::Median {
+                    size,
+                } => self.median(size)?,
+                Operation::Blur {
+                    options,
+                } => self.blur(options)?,
+                Operation::Dilate {
+                    width,
+                } => self.dilate(width)?,
+                Operation::Erode {
+                    width,
+                } => self.erode(width)?,
+                Operation::Flatten {
+                    options,
+                } => self.flatten(options)?,
+                Operation::Unflatten => self.unflatten()?,
+                Operation::Gamma {
+                    gamma,
+                    gamma_out,
+                } => self.gamma(gamma, gamma_out)?,
+                Operation::Negate {
+                    negate,
+                    options,
+                } => self.negate(negate, options)?,
+                Operation::Normalise {
+                    options,
+                } => self.normalise(options)?,
+                Operation::Clahe {
+                    options,
+                } => self.clahe(options)?,
+                Operation::Convolve {
+                    kernel,
+                } => self.convolve(kernel)?,
+                Operation::Threshold {
+                    threshold,
+                    options,
+                } => self.threshold(threshold, options)?,
+                Operation::Linear {
+                    a,
+                    b,
+                } => self.linear(a, b)?,
+                Operation::Recomb {
+                    matrix,
+                } => self.recomb(matrix)?,
+                Operation::Modulate {
+                    options,
+                } => self.modulate(options)?,
+                Operation::Resize {
+                    options,
+                } => self.resize_with_opts(options)?,
+                Operation::Extend {
+                    options,
+                } => self.extend(options)?,
+                Operation::Extract {
+                    region,
+                } => self.extract(region)?,
+                Operation::Trim {
+                    options,
+                } => self.trim(options)?,
+                Operation::RemoveAlpha => self.remove_alpha(),
+                Operation::EnsureAlpha {
+                    alpha,
+                } => self.ensure_alpha(alpha)?,
+                Operation::ExtractChannel {
+                    channel,
+                } => self.extract_channel(channel)?,
+                Operation::Bandbool {
+                    operator,
+                } => self.bandbool(operator),
+                Operation::Tint {
+                    colour,
+                } => self.tint(colour),
+                Operation::Greyscale {
+                    greyscale,
+                } => self.greyscale(greyscale),
+                Operation::PipelineColourspace {
+                    colourspace,
+                } => self.pipeline_colourspace(colourspace),
+                Operation::ToColourspace {
+                    colourspace,
+                } => self.to_colourspace(colourspace),
+                Operation::KeepExif => self.keep_exif(),
+                Operation::WithExif {
+                    exif,
+                } => self.with_exif(exif),
+                Operation::WithExifMerge {
+                    exif,
+                } => self.with_exif_merge(exif),
+                Operation::KeepIccProfile => self.keep_icc_profile(),
+                Operation::WithIccProfile {
+                    icc,
+                    options,
+                } => self.with_icc_profile(&icc, options),
+                Operation::KeepXmp => self.keep_xmp(),
+                Operation::WithXmp {
+                    xmp,
+                } => self.with_xmp(&xmp),
+                Operation::KeepMetadata => self.keep_metadata(),
+                Operation::StripMetadata {
+                    policy,
+                } => self.strip_metadata(policy),
+                Operation::WithIptcFields {
+                    fields,
+                } => self.with_iptc_fields(fields),
+                Operation::WithXmpFields {
+                    fields,
+                } => self.with_xmp_fields(fields),
+                Operation::WithMetadata {
+                    options,
+                } => self.with_metadata(options)?,
+                Operation::Jpeg {
+                    options,
+                } => self.jpeg(options)?,
+                Operation::Png {
+                    options,
+                } => self.png(options)?,
+                Operation::Webp {
+                    options,
+                } => self.webp(options)?,
+                Operation::Gif {
+                    options,
+                } => self.gif(options)?,
+                Operation::Jp2 {
+                    options,
+                } => self.jp2(options)?,
+                Operation::Tiff {
+                    options,
+                } => self.tiff(options)?,
+                Operation::Avif {
+                    options,
+                } => self.avif(options)?,
+                Operation::Heif {
+                    options,
+                } => self.heif(options)?,
+                Operation::Jxl {
+                    options,
+                } => self.jxl(options)?,
+                Operation::Raw {
+                    options,
+                } => self.raw(options)?,
+                Operation::Tile {
+                    options,
+                } => self.tile(options)?,
+            };
+        }
+
+        Ok(self)
+    }
+
+    /**
+     * Export the operations applied so far as a recipe that can be stored and replayed with {@link apply_recipe}.
+     *
+     * @example
+     * let pipeline = Sharp::new_from_file("input.jpg")?.resize(300, 200)?.webp(None)?;
+     * let json = serde_json::to_string(&pipeline.to_recipe())?;
+     */
+    pub fn to_recipe(&self) -> Recipe {
+        Recipe {
+            operations: self.options.recipe.clone(),
+        }
+    }
+
+    /*
+     * Record a builder call for to_recipe, once its arguments have been validated.
+     */
+    pub(crate) fn record(&mut self, operation: Operation) {
+        self.options.recipe.push(operation);
+    }
+}
+
+/*
+  libvips enums are serialized by their nickname, e.g. "lanczos3" for Kernel::Lanczos3
+*/
+pub(crate) trait VipsEnum: Sized + Copy {
+    fn g_type() -> GType;
+    fn to_int(self) -> i32;
+    fn from_int(value: i32) -> Option<Self>;
+}
+
+macro_rules! vips_enum {
+    ($($enum_type:ty => $get_type:ident),* $(,)?) => {
+        $(
+            impl VipsEnum for $enum_type {
+                fn g_type() -> GType {
+                    unsafe { $get_type() }
+                }
+
+                fn to_int(self) -> i32 {
+                    self as i32
+                }
+
+                fn from_int(value: i32) -> Option<Self> {
+                    <$enum_type as FromPrimitive>::from_i32(value)
+                }
+            }
+        )*
+    };
+}
+
+vips_enum!(
+    BandFormat => vips_band_format_get_type,
+    Extend => vips_extend_get_type,
+    ForeignDzContainer => vips_foreign_dz_container_get_type,
+    ForeignDzDepth => vips_foreign_dz_depth_get_type,
+    ForeignDzLayout => vips_foreign_dz_layout_get_type,
+    ForeignHeifCompression => vips_foreign_heif_compression_get_type,
+    ForeignTiffCompression => vips_foreign_tiff_compression_get_type,
+    ForeignTiffPredictor => vips_foreign_tiff_predictor_get_type,
+    ForeignTiffResunit => vips_foreign_tiff_resunit_get_type,
+    ForeignWebpPreset => vips_foreign_webp_preset_get_type,
+    Interpretation => vips_interpretation_get_type,
+    Kernel => vips_kernel_get_type,
+    OperationBoolean => vips_operation_boolean_get_type,
+    Precision => vips_precision_get_type,
+);
+
+pub(crate) mod vips_enum {
+    use super::VipsEnum;
+    use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<T: VipsEnum, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let nick = super::nick(*value).ok_or_else(|| S::Error::custom("unknown enum value"))?;
+        serializer.serialize_str(&nick)
+    }
+
+    pub(crate) fn deserialize<'de, T: VipsEnum, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let nick = String::deserialize(deserializer)?;
+        super::from_nick(&nick).ok_or_else(|| D::Error::custom(format!("unknown value {:?}", nick)))
+    }
+
+    pub(crate) mod option {
+        use super::super::VipsEnum;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub(crate) fn serialize<T: VipsEnum, S: Serializer>(
+            value: &Option<T>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub(crate) fn deserialize<'de, T: VipsEnum, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<T>, D::Error> {
+            let nick: Option<String> = Option::deserialize(deserializer)?;
+            match nick {
+                Some(nick) => super::super::from_nick(&nick).map(Some).ok_or_else(|| {
+                    <D::Error as serde::de::Error>::custom(format!("unknown value {:?}", nick))
+                }),
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+fn nick<T: VipsEnum>(value: T) -> Option<String> {
+    let nick = unsafe { vips_enum_nick(T::g_type(), value.to_int()) };
+    if nick.is_null() {
+        return None;
+    }
+
+    Some(unsafe { CStr::from_ptr(nick) }.to_string_lossy().to_string())
+}
+
+fn from_nick<T: VipsEnum>(nick: &str) -> Option<T> {
+    let domain = CString::new("recipe").ok()?;
+    let nick = CString::new(nick).ok()?;
+    let value = unsafe { vips_enum_from_nick(domain.as_ptr(), T::g_type(), nick.as_ptr()) };
+    if value < 0 {
+        return None;
+    }
+
+    T::from_int(value)
+}
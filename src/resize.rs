@@ -1,8 +1,12 @@
-use crate::{common::Canvas, in_range, Colour, InvalidParameterError, Sharp};
+use crate::{
+    common::Canvas, in_range, recipe::Operation, Colour, InvalidParameterError, Sharp, SharpError,
+};
 use num_derive::{FromPrimitive, ToPrimitive};
 use rs_vips::ops::{Extend, Kernel};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Fit {
     Contain,
     Cover,
@@ -11,7 +15,8 @@ pub enum Fit {
     Outside,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ResizeOptions {
     /** Alternative means of specifying width. If both are present self takes priority. */
     pub width: Option<i32>,
@@ -24,6 +29,7 @@ pub struct ResizeOptions {
     /** Background colour when using a fit of contain, parsed by the color module, defaults to black without transparency. (optional, default {r:0,g:0,b:0,alpha:1}) */
     pub background: Option<Colour>,
     /** The kernel to use for image reduction. (optional, default "lanczos3") */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub kernel: Option<Kernel>,
     /** Do not enlarge if the width or height are already less than the specified dimensions, equivalent to GraphicsMagick"s > geometry option. (optional, default false) */
     pub without_enlargement: Option<bool>,
@@ -33,7 +39,8 @@ pub struct ResizeOptions {
     pub fast_shrink_on_load: Option<bool>,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ExtendOptions {
     /** single pixel count to top edge (optional, default 0) */
     pub top: Option<i32>,
@@ -46,10 +53,12 @@ pub struct ExtendOptions {
     /** background colour, parsed by the color module, defaults to black without transparency. (optional, default {r:0,g:0,b:0,alpha:1}) */
     pub background: Option<Colour>,
     /** how the extension is done, one of: "background", "copy", "repeat", "mirror" (optional, default `'background'`) */
+    #[serde(with = "crate::recipe::vips_enum::option")]
     pub extend_with: Option<Extend>,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Region {
     /** zero-indexed offset from left edge */
     pub left: u32,
@@ -61,7 +70,8 @@ pub struct Region {
     pub height: u32,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TrimOptions {
     /** Background colour, parsed by the color module, defaults to that of the top-left pixel. (optional) */
     pub background: Option<Colour>,
@@ -84,7 +94,8 @@ pub enum Gravity {
     Northwest = 8,
 }
 
-#[derive(Debug, Clone, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Clone, FromPrimitive, ToPrimitive, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Position {
     Centre = 0,
     Top = 1,
@@ -222,7 +233,7 @@ impl Sharp {
      *   );
      *
      */
-    pub fn resize(self, width: i32, height: i32) -> Result<Self, String> {
+    pub fn resize(self, width: i32, height: i32) -> Result<Self, SharpError> {
         let options = ResizeOptions {
             width: Some(width),
             height: Some(height),
@@ -231,7 +242,7 @@ impl Sharp {
         self.resize_(options)
     }
 
-    pub fn resize_with_opts(self, options: ResizeOptions) -> Result<Self, String> {
+    pub fn resize_with_opts(self, options: ResizeOptions) -> Result<Self, SharpError> {
         self.resize_(options)
     }
 
@@ -243,7 +254,10 @@ impl Sharp {
         (self.options.angle % 360) != 0 || self.options.rotation_angle != 0.0
     }
 
-    fn resize_(mut self, options: ResizeOptions) -> Result<Self, String> {
+    fn resize_(mut self, options: ResizeOptions) -> Result<Self, SharpError> {
+        let operation = Operation::Resize {
+            options: options.clone(),
+        };
         if self.is_resize_expected() {
             println!("ignoring previous resize options");
         }
@@ -321,7 +335,8 @@ impl Sharp {
             self.options.rotate_before = true;
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -362,7 +377,10 @@ impl Sharp {
      *   ...
      *
      */
-    pub fn extend(mut self, extend: ExtendOptions) -> Result<Self, String> {
+    pub fn extend(mut self, extend: ExtendOptions) -> Result<Self, SharpError> {
+        let operation = Operation::Extend {
+            options: extend.clone(),
+        };
         if let Some(top) = extend.top {
             if top >= 0 {
                 self.options.extend_top = top;
@@ -398,7 +416,8 @@ impl Sharp {
             self.options.extend_with = extend_with;
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -424,7 +443,10 @@ impl Sharp {
      *   });
      *
      */
-    pub fn extract(mut self, region: Region) -> Result<Self, String> {
+    pub fn extract(mut self, region: Region) -> Result<Self, SharpError> {
+        let operation = Operation::Extract {
+            region: region.clone(),
+        };
         let is_post = self.is_resize_expected() || self.options.width_pre != -1;
 
         if (is_post && self.options.width_post != -1) || (!is_post && self.options.width_pre != -1)
@@ -456,7 +478,8 @@ impl Sharp {
             self.options.orient_before = true;
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 
     /**
@@ -507,7 +530,10 @@ impl Sharp {
      * @returns {Sharp}
      * @throws {Error} Invalid parameters
      */
-    pub fn trim(mut self, options: Option<TrimOptions>) -> Result<Self, String> {
+    pub fn trim(mut self, options: Option<TrimOptions>) -> Result<Self, SharpError> {
+        let operation = Operation::Trim {
+            options: options.clone(),
+        };
         self.options.trim_threshold = 10.0;
         if let Some(options) = options {
             if let Some(background) = options.background {
@@ -528,6 +554,7 @@ impl Sharp {
             self.options.rotate_before = true;
         }
 
-        Ok(self)
+        self.record(operation);
+        Ok(self.step())
     }
 }
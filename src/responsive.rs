@@ -0,0 +1,206 @@
+use crate::{
+    output::{FormatEnum, FormatOptions, OutputInfo},
+    pipeline,
+    resize::ResizeOptions,
+    InvalidParameterError, Sharp, SharpError,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default)]
+pub struct ResponsiveOptions {
+    /** Skip widths larger than the processed image. (optional, default true) */
+    pub without_enlargement: Option<bool>,
+    /** Options for each output format, see {@link to_format}. */
+    pub format_options: Option<FormatOptions>,
+}
+
+/** One width and format of a responsive set. */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rendition {
+    pub width: i32,
+    pub height: i32,
+    /** The requested format, e.g. `avif` or `jpeg` */
+    pub format: String,
+    pub mime_type: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+    pub info: OutputInfo,
+}
+
+/**
+ * Renditions produced by {@link responsive_set}, ordered by format and then by width.
+ *
+ * Serializing the set, for example as JSON, includes everything except the image data.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponsiveSet {
+    pub renditions: Vec<Rendition>,
+}
+
+impl ResponsiveSet {
+    /**
+     * The `srcset` attribute listing every width of a format, using `url` to name each rendition.
+     *
+     * @example
+     * let srcset = set.srcset("webp", |r| format!("/img/hero-{}.webp", r.width));
+     * // "/img/hero-320.webp 320w, /img/hero-640.webp 640w"
+     */
+    pub fn srcset<F: Fn(&Rendition) -> String>(&self, format: &str, url: F) -> String {
+        self.renditions
+            .iter()
+            .filter(|rendition| rendition.format == format)
+            .map(|rendition| format!("{} {}w", url(rendition), rendition.width))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /**
+     * A `<picture>` element with one `<source>` per format and an `<img>` fallback using the last format.
+     *
+     * @example
+     * let html = set.picture(|r| format!("/img/hero-{}.{}", r.width, r.format), "100vw", "A hero image");
+     */
+    pub fn picture<F: Fn(&Rendition) -> String>(&self, url: F, sizes: &str, alt: &str) -> String {
+        let mut formats: Vec<&str> = Vec::new();
+        for rendition in self.renditions.iter() {
+            if !formats.contains(&rendition.format.as_str()) {
+                formats.push(&rendition.format);
+            }
+        }
+
+        let fallback = match formats.pop() {
+            Some(fallback) => fallback,
+            None => return String::new(),
+        };
+
+        let mut html = String::from("<picture>\n");
+        for format in formats {
+            let mime_type = self
+                .renditions
+                .iter()
+                .find(|rendition| rendition.format == format)
+                .map(|rendition| rendition.mime_type.as_str())
+                .unwrap_or_default();
+            html.push_str(&format!(
+                "  <source type=\"{}\" srcset=\"{}\" sizes=\"{}\">\n",
+                escape(mime_type),
+                escape(&self.srcset(format, &url)),
+                escape(sizes)
+            ));
+        }
+
+        if let Some(largest) =
+            self.renditions.iter().rev().find(|rendition| rendition.format == fallback)
+        {
+            html.push_str(&format!(
+                "  <img src=\"{}\" srcset=\"{}\" sizes=\"{}\" width=\"{}\" height=\"{}\" alt=\"{}\">\n",
+                escape(&url(largest)),
+                escape(&self.srcset(fallback, &url)),
+                escape(sizes),
+                largest.width,
+                largest.height,
+                escape(alt)
+            ));
+        }
+        html.push_str("</picture>");
+        html
+    }
+}
+
+impl Sharp {
+    /**
+     * Produce every combination of width and format from a single decode of the input,
+     * after the operations already configured on this pipeline.
+     *
+     * Widths larger than the processed image are skipped unless `without_enlargement` is false;
+     * if every width is skipped, the width of the processed image is used instead.
+     *
+     * @example
+     * let set = Sharp::new_from_file("hero.jpg")?.responsive_set(
+     *   &[320, 640, 960, 1280, 1920],
+     *   &[FormatEnum::Avif, FormatEnum::Webp, FormatEnum::Jpeg],
+     *   None,
+     * )?;
+     * for rendition in set.renditions.iter() {
+     *   std::fs::write(format!("hero-{}.{}", rendition.width, rendition.format), &rendition.data)?;
+     * }
+     */
+    pub fn responsive_set(
+        mut self,
+        widths: &[i32],
+        formats: &[FormatEnum],
+        options: Option<ResponsiveOptions>,
+    ) -> Result<ResponsiveSet, SharpError> {
+        if widths.is_empty() || widths.iter().any(|width| *width <= 0) {
+            return Err(InvalidParameterError!("widths", "positive integers", widths));
+        }
+        if formats.is_empty() {
+            return Err(InvalidParameterError!("formats", "at least one format", formats));
+        }
+
+        let options = options.unwrap_or_default();
+        let without_enlargement = options.without_enlargement.unwrap_or(true);
+        let decoded = pipeline::decode(self.options.clone())?;
+        let mut widths = widths.to_vec();
+        if without_enlargement {
+            let processed_width = decoded.width();
+            widths.retain(|width| *width <= processed_width);
+            if widths.is_empty() {
+                widths.push(processed_width);
+            }
+        }
+        widths.sort();
+        widths.dedup();
+
+        let mut branches = Vec::new();
+        let mut keys = Vec::new();
+        for format in formats {
+            for width in widths.iter() {
+                let mut branch = self
+                    .fork()
+                    .resize_with_opts(ResizeOptions {
+                        width: Some(*width),
+                        without_enlargement: Some(without_enlargement),
+                        ..Default::default()
+                    })?
+                    .to_format(*format, options.format_options.clone())?;
+                branch.options.file_out = String::new();
+                branches.push(branch.options);
+                keys.push(*format);
+            }
+        }
+
+        let renditions = branches
+            .into_iter()
+            .map(|branch| decoded.finish(branch))
+            .collect::<Result<Vec<_>, SharpError>>()?
+            .into_iter()
+            .zip(keys)
+            .map(|(mut baton, format)| {
+                let format = match format {
+                    FormatEnum::Jpg => FormatEnum::Jpeg,
+                    FormatEnum::Tif => FormatEnum::Tiff,
+                    format => format,
+                };
+                let data = std::mem::take(&mut baton.buffer_out);
+                let info = Sharp::create_output_info(baton);
+                Rendition {
+                    width: info.width,
+                    height: info.height,
+                    format: format.to_string(),
+                    mime_type: format.mime_type().to_string(),
+                    data,
+                    info,
+                }
+            })
+            .collect();
+
+        Ok(ResponsiveSet {
+            renditions,
+        })
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
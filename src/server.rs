@@ -0,0 +1,374 @@
+use crate::{
+    batch::default_concurrency,
+    input::SharpOptions,
+    output::{AvifOptions, JpegOptions, PngOptions, WebpOptions},
+    resize::{Fit, ResizeOptions},
+    Sharp, SharpError,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    net::ToSocketAddrs,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Default)]
+pub struct ServerOptions {
+    /** Key used to verify the signature segment of each URL. Requests are not signed when omitted. */
+    pub key: Option<Vec<u8>>,
+    /** Salt prepended to the path before signing. (optional, default empty) */
+    pub salt: Option<Vec<u8>>,
+    /** Do not process input images where the number of pixels (width x height) exceeds this limit. (optional, default 268402689) */
+    pub limit_input_pixels: Option<usize>,
+    /** Number of seconds after which processing of a request is stopped. (optional, default 0, no limit) */
+    pub timeout: Option<u32>,
+    /** Number of requests processed at the same time. (optional, default as for {@link batch::run}) */
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+/**
+ * Serves images from a local directory, transformed according to options encoded in the URL path.
+ *
+ * URLs follow the imgproxy layout, `/<signature>/<option>/<option>/.../<path>`, where each option is
+ * a colon separated name and arguments:
+ * - `rs:<fit>:<width>:<height>` or `resize:`, where fit is `fit`, `fill` or `force`
+ * - `w:<width>` or `width:`, `h:<height>` or `height:`
+ * - `q:<quality>` or `quality:`
+ * - `f:<format>` or `format:`, one of `jpeg`, `png`, `webp` or `avif`. The input format is kept when omitted.
+ *
+ * The signature is the URL-safe base64 HMAC-SHA256 of the salt and the rest of the path, starting with `/`.
+ * When no key is set, the signature segment may be omitted or given as `unsafe` or `_`.
+ *
+ * @example
+ * let server = ImageServer::new("/var/www/images", ServerOptions {
+ *   key: Some(b"secret".to_vec()),
+ *   timeout: Some(10),
+ *   ..Default::default()
+ * });
+ * let url = server.sign("/rs:fill:300:200/q:80/f:webp/photos/cat.jpg");
+ * server.serve("127.0.0.1:8080")?;
+ */
+#[derive(Debug, Clone)]
+pub struct ImageServer {
+    root: PathBuf,
+    options: ServerOptions,
+}
+
+#[derive(Debug, Default)]
+struct Request {
+    fit: Option<Fit>,
+    width: Option<i32>,
+    height: Option<i32>,
+    quality: Option<i32>,
+    format: Option<String>,
+    path: PathBuf,
+}
+
+impl ImageServer {
+    pub fn new<P: AsRef<Path>>(root: P, options: ServerOptions) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            options,
+        }
+    }
+
+    /**
+     * Prefix a path, such as `/rs:fit:300:200/photo.jpg`, with its signature.
+     */
+    pub fn sign(&self, path: &str) -> String {
+        match &self.options.key {
+            Some(key) => format!("/{}{}", self.signature(key, path), path),
+            None => format!("/unsafe{}", path),
+        }
+    }
+
+    fn signature(&self, key: &[u8], path: &str) -> String {
+        let mut mac = self.mac(key);
+        mac.update(path.as_bytes());
+        URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    fn mac(&self, key: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(self.options.salt.as_deref().unwrap_or_default());
+        mac
+    }
+
+    /**
+     * Handle the path and query of a GET request, without any network access.
+     */
+    pub fn handle(&self, url: &str) -> ServerResponse {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let path = match self.verify(path) {
+            Ok(path) => path,
+            Err(status) => return error(status, "Invalid signature"),
+        };
+
+        let request = match parse(path) {
+            Ok(request) => request,
+            Err(message) => return error(400, &message),
+        };
+
+        match self.process(request) {
+            Ok((body, format)) => ServerResponse {
+                status: 200,
+                content_type: content_type(&format).to_string(),
+                body,
+            },
+            Err(e) => {
+                let status = match e {
+                    SharpError::InvalidParameter {
+                        ..
+                    } => 400,
+                    SharpError::Io(_) => 404,
+                    SharpError::UnsupportedFormat(_) | SharpError::Decode(_) => 415,
+                    SharpError::LimitExceeded(_) => 413,
+                    SharpError::Timeout(_) => 504,
+                    _ => 500,
+                };
+                error(status, &e.message())
+            }
+        }
+    }
+
+    /*
+      Check and remove the signature segment
+    */
+    fn verify<'a>(&self, path: &'a str) -> Result<&'a str, u16> {
+        let trimmed = path.strip_prefix('/').ok_or(404_u16)?;
+        let (signature, rest) = match trimmed.find('/') {
+            Some(index) => (&trimmed[..index], &trimmed[index..]),
+            None => ("", path),
+        };
+
+        match &self.options.key {
+            Some(key) => {
+                let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| 403_u16)?;
+                let mut mac = self.mac(key);
+                mac.update(rest.as_bytes());
+                mac.verify_slice(&signature).map_err(|_| 403_u16)?;
+                Ok(rest)
+            }
+            None if signature == "unsafe" || signature == "_" => Ok(rest),
+            None => Ok(path),
+        }
+    }
+
+    fn process(&self, request: Request) -> Result<(Vec<u8>, String), SharpError> {
+        let file = self.root.join(&request.path);
+        if !file.is_file() {
+            return Err(SharpError::Io(format!("{} not found", request.path.display())));
+        }
+
+        let options = SharpOptions {
+            limit_input_pixels: self.options.limit_input_pixels,
+            ..Default::default()
+        };
+        let mut sharp = Sharp::new_from_file_with_opts(&file, options)?
+            .timeout(self.options.timeout.unwrap_or_default());
+
+        if request.width.is_some() || request.height.is_some() {
+            sharp = sharp.resize_with_opts(ResizeOptions {
+                width: request.width,
+                height: request.height,
+                fit: request.fit,
+                ..Default::default()
+            })?;
+        }
+
+        sharp = match request.format.as_deref() {
+            Some("jpeg") => sharp.jpeg(Some(JpegOptions {
+                quality: request.quality,
+                ..Default::default()
+            }))?,
+            Some("png") => sharp.png(Some(PngOptions {
+                quality: request.quality,
+                ..Default::default()
+            }))?,
+            Some("webp") => sharp.webp(Some(WebpOptions {
+                quality: request.quality,
+                ..Default::default()
+            }))?,
+            Some("avif") => sharp.avif(Some(AvifOptions {
+                quality: request.quality,
+                ..Default::default()
+            }))?,
+            Some(format) => {
+                return Err(SharpError::InvalidParameter {
+                    name: "format".to_string(),
+                    expected: "one of jpeg, png, webp or avif".to_string(),
+                    actual: format.to_string(),
+                })
+            }
+            None => sharp,
+        };
+
+        let (body, info) = sharp.to_buffer_with_info()?;
+        Ok((body, request.format.unwrap_or(info.format)))
+    }
+
+    /**
+     * Listen for HTTP requests on the address, blocking the current thread.
+     */
+    pub fn serve<A: ToSocketAddrs>(self, address: A) -> Result<(), SharpError> {
+        let server = tiny_http::Server::http(address).map_err(|e| SharpError::Io(e.to_string()))?;
+        let server = Arc::new(server);
+        let image_server = Arc::new(self);
+        let concurrency = image_server.options.concurrency.unwrap_or_else(default_concurrency);
+
+        let workers: Vec<_> = (0..concurrency.max(1))
+            .map(|index| {
+                let server = server.clone();
+                let image_server = image_server.clone();
+                thread::Builder::new()
+                    .name(format!("sharp-server-{}", index))
+                    .spawn(move || {
+                        for request in server.incoming_requests() {
+                            image_server.respond(request);
+                        }
+                    })
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+
+        for worker in workers {
+            let _ = worker.join();
+        }
+        Ok(())
+    }
+
+    fn respond(&self, request: tiny_http::Request) {
+        let response = match request.method() {
+            tiny_http::Method::Get | tiny_http::Method::Head => self.handle(request.url()),
+            _ => error(405, "Method not allowed"),
+        };
+        let header =
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], response.content_type.as_bytes())
+                .expect("Content-Type is a valid header");
+        let _ = request.respond(
+            tiny_http::Response::from_data(response.body)
+                .with_status_code(response.status)
+                .with_header(header),
+        );
+    }
+}
+
+/*
+  Split the options from the file path, which must stay inside the root directory
+*/
+fn parse(path: &str) -> Result<Request, String> {
+    let mut request = Request::default();
+    let mut segments = path.trim_start_matches('/').split('/').peekable();
+
+    while let Some(segment) = segments.peek() {
+        if !segment.contains(':') {
+            break;
+        }
+        let mut args = segment.split(':');
+        let name = args.next().unwrap_or_default();
+        let args: Vec<&str> = args.collect();
+        match name {
+            "rs" | "resize" => {
+                request.fit = Some(match args.first().copied().unwrap_or("fit") {
+                    "fit" | "" => Fit::Inside,
+                    "fill" => Fit::Cover,
+                    "force" => Fit::Fill,
+                    fit => return Err(format!("Invalid resizing type {}", fit)),
+                });
+                request.width = number(args.get(1))?;
+                request.height = number(args.get(2))?;
+            }
+            "w" | "width" => request.width = number(args.first())?,
+            "h" | "height" => request.height = number(args.first())?,
+            "q" | "quality" => request.quality = number(args.first())?,
+            "f" | "format" => {
+                request.format = args.first().map(|format| match *format {
+                    "jpg" => "jpeg".to_string(),
+                    format => format.to_string(),
+                })
+            }
+            _ => return Err(format!("Unknown option {}", name)),
+        }
+        segments.next();
+    }
+
+    let path: PathBuf =
+        segments.map(decode).collect::<Result<Vec<String>, String>>()?.iter().collect();
+    if path.as_os_str().is_empty()
+        || path.components().any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err("Invalid path".to_string());
+    }
+    request.path = path;
+    Ok(request)
+}
+
+/*
+  Parse a numeric argument, where 0 or an empty value means not set
+*/
+fn number(arg: Option<&&str>) -> Result<Option<i32>, String> {
+    match arg {
+        None | Some(&"") => Ok(None),
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(0) => Ok(None),
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(format!("Invalid number {}", arg)),
+        },
+    }
+}
+
+/*
+  Decode percent-encoded characters of a path segment
+*/
+fn decode(segment: &str) -> Result<String, String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = segment.get(index + 1..index + 3).ok_or("Invalid path")?;
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| "Invalid path")?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| "Invalid path".to_string())
+}
+
+fn content_type(format: &str) -> &'static str {
+    match format {
+        "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "heif" => "image/heif",
+        "gif" => "image/gif",
+        "tiff" => "image/tiff",
+        "jxl" => "image/jxl",
+        "jp2" => "image/jp2",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+fn error(status: u16, message: &str) -> ServerResponse {
+    ServerResponse {
+        status,
+        content_type: "text/plain; charset=utf-8".to_string(),
+        body: message.as_bytes().to_vec(),
+    }
+}
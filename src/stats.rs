@@ -1,17 +1,29 @@
 use crate::{
-    common::{remove_alpha, ImageType},
+    common::{remove_alpha, ImageType, InputDescriptor},
     input::open_input,
-    Sharp,
+    pipeline,
+    resize::Region,
+    Sharp, SharpError,
 };
 use rs_vips::{
     bindings::vips_interpretation_max_alpha,
-    operator::Index,
-    ops::{Access, Interpretation},
+    error::Error::OperationError,
+    operator::{Ge, Index},
+    ops::{Access, BandFormat, Interpretation},
     voption::{Setter, VOption},
     Result, VipsImage,
 };
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Default)]
+pub struct StatsOptions {
+    /** Only measure this area of the image (optional) */
+    pub region: Option<Region>,
+    /** Only measure pixels that are not fully transparent (optional, default false) */
+    pub alpha_mask: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChannelStats {
     pub min: i32,
     pub max: i32,
@@ -25,7 +37,7 @@ pub struct ChannelStats {
     pub max_y: i32,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Stats {
     pub channel_stats: Vec<ChannelStats>,
     pub is_opaque: bool,
@@ -48,80 +60,247 @@ const STAT_MAXX_INDEX: i32 = 8;
 const STAT_MAXY_INDEX: i32 = 9;
 
 impl Sharp {
-    pub fn stats(&self) -> Result<Stats> {
+    pub fn stats(&self) -> core::result::Result<Stats, SharpError> {
         let _guard = crate::util::VipsGuard;
 
         let mut input_option = self.options.input.clone();
         input_option.access = Access::Random;
 
-        let (image, image_type) = open_input(&input_option)?;
+        get_stats(&input_option)
+    }
+
+    /**
+     * Access to pixel-derived image statistics for every channel of the processed image,
+     * after all the operations of the pipeline, without encoding any output.
+     *
+     * The image is measured as it would be encoded, after conversion to the output colourspace,
+     * for example by {@link to_colourspace}, and to the output ICC profile of {@link with_icc_profile}.
+     *
+     * Statistics can be restricted to a `region` of the processed image and,
+     * with `alpha_mask`, to the pixels of that region that are not fully transparent.
+     *
+     * @example
+     * let stats = Sharp::new_from_file("input.png")?
+     *   .trim(None)?
+     *   .to_stats(Some(StatsOptions {
+     *     alpha_mask: Some(true),
+     *     ..Default::default()
+     *   }))?;
+     * let entropy = stats.entropy;
+     */
+    pub fn to_stats(
+        self,
+        options: Option<StatsOptions>,
+    ) -> core::result::Result<Stats, SharpError> {
+        let options = options.unwrap_or_default();
+        let decoded = pipeline::decode(self.options)?;
+        let image = decoded.output_image()?;
+
+        measure(&image, &options).map_err(SharpError::from)
+    }
+}
+
+fn get_stats(input: &InputDescriptor) -> core::result::Result<Stats, SharpError> {
+    let (image, image_type) = open_input(input)?;
+
+    let stats = Stats {
+        is_opaque: true,
+        ..Default::default()
+    };
+
+    if image_type == ImageType::UNKNOWN {
+        return Ok(stats);
+    }
+
+    Ok(image_stats(image, stats)?)
+}
+
+fn measure(image: &VipsImage, options: &StatsOptions) -> Result<Stats> {
+    let mut image = image.copy()?;
+
+    if let Some(region) = &options.region {
+        image = image.extract_area(
+            region.left as _,
+            region.top as _,
+            region.width as _,
+            region.height as _,
+        )?;
+    }
+
+    let stats = Stats {
+        is_opaque: true,
+        ..Default::default()
+    };
+
+    if options.alpha_mask.unwrap_or(false) && image.hasalpha() {
+        let mask = image.at(image.get_bands() - 1).ge(f64::MIN_POSITIVE);
+        return masked_stats(image, &mask, stats);
+    }
+
+    image_stats(image, stats)
+}
+
+fn image_stats(image: VipsImage, mut stats: Stats) -> Result<Stats> {
+    let stats_image = image.stats()?;
+    let bands = image.get_bands();
 
-        let mut stats = Stats {
-            is_opaque: true,
-            ..Default::default()
+    for b in 1..bands + 1 {
+        let cstats = ChannelStats {
+            min: *(stats_image.getpoint(STAT_MIN_INDEX, b)?.first().unwrap_or(&0.0)) as _,
+            max: *(stats_image.getpoint(STAT_MAX_INDEX, b)?.first().unwrap_or(&0.0)) as _,
+            sum: *(stats_image.getpoint(STAT_SUM_INDEX, b)?.first().unwrap_or(&0.0)),
+            squares_sum: *(stats_image.getpoint(STAT_SQ_SUM_INDEX, b)?.first().unwrap_or(&0.0))
+                as _,
+            mean: *(stats_image.getpoint(STAT_MEAN_INDEX, b)?.first().unwrap_or(&0.0)),
+            stdev: *(stats_image.getpoint(STAT_STDEV_INDEX, b)?.first().unwrap_or(&0.0)) as _,
+            min_x: *(stats_image.getpoint(STAT_MINX_INDEX, b)?.first().unwrap_or(&0.0)) as _,
+            min_y: *(stats_image.getpoint(STAT_MINY_INDEX, b)?.first().unwrap_or(&0.0)) as _,
+            max_x: *(stats_image.getpoint(STAT_MAXX_INDEX, b)?.first().unwrap_or(&0.0)) as _,
+            max_y: *(stats_image.getpoint(STAT_MAXY_INDEX, b)?.first().unwrap_or(&0.0)) as _,
         };
+        stats.channel_stats.push(cstats);
+    }
 
-        if image_type == ImageType::UNKNOWN {
-            return Ok(stats);
+    // Image is not opaque when alpha layer is present and contains a non-mamixa value
+    if image.hasalpha() {
+        let min_alpha = *(stats_image.getpoint(STAT_MIN_INDEX, bands)?.first().unwrap_or(&0.0));
+        if min_alpha != unsafe { vips_interpretation_max_alpha(image.get_interpretation()? as _) } {
+            stats.is_opaque = false;
         }
+    }
 
-        let stats_image = image.stats()?;
-        let bands = image.get_bands();
-
-        for b in 1..bands + 1 {
-            let cstats = ChannelStats {
-                min: *(stats_image.getpoint(STAT_MIN_INDEX, b)?.first().unwrap_or(&0.0)) as _,
-                max: *(stats_image.getpoint(STAT_MAX_INDEX, b)?.first().unwrap_or(&0.0)) as _,
-                sum: *(stats_image.getpoint(STAT_SUM_INDEX, b)?.first().unwrap_or(&0.0)),
-                squares_sum: *(stats_image.getpoint(STAT_SQ_SUM_INDEX, b)?.first().unwrap_or(&0.0))
-                    as _,
-                mean: *(stats_image.getpoint(STAT_MEAN_INDEX, b)?.first().unwrap_or(&0.0)),
-                stdev: *(stats_image.getpoint(STAT_STDEV_INDEX, b)?.first().unwrap_or(&0.0)) as _,
-                min_x: *(stats_image.getpoint(STAT_MINX_INDEX, b)?.first().unwrap_or(&0.0)) as _,
-                min_y: *(stats_image.getpoint(STAT_MINY_INDEX, b)?.first().unwrap_or(&0.0)) as _,
-                max_x: *(stats_image.getpoint(STAT_MAXX_INDEX, b)?.first().unwrap_or(&0.0)) as _,
-                max_y: *(stats_image.getpoint(STAT_MAXY_INDEX, b)?.first().unwrap_or(&0.0)) as _,
-            };
-            stats.channel_stats.push(cstats);
-        }
+    // Convert to greyscale
+    let greyscale = image.colourspace(Interpretation::BW)?.at(0);
+    // Estimate entropy via histogram of greyscale value frequency
+    stats.entropy = greyscale.hist_find()?.hist_entropy()?.abs();
 
-        // Image is not opaque when alpha layer is present and contains a non-mamixa value
-        if image.hasalpha() {
-            let min_alpha = *(stats_image.getpoint(STAT_MIN_INDEX, bands)?.first().unwrap_or(&0.0));
-            if min_alpha
-                != unsafe { vips_interpretation_max_alpha(image.get_interpretation()? as _) }
-            {
-                stats.is_opaque = false;
-            }
-        }
+    // Estimate sharpness via standard deviation of greyscale laplacian
+    if image.get_width() > 1 || image.get_height() > 1 {
+        let laplacian =
+            VipsImage::new_matrixv(3, 3, &[0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0])?;
+        laplacian.set_double("scale", 9.0)?;
+        stats.sharpness = greyscale.conv(&laplacian)?.deviate()?;
+    }
+
+    // Most dominant sRGB colour via 4096-bin 3D histogram
+    let hist = remove_alpha(image)?
+        .colourspace(Interpretation::Srgb)?
+        .hist_find_ndim_with_opts(VOption::new().set("bins", 16))?;
+    let maxpos = hist.maxpos()?;
+    let dx = maxpos.0;
+    let dy = maxpos.1;
+    let pel = hist.getpoint(dx as _, dy as _)?;
+    let max_val = pel.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let dz = pel.iter().position(|&x| x == max_val).unwrap() as f64;
+    stats.dominant_red = dx * 16.0 + 8.0;
+    stats.dominant_green = dy * 16.0 + 8.0;
+    stats.dominant_blue = dz * 16.0 + 8.0;
+
+    Ok(stats)
+}
+
+/*
+  Stats of the pixels where the mask is 255. Pixels outside of the mask are replaced
+  so that they can never be the minimum, the maximum or the dominant colour.
+*/
+fn masked_stats(image: VipsImage, mask: &VipsImage, mut stats: Stats) -> Result<Stats> {
+    let pixels = image.get_width() as f64 * image.get_height() as f64;
+    let weight = mask.linear(&[1.0 / 255.0], &[0.0])?;
+    let count = weight.avg()? * pixels;
+    if count < 1.0 {
+        return Err(OperationError("No pixels to measure within the alpha mask".to_string()));
+    }
+
+    for b in 0..image.get_bands() {
+        let band = image.at(b).cast(BandFormat::Double)?;
+        let (sum, squares_sum) = masked_sums(&band, &weight, pixels)?;
+        let lowest =
+            mask.ifthenelse(&band, &VipsImage::new_from_image(&band, &[f64::MAX])?)?.stats()?;
+        let highest =
+            mask.ifthenelse(&band, &VipsImage::new_from_image(&band, &[f64::MIN])?)?.stats()?;
+        stats.channel_stats.push(ChannelStats {
+            min: stat(&lowest, STAT_MIN_INDEX)? as _,
+            max: stat(&highest, STAT_MAX_INDEX)? as _,
+            sum,
+            squares_sum,
+            mean: sum / count,
+            stdev: deviation(sum, squares_sum, count),
+            min_x: stat(&lowest, STAT_MINX_INDEX)? as _,
+            min_y: stat(&lowest, STAT_MINY_INDEX)? as _,
+            max_x: stat(&highest, STAT_MAXX_INDEX)? as _,
+            max_y: stat(&highest, STAT_MAXY_INDEX)? as _,
+        });
+    }
 
-        // Convert to greyscale
-        let greyscale = image.colourspace(Interpretation::BW)?.at(0);
-        // Estimate entropy via histogram of greyscale value frequency
-        stats.entropy = greyscale.hist_find()?.hist_entropy()?.abs();
-
-        // Estimate sharpness via standard deviation of greyscale laplacian
-        if image.get_width() > 1 || image.get_height() > 1 {
-            let laplacian =
-                VipsImage::new_matrixv(3, 3, &[0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0])?;
-            laplacian.set_double("scale", 9.0)?;
-            stats.sharpness = greyscale.conv(&laplacian)?.deviate()?;
+    // The mask only selects pixels with some alpha, so compare the lowest of them with the maximum
+    if let Some(alpha) = stats.channel_stats.last() {
+        let max_alpha = unsafe { vips_interpretation_max_alpha(image.get_interpretation()? as _) };
+        if alpha.min as f64 != max_alpha {
+            stats.is_opaque = false;
         }
+    }
+
+    // Entropy of the greyscale histogram, counting only masked pixels
+    let greyscale = image.colourspace(Interpretation::BW)?.at(0);
+    stats.entropy = weight.hist_find_indexed(&greyscale)?.hist_entropy()?.abs();
+
+    // Sharpness from the deviation of the greyscale laplacian within the mask
+    if image.get_width() > 1 || image.get_height() > 1 {
+        let laplacian =
+            VipsImage::new_matrixv(3, 3, &[0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0])?;
+        laplacian.set_double("scale", 9.0)?;
+        let edges = greyscale.conv(&laplacian)?.cast(BandFormat::Double)?;
+        let (sum, squares_sum) = masked_sums(&edges, &weight, pixels)?;
+        stats.sharpness = deviation(sum, squares_sum, count);
+    }
+
+    // Most dominant sRGB colour, with the pixels outside of the mask moved into the first bin and taken out again
+    let srgb = remove_alpha(image)?.colourspace(Interpretation::Srgb)?;
+    let black = VipsImage::new_from_image(&srgb, &[0.0, 0.0, 0.0])?;
+    let hist =
+        mask.ifthenelse(&srgb, &black)?.hist_find_ndim_with_opts(VOption::new().set("bins", 16))?;
+    let mut counts: Vec<f64> = hist
+        .cast(BandFormat::Double)?
+        .write_to_memory()
+        .chunks_exact(8)
+        .map(|bytes| f64::from_ne_bytes(bytes.try_into().unwrap_or_default()))
+        .collect();
+    counts[0] -= pixels - count;
+    let dominant = counts
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1).then(b.0.cmp(&a.0)))
+        .map(|(index, _)| index)
+        .unwrap_or(0);
+    // Histogram pixels are indexed by red and green, with one band per blue bin
+    stats.dominant_red = (dominant / 16 % 16) as f64 * 16.0 + 8.0;
+    stats.dominant_green = (dominant / 256) as f64 * 16.0 + 8.0;
+    stats.dominant_blue = (dominant % 16) as f64 * 16.0 + 8.0;
+
+    Ok(stats)
+}
+
+fn stat(stats_image: &VipsImage, index: i32) -> Result<f64> {
+    Ok(*(stats_image.getpoint(index, 0)?.first().unwrap_or(&0.0)))
+}
+
+/*
+  Sum and sum of squares of a double band, weighted by a mask of 0 and 1
+*/
+fn masked_sums(band: &VipsImage, weight: &VipsImage, pixels: f64) -> Result<(f64, f64)> {
+    let weighted = band.multiply(weight)?;
+    let sum = weighted.avg()? * pixels;
+    let squares_sum = weighted.multiply(band)?.avg()? * pixels;
+    Ok((sum, squares_sum))
+}
 
-        // Most dominant sRGB colour via 4096-bin 3D histogram
-        let hist = remove_alpha(image)?
-            .colourspace(Interpretation::Srgb)?
-            .hist_find_ndim_with_opts(VOption::new().set("bins", 16))?;
-        let maxpos = hist.maxpos()?;
-        let dx = maxpos.0;
-        let dy = maxpos.1;
-        let pel = hist.getpoint(dx as _, dy as _)?;
-        let max_val = pel.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-        let dz = pel.iter().position(|&x| x == max_val).unwrap() as f64;
-        stats.dominant_red = dx * 16.0 + 8.0;
-        stats.dominant_green = dy * 16.0 + 8.0;
-        stats.dominant_blue = dz * 16.0 + 8.0;
-
-        Ok(stats)
+/*
+  Sample standard deviation, as calculated by libvips
+*/
+fn deviation(sum: f64, squares_sum: f64, count: f64) -> f64 {
+    if count > 1.0 {
+        ((squares_sum - sum * sum / count) / (count - 1.0)).max(0.0).sqrt()
+    } else {
+        0.0
     }
 }
@@ -0,0 +1,230 @@
+use rs_vips::{
+    bindings::{
+        g_signal_connect_data, g_signal_handler_disconnect, gulong, vips_source_custom_new,
+        vips_target_custom_new, vips_target_end, vips_target_write, GClosure, VipsSourceCustom,
+        VipsTargetCustom,
+    },
+    error::Error::OperationError,
+    Result, VipsSource, VipsTarget,
+};
+use std::{
+    ffi::{c_int, c_void},
+    fmt,
+    io::{ErrorKind, Read, Write},
+    marker::PhantomData,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/*
+  Shared handle to a caller-provided reader, consumed by the first image opened from it
+*/
+#[derive(Clone)]
+pub(crate) struct StreamReader {
+    reader: Arc<Mutex<Box<dyn Read + Send>>>,
+    opened: Arc<AtomicBool>,
+}
+
+impl StreamReader {
+    pub(crate) fn new<R: Read + Send + 'static>(reader: R) -> Self {
+        Self {
+            reader: Arc::new(Mutex::new(Box::new(reader))),
+            opened: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /*
+      Claim the reader for an image, false when an earlier image has already consumed it
+    */
+    pub(crate) fn open(&self) -> bool {
+        !self.opened.swap(true, Ordering::SeqCst)
+    }
+}
+
+impl fmt::Debug for StreamReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StreamReader")
+    }
+}
+
+/*
+  Borrowed writer, reached from libvips only through the signal handlers of a WriterTarget
+*/
+struct StreamWriter<'a> {
+    writer: &'a mut (dyn Write + Send),
+}
+
+/*
+  libvips target attached to a borrowed writer.
+  libvips may keep its own reference to the target after saving, so the handlers that hold the
+  writer are disconnected when this is dropped rather than when the target is finalized.
+*/
+pub(crate) struct WriterTarget<'a> {
+    target: VipsTarget,
+    handlers: [gulong; 2],
+    writer: PhantomData<&'a mut (dyn Write + Send)>,
+}
+
+impl Deref for WriterTarget<'_> {
+    type Target = VipsTarget;
+
+    fn deref(&self) -> &VipsTarget {
+        &self.target
+    }
+}
+
+impl Drop for WriterTarget<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the handlers were connected to this target by new_target and are only
+        // disconnected here, while our reference keeps the target alive. Disconnecting the
+        // "write" handler runs free_writer, so the writer is never reached once 'a ends.
+        unsafe {
+            for handler in self.handlers {
+                g_signal_handler_disconnect(self.target.as_mut_ptr() as *mut _, handler);
+            }
+        }
+    }
+}
+
+/*
+  Create a libvips source that pulls data from the reader
+*/
+pub(crate) fn new_source(reader: &StreamReader) -> Result<VipsSource> {
+    unsafe {
+        let source = vips_source_custom_new();
+        if source.is_null() {
+            return Err(OperationError("Unable to create input stream".to_string()));
+        }
+
+        let data = Box::into_raw(Box::new(reader.clone()));
+        g_signal_connect_data(
+            source as *mut _,
+            b"read\0".as_ptr() as *const _,
+            Some(std::mem::transmute::<*const (), unsafe extern "C" fn()>(
+                source_read_call_back as *const (),
+            )),
+            data as *mut _,
+            Some(free_reader),
+            0,
+        );
+
+        Ok(VipsSource::from(source as *mut rs_vips::bindings::VipsSource))
+    }
+}
+
+/*
+  Create a libvips target that pushes data to the writer
+*/
+pub(crate) fn new_target<'a>(writer: &'a mut (dyn Write + Send)) -> Result<WriterTarget<'a>> {
+    unsafe {
+        let target = vips_target_custom_new();
+        if target.is_null() {
+            return Err(OperationError("Unable to create output stream".to_string()));
+        }
+
+        // SAFETY: the data borrows the writer for 'a. It is only freed by disconnecting the
+        // "write" handler, which WriterTarget<'a> does on drop, and the "end" handler is
+        // disconnected first, so neither callback can run after the borrow ends.
+        let data = Box::into_raw(Box::new(StreamWriter {
+            writer,
+        }));
+        let end = g_signal_connect_data(
+            target as *mut _,
+            b"end\0".as_ptr() as *const _,
+            Some(std::mem::transmute::<*const (), unsafe extern "C" fn()>(
+                target_end_call_back as *const (),
+            )),
+            data as *mut _,
+            None,
+            0,
+        );
+        let write = g_signal_connect_data(
+            target as *mut _,
+            b"write\0".as_ptr() as *const _,
+            Some(std::mem::transmute::<*const (), unsafe extern "C" fn()>(
+                target_write_call_back as *const (),
+            )),
+            data as *mut _,
+            Some(free_writer),
+            0,
+        );
+
+        Ok(WriterTarget {
+            target: VipsTarget::from(target as *mut rs_vips::bindings::VipsTarget),
+            handlers: [end, write],
+            writer: PhantomData,
+        })
+    }
+}
+
+/*
+  Write a complete chunk of data, such as raw pixels, to the target and end it
+*/
+pub(crate) fn write_to_target(target: &VipsTarget, data: &[u8]) -> Result<()> {
+    unsafe {
+        if vips_target_write(target.as_mut_ptr(), data.as_ptr() as *const _, data.len() as _) != 0
+            || vips_target_end(target.as_mut_ptr()) != 0
+        {
+            return Err(OperationError("Unable to write to output stream".to_string()));
+        }
+    }
+    Ok(())
+}
+
+unsafe extern "C" fn source_read_call_back(
+    _source: *mut VipsSourceCustom,
+    buffer: *mut c_void,
+    length: i64,
+    data: *mut c_void,
+) -> i64 {
+    let reader = &*(data as *const StreamReader);
+    let buffer = std::slice::from_raw_parts_mut(buffer as *mut u8, length as usize);
+    let mut reader = match reader.reader.lock() {
+        Ok(reader) => reader,
+        Err(_) => return -1,
+    };
+
+    loop {
+        match reader.read(buffer) {
+            Ok(size) => return size as i64,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return -1,
+        }
+    }
+}
+
+unsafe extern "C" fn target_write_call_back(
+    _target: *mut VipsTargetCustom,
+    buffer: *const c_void,
+    length: i64,
+    data: *mut c_void,
+) -> i64 {
+    let stream = &mut *(data as *mut StreamWriter);
+    let buffer = std::slice::from_raw_parts(buffer as *const u8, length as usize);
+    match stream.writer.write_all(buffer) {
+        Ok(_) => length,
+        Err(_) => -1,
+    }
+}
+
+unsafe extern "C" fn target_end_call_back(
+    _target: *mut VipsTargetCustom,
+    data: *mut c_void,
+) -> c_int {
+    let stream = &mut *(data as *mut StreamWriter);
+    match stream.writer.flush() {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
+
+unsafe extern "C" fn free_reader(data: *mut c_void, _closure: *mut GClosure) {
+    drop(Box::from_raw(data as *mut StreamReader));
+}
+
+unsafe extern "C" fn free_writer(data: *mut c_void, _closure: *mut GClosure) {
+    drop(Box::from_raw(data as *mut StreamWriter));
+}
@@ -0,0 +1,161 @@
+use crate::SharpError;
+use std::{
+    future::Future,
+    panic::{catch_unwind, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Sender},
+        Arc, Mutex, OnceLock,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+/**
+ * Handle used to stop processing from another thread or task.
+ *
+ * Cancelling kills the image being written, so the pending output method returns `SharpError::Cancelled`.
+ * Dropping the future returned by an async output method cancels it automatically.
+ *
+ * @example
+ * let token = CancellationToken::new();
+ * let task = Sharp::new_from_file("input.jpg")?.with_cancellation(token.clone()).to_buffer_async();
+ * token.cancel();
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Request that processing stops as soon as possible. */
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /** Whether cancellation has been requested. */
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/*
+  Dedicated pool of blocking threads, started on first use
+*/
+fn pool() -> &'static Mutex<Sender<Job>> {
+    static POOL: OnceLock<Mutex<Sender<Job>>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let (sender, receiver) = channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let size = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+
+        for index in 0..size {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("sharp-worker-{}", index))
+                .spawn(move || loop {
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => return,
+                    }
+                })
+                .expect("Failed to spawn worker thread");
+        }
+
+        Mutex::new(sender)
+    })
+}
+
+struct Shared<T> {
+    result: Option<core::result::Result<T, SharpError>>,
+    waker: Option<Waker>,
+}
+
+/*
+  Future resolved when a job on the blocking pool completes, cancelling the job when dropped early
+*/
+pub(crate) struct BlockingTask<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    cancel: CancellationToken,
+    done: bool,
+}
+
+/*
+  Run a job on the blocking pool, returning a future that any executor can await
+*/
+pub(crate) fn spawn<T, F>(cancel: CancellationToken, job: F) -> BlockingTask<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> core::result::Result<T, SharpError> + Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        result: None,
+        waker: None,
+    }));
+
+    let job_shared = shared.clone();
+    let job: Job = Box::new(move || {
+        let result = catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|_| {
+            Err(SharpError::Operation("Processing thread panicked".to_string()))
+        });
+        if let Ok(mut shared) = job_shared.lock() {
+            shared.result = Some(result);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    });
+
+    let sent = match pool().lock() {
+        Ok(sender) => sender.send(job).is_ok(),
+        Err(_) => false,
+    };
+    if !sent {
+        shared.lock().unwrap().result =
+            Some(Err(SharpError::Operation("Unable to start processing thread".to_string())));
+    }
+
+    BlockingTask {
+        shared,
+        cancel,
+        done: false,
+    }
+}
+
+impl<T> Future for BlockingTask<T> {
+    type Output = core::result::Result<T, SharpError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut shared = this.shared.lock().unwrap();
+        match shared.result.take() {
+            Some(result) => {
+                this.done = true;
+                Poll::Ready(result)
+            }
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for BlockingTask<T> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.cancel.cancel();
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    input::{create_input_descriptor, Input, SharpInput, SharpOptions},
+    pipeline::{init_options, PipelineBaton},
+    Sharp, SharpError,
+};
+
+/**
+ * Operations and output options configured once, without an input, and applied to any number of inputs.
+ *
+ * The template is built with the usual builder methods, which validate their arguments as normal.
+ * Each call to {@link apply} binds an input to a copy of the configured pipeline.
+ *
+ * @example
+ * let template = SharpTemplate::new(|sharp| sharp.resize(300, 200)?.sharpen(None)?.webp(None))?;
+ * for file in files {
+ *   template.apply(Input::path(&file))?.to_file(file.with_extension("webp"))?;
+ * }
+ */
+#[derive(Debug, Clone)]
+pub struct SharpTemplate {
+    options: PipelineBaton,
+    input_options: Option<SharpOptions>,
+}
+
+impl SharpTemplate {
+    pub fn new<F>(build: F) -> Result<Self, SharpError>
+    where
+        F: FnOnce(Sharp) -> Result<Sharp, SharpError>,
+    {
+        Self::new_template(build, None)
+    }
+
+    pub fn new_with_opts<F>(options: SharpOptions, build: F) -> Result<Self, SharpError>
+    where
+        F: FnOnce(Sharp) -> Result<Sharp, SharpError>,
+    {
+        Self::new_template(build, Some(options))
+    }
+
+    fn new_template<F>(build: F, input_options: Option<SharpOptions>) -> Result<Self, SharpError>
+    where
+        F: FnOnce(Sharp) -> Result<Sharp, SharpError>,
+    {
+        Sharp::init()?;
+        let sharp = build(Sharp {
+            options: init_options(),
+        })?;
+
+        Ok(Self {
+            options: sharp.options,
+            input_options,
+        })
+    }
+
+    /**
+     * Create a pipeline that reads the input and then runs the operations of this template.
+     *
+     * Pipelines do not share the cancellation token of the template, see {@link with_cancellation}.
+     */
+    pub fn apply(&self, input: Input) -> Result<Sharp, SharpError> {
+        let mut baton = self.options.clone();
+        baton.cancel = None;
+        let mut descriptor = create_input_descriptor(
+            SharpInput::Single(input.inner),
+            self.input_options.clone(),
+            &mut baton,
+        )?;
+        // Input settings made by the builder methods of the template
+        descriptor.auto_orient |= self.options.input.auto_orient;
+        baton.input = descriptor;
+
+        Ok(Sharp {
+            options: baton,
+        })
+    }
+}
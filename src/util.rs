@@ -186,10 +186,13 @@ pub(crate) fn available_formats() -> HashMap<String, AvailableFormat> {
         let load_buffer = new_c_string(&format!("{f}load_buffer")).unwrap();
         let has_input_buffer =
             !unsafe { vips_class_find(basename.as_ptr(), load_buffer.as_ptr()) }.is_null();
+        let load_source = new_c_string(&format!("{f}load_source")).unwrap();
+        let has_input_stream =
+            !unsafe { vips_class_find(basename.as_ptr(), load_source.as_ptr()) }.is_null();
         let input = AvailableFormatInput {
             file: has_input_file,
             buffer: has_input_buffer,
-            stream: has_input_buffer,
+            stream: has_input_stream,
         };
         // Output
         let save = new_c_string(&format!("{f}save")).unwrap();
@@ -197,10 +200,13 @@ pub(crate) fn available_formats() -> HashMap<String, AvailableFormat> {
         let save_buffer = new_c_string(&format!("{f}save_buffer")).unwrap();
         let has_output_buffer =
             unsafe { vips_type_find(basename.as_ptr(), save_buffer.as_ptr()) } > 0;
+        let save_target = new_c_string(&format!("{f}save_target")).unwrap();
+        let has_output_stream =
+            unsafe { vips_type_find(basename.as_ptr(), save_target.as_ptr()) } > 0;
         let output = AvailableFormatOutput {
             file: has_output_file,
             buffer: has_output_buffer,
-            stream: has_output_buffer,
+            stream: has_output_stream,
         };
 
         formats.insert(
@@ -0,0 +1,62 @@
+mod fixtures;
+use sharp::{
+    batch::{self, BatchItem, BatchOptions, BatchTarget},
+    input::Input,
+    template::SharpTemplate,
+};
+
+#[test]
+pub fn batch() {
+    //One result per item, written to files and buffers
+    let template = SharpTemplate::new(|sharp| sharp.resize(320, 240)?.png(None)).unwrap();
+    let items = vec![
+        BatchItem::file(Input::path(fixtures::inputJpg()), fixtures::output("output.batch-0.png")),
+        BatchItem::buffer(Input::path(fixtures::inputPng())),
+        BatchItem::file(Input::path(fixtures::inputWebP()), fixtures::output("output.batch-2.png")),
+    ];
+
+    let mut results: Vec<_> = batch::run(
+        template,
+        items,
+        Some(BatchOptions {
+            concurrency: Some(2),
+        }),
+    )
+    .collect();
+    results.sort_by_key(|result| result.index);
+    assert_eq!(3, results.len());
+    for result in results.iter() {
+        let info = result.result.as_ref().unwrap();
+        assert_eq!("png", info.format);
+        assert_eq!(320, info.width);
+        assert_eq!(240, info.height);
+    }
+    assert!(matches!(results[0].target, BatchTarget::File(_)));
+    assert!(results[0].data.is_empty());
+    assert!(fixtures::output("output.batch-0.png").exists());
+    assert!(matches!(results[1].target, BatchTarget::Buffer));
+    assert!(!results[1].data.is_empty());
+}
+
+#[test]
+pub fn batch_failure() {
+    //A failing item does not stop the batch
+    let items = vec![
+        BatchItem::buffer(Input::path(fixtures::inputJpg())),
+        BatchItem::buffer(Input::path(fixtures::path("does-not-exist.jpg"))),
+        BatchItem::buffer(Input::buffer(Vec::new())),
+        BatchItem::buffer(Input::path(fixtures::inputPng())),
+    ];
+
+    let mut results: Vec<_> = batch::run_with(items, None, |input| {
+        let template = SharpTemplate::new(|sharp| sharp.resize(100, 100)?.jpeg(None))?;
+        template.apply(input)
+    })
+    .collect();
+    results.sort_by_key(|result| result.index);
+    assert_eq!(4, results.len());
+    assert!(results[0].result.is_ok());
+    assert!(results[1].result.is_err());
+    assert!(results[2].result.is_err());
+    assert!(results[3].result.is_ok());
+}
@@ -0,0 +1,68 @@
+use sharp::{operation::BlurOptions, CancellationToken, Sharp, SharpError};
+use std::{
+    future::Future,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+mod fixtures;
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn cancel() {
+    //Async output without an async runtime
+    let data = block_on(
+        Sharp::new_from_file(fixtures::inputJpg())
+            .unwrap()
+            .resize(320, 240)
+            .unwrap()
+            .to_buffer_async(),
+    )
+    .unwrap();
+    assert!(!data.is_empty());
+
+    //Already cancelled
+    let token = CancellationToken::new();
+    token.cancel();
+    match Sharp::new_from_file(fixtures::inputJpg()).unwrap().with_cancellation(token).to_buffer() {
+        Ok(_) => panic!("not cancelled"),
+        Err(e) => assert!(matches!(e, SharpError::Cancelled(_))),
+    }
+
+    //Cancelled while processing
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    match Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .blur(Some(BlurOptions {
+            sigma: 200.0,
+            ..Default::default()
+        }))
+        .unwrap()
+        .with_cancellation(token)
+        .on_progress(move |_| canceller.cancel())
+        .to_buffer()
+    {
+        Ok(_) => panic!("not cancelled"),
+        Err(e) => assert!(matches!(e, SharpError::Cancelled(_))),
+    }
+}
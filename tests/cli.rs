@@ -0,0 +1,67 @@
+#![cfg(feature = "cli")]
+mod fixtures;
+use sharp::Sharp;
+use std::process::Command;
+
+fn sharp() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_sharp"))
+}
+
+#[test]
+pub fn cli_resize() {
+    //Resize and convert a single file
+    let output = fixtures::output("output.cli-resize.webp");
+    let status = sharp()
+        .args(["resize", "--width", "320", "--height", "240", "--fit", "cover"])
+        .args(["--format", "webp", "--quality", "80"])
+        .arg(fixtures::inputJpg())
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let metadata = Sharp::new_from_file(&output).unwrap().metadata().unwrap();
+    assert_eq!("webp", metadata.format);
+    assert_eq!(320, metadata.width);
+    assert_eq!(240, metadata.height);
+}
+
+#[test]
+pub fn cli_batch() {
+    //Several inputs are written to a directory
+    let dir = fixtures::output("cli-batch");
+    let status = sharp()
+        .args(["extract", "--left", "0", "--top", "0", "--width", "10", "--height", "10"])
+        .args(["--format", "png"])
+        .arg(fixtures::inputJpg())
+        .arg(fixtures::inputWebP())
+        .arg("-o")
+        .arg(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(dir.join(fixtures::inputJpg().with_extension("png").file_name().unwrap()).exists());
+    assert!(dir.join(fixtures::inputWebP().with_extension("png").file_name().unwrap()).exists());
+
+    //Invalid arguments fail
+    let status = sharp()
+        .args(["resize", "--width=-1"])
+        .arg(fixtures::inputJpg())
+        .arg("-o")
+        .arg(fixtures::output("output.cli-invalid.png"))
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
+
+#[test]
+pub fn cli_metadata() {
+    //Metadata is printed as JSON
+    let output = sharp().arg("metadata").arg(fixtures::inputJpg()).output().unwrap();
+    assert!(output.status.success());
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!("jpeg", metadata["format"]);
+    assert_eq!(2725, metadata["width"]);
+    assert_eq!(2225, metadata["height"]);
+}
@@ -0,0 +1,75 @@
+mod fixtures;
+use sharp::{comparison::CompareOptions, input::Input, operation::BlurOptions, Sharp, SharpError};
+
+#[test]
+pub fn compare_identical() {
+    let comparison =
+        sharp::compare(Input::path(fixtures::inputJpg()), Input::path(fixtures::inputJpg()))
+            .unwrap();
+    assert!(comparison.is_identical());
+    assert_eq!(2725, comparison.width);
+    assert_eq!(2225, comparison.height);
+    assert_eq!(3, comparison.channels.len());
+    assert_eq!(0.0, comparison.mse);
+    assert!(comparison.psnr.is_infinite());
+    assert!(comparison.ssim > 0.9999);
+    for channel in comparison.channels.iter() {
+        assert_eq!(0.0, channel.max_error);
+    }
+}
+
+#[test]
+pub fn compare_different() {
+    let resized = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .png(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+    let blurred = Sharp::new_from_buffer(resized.clone())
+        .unwrap()
+        .blur(Some(BlurOptions {
+            sigma: 3.0,
+            ..Default::default()
+        }))
+        .unwrap()
+        .png(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+
+    //Errors and similarity
+    let comparison =
+        sharp::compare(Input::buffer(resized.clone()), Input::buffer(blurred.clone())).unwrap();
+    assert!(!comparison.is_identical());
+    assert!(comparison.different_pixels <= 320 * 240);
+    assert!(comparison.mse > 0.0);
+    assert!(comparison.psnr.is_finite());
+    assert!(comparison.ssim < 0.99);
+    assert!(comparison.channels.iter().all(|channel| channel.max_error > 0.0));
+
+    //Threshold ignores small differences
+    let tolerant = sharp::compare_with_opts(
+        Input::buffer(resized.clone()),
+        Input::buffer(blurred.clone()),
+        CompareOptions {
+            threshold: Some(255),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(0, tolerant.different_pixels);
+
+    //Diff image through the output methods
+    let (_, info) =
+        comparison.diff_image().unwrap().png(None).unwrap().to_buffer_with_info().unwrap();
+    assert_eq!(320, info.width);
+    assert_eq!(240, info.height);
+    assert_eq!(3, info.channels);
+
+    //Dimensions must match
+    let result = sharp::compare(Input::buffer(resized), Input::path(fixtures::inputJpg()));
+    assert!(matches!(result, Err(SharpError::Operation(_))));
+}
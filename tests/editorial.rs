@@ -0,0 +1,107 @@
+mod fixtures;
+use sharp::{editorial::EditorialFields, output::TiffOptions, Sharp};
+
+fn fields() -> EditorialFields {
+    EditorialFields {
+        creator: vec!["John Doe".to_string(), "Jane Doe".to_string()],
+        rights: Some("© 2024 John & Jane <Doe>".to_string()),
+        description: Some("A red square".to_string()),
+        keywords: vec!["red".to_string(), "square".to_string()],
+        headline: Some("Red".to_string()),
+        credit: Some("sharp".to_string()),
+    }
+}
+
+#[test]
+pub fn with_iptc_fields() {
+    //Create IPTC
+    let data =
+        fixtures::redSquare().with_iptc_fields(fields()).jpeg(None).unwrap().to_buffer().unwrap();
+    let metadata = Sharp::new_from_buffer(data.clone()).unwrap().metadata().unwrap();
+    assert!(metadata.iptc.starts_with(b"Photoshop 3.0\0"));
+    assert_eq!(fields(), metadata.iptc_fields);
+
+    //Merge and survive resize
+    let data = Sharp::new_from_buffer(data)
+        .unwrap()
+        .resize(4, 4)
+        .unwrap()
+        .with_iptc_fields(EditorialFields {
+            headline: Some("Resized".to_string()),
+            ..Default::default()
+        })
+        .to_buffer()
+        .unwrap();
+    let metadata = Sharp::new_from_buffer(data).unwrap().metadata().unwrap();
+    assert_eq!(4, metadata.width);
+    assert_eq!(Some("Resized".to_string()), metadata.iptc_fields.headline);
+    assert_eq!(fields().creator, metadata.iptc_fields.creator);
+    assert_eq!(fields().keywords, metadata.iptc_fields.keywords);
+
+    //Raw IIM for TIFF
+    let data = fixtures::redSquare()
+        .with_iptc_fields(fields())
+        .tiff(Some(TiffOptions::default()))
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+    let metadata = Sharp::new_from_buffer(data).unwrap().metadata().unwrap();
+    assert_eq!(0x1c, metadata.iptc[0]);
+    assert_eq!(fields(), metadata.iptc_fields);
+
+    //Truncate to the length of each dataset, on a character boundary
+    let data = fixtures::redSquare()
+        .with_iptc_fields(EditorialFields {
+            creator: vec![format!("a{}", "é".repeat(20))],
+            headline: Some("h".repeat(300)),
+            ..Default::default()
+        })
+        .jpeg(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+    let metadata = Sharp::new_from_buffer(data).unwrap().metadata().unwrap();
+    assert_eq!(vec![format!("a{}", "é".repeat(15))], metadata.iptc_fields.creator);
+    assert_eq!(Some("h".repeat(256)), metadata.iptc_fields.headline);
+}
+
+#[test]
+pub fn with_xmp_fields() {
+    //Create XMP
+    let data =
+        fixtures::redSquare().with_xmp_fields(fields()).png(None).unwrap().to_buffer().unwrap();
+    let metadata = Sharp::new_from_buffer(data).unwrap().metadata().unwrap();
+    assert_eq!(fields(), metadata.xmp_fields);
+
+    //Merge into an existing packet
+    let xmp = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:CreatorTool="Editor">
+      <dc:creator><rdf:Seq><rdf:li>Someone Else</rdf:li></rdf:Seq></dc:creator>
+      <dc:rights><rdf:Alt><rdf:li xml:lang="x-default">Public domain</rdf:li></rdf:Alt></dc:rights>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+    let data = fixtures::redSquare()
+        .with_xmp(xmp)
+        .with_xmp_fields(EditorialFields {
+            creator: vec!["John Doe".to_string()],
+            ..Default::default()
+        })
+        .jpeg(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+    let metadata = Sharp::new_from_buffer(data).unwrap().metadata().unwrap();
+    assert_eq!(vec!["John Doe".to_string()], metadata.xmp_fields.creator);
+    assert_eq!(Some("Public domain".to_string()), metadata.xmp_fields.rights);
+    let xmp = String::from_utf8_lossy(&metadata.xmp);
+    assert!(xmp.contains("xmp:CreatorTool=\"Editor\""));
+    assert!(!xmp.contains("Someone Else"));
+
+    //Read from a file
+    let metadata = Sharp::new_from_file(fixtures::inputJpg()).unwrap().metadata().unwrap();
+    assert!(metadata.xmp_fields.creator.is_empty());
+}
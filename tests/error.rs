@@ -0,0 +1,55 @@
+mod fixtures;
+use sharp::{operation::BlurOptions, resize::Region, Sharp, SharpError};
+
+#[test]
+pub fn error() {
+    //'invalid parameter'
+    match Sharp::new_from_file(fixtures::inputJpg()).unwrap().blur(Some(BlurOptions {
+        sigma: 1001.0,
+        ..Default::default()
+    })) {
+        Err(SharpError::InvalidParameter {
+            name,
+            actual,
+            ..
+        }) => {
+            assert_eq!("options.sigma", name);
+            assert_eq!("1001.0", actual);
+        }
+        _ => panic!("expected invalid parameter"),
+    }
+
+    //'missing input file'
+    match Sharp::new_from_file("does-not-exist.jpg").unwrap().to_buffer() {
+        Err(SharpError::Io(_)) => {}
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("expected error"),
+    }
+
+    //'same file for input and output'
+    match Sharp::new_from_file(fixtures::inputJpg()).unwrap().to_file(fixtures::inputJpg()) {
+        Err(SharpError::Io(_)) => {}
+        _ => panic!("expected io error"),
+    }
+
+    //'kind of a previous error is not carried over'
+    assert!(matches!(
+        Sharp::new_from_file("does-not-exist.jpg").unwrap().metadata(),
+        Err(SharpError::Io(_))
+    ));
+    match Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .extract(Region {
+            left: 0,
+            top: 0,
+            width: 10000,
+            height: 10,
+        })
+        .unwrap()
+        .to_buffer()
+    {
+        Err(SharpError::Operation(_)) => {}
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("expected error"),
+    }
+}
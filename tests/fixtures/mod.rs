@@ -1,7 +1,12 @@
 #![allow(non_snake_case)]
 #![allow(dead_code)]
 #![allow(unused_imports)]
-use sharp::{operation::FlattenOptions, resize::ResizeOptions, Colour, Sharp};
+use sharp::{
+    input::{Create, Inputs},
+    operation::FlattenOptions,
+    resize::ResizeOptions,
+    Colour, Sharp,
+};
 use std::path::{Path, PathBuf};
 use zouni::fs;
 
@@ -448,7 +453,8 @@ fn fingerprint(image: PathOrBuffer) -> Result<Vec<u8>, String> {
         .unwrap()
         .raw(None)
         .unwrap()
-        .to_buffer()?;
+        .to_buffer()
+        .map_err(|e| e.to_string())?;
 
     let mut fingerprint = Vec::new();
     for col in 0..8 {
@@ -503,6 +509,21 @@ pub fn output(name: &str) -> PathBuf {
     Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("output").join(name)
 }
 
+pub fn solid(width: i32, height: i32, channels: i32, background: Colour) -> Sharp {
+    Sharp::new(Inputs::new().create(Create {
+        width,
+        height,
+        channels,
+        background,
+        ..Default::default()
+    }))
+    .unwrap()
+}
+
+pub fn redSquare() -> Sharp {
+    solid(8, 8, 3, Colour::rgb(255, 0, 0))
+}
+
 pub fn clean_up() {
     fs::readdir(output(""), false, false)
         .unwrap()
@@ -0,0 +1,40 @@
+mod fixtures;
+use sharp::{resize::Region, Sharp};
+
+#[test]
+pub fn fork() {
+    //Each branch has its own format and dimensions
+    let mut pipeline = Sharp::new_from_file(fixtures::inputJpg()).unwrap();
+    let large = pipeline.fork().resize(320, 240).unwrap().jpeg(None).unwrap();
+    let small = pipeline.fork().resize(100, 100).unwrap().webp(None).unwrap();
+    let outputs = pipeline.to_many(vec![large, small]).unwrap();
+    assert_eq!(2, outputs.len());
+    let (data, info) = &outputs[0];
+    assert!(!data.is_empty());
+    assert_eq!("jpeg", info.format);
+    assert_eq!(320, info.width);
+    assert_eq!(240, info.height);
+    let (data, info) = &outputs[1];
+    assert!(!data.is_empty());
+    assert_eq!("webp", info.format);
+    assert_eq!(100, info.width);
+    assert_eq!(100, info.height);
+
+    //Shared operations run before each branch
+    let mut pipeline = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .extract(Region {
+            left: 0,
+            top: 0,
+            width: 400,
+            height: 300,
+        })
+        .unwrap();
+    let original = pipeline.fork().png(None).unwrap();
+    let halved = pipeline.fork().resize(200, 150).unwrap().png(None).unwrap();
+    let outputs = pipeline.to_many(vec![original, halved]).unwrap();
+    assert_eq!(400, outputs[0].1.width);
+    assert_eq!(300, outputs[0].1.height);
+    assert_eq!(200, outputs[1].1.width);
+    assert_eq!(150, outputs[1].1.height);
+}
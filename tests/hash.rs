@@ -0,0 +1,60 @@
+mod fixtures;
+use sharp::{
+    hash::{hamming_distance, HashKind},
+    Sharp,
+};
+
+#[test]
+pub fn perceptual_hash() {
+    let resized = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .jpeg(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+
+    for kind in [HashKind::Average, HashKind::Difference, HashKind::Perceptual] {
+        //Near-duplicates are close
+        let original =
+            Sharp::new_from_file(fixtures::inputJpg()).unwrap().perceptual_hash(kind).unwrap();
+        let duplicate =
+            Sharp::new_from_buffer(resized.clone()).unwrap().perceptual_hash(kind).unwrap();
+        assert_eq!(8, original.len());
+        assert!(hamming_distance(&original, &duplicate) <= 10, "{:?}", kind);
+
+        //Different images are far apart
+        let other =
+            Sharp::new_from_file(fixtures::inputPng()).unwrap().perceptual_hash(kind).unwrap();
+        assert!(hamming_distance(&original, &other) > 10, "{:?}", kind);
+    }
+
+    //256-bit hashes
+    let hash = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .perceptual_hash(HashKind::Perceptual256)
+        .unwrap();
+    assert_eq!(32, hash.len());
+}
+
+#[test]
+pub fn perceptual_hash_orientation() {
+    //Hash is computed after auto-orient
+    let expected = Sharp::new_from_file(fixtures::inputJpgWithLandscapeExif1())
+        .unwrap()
+        .perceptual_hash(HashKind::Difference)
+        .unwrap();
+    for input in [fixtures::inputJpgWithLandscapeExif6(), fixtures::inputJpgWithLandscapeExif8()] {
+        let actual =
+            Sharp::new_from_file(input).unwrap().perceptual_hash(HashKind::Difference).unwrap();
+        assert!(hamming_distance(&expected, &actual) <= 6);
+    }
+}
+
+#[test]
+pub fn hamming() {
+    assert_eq!(0, hamming_distance(&[0xff, 0x00], &[0xff, 0x00]));
+    assert_eq!(9, hamming_distance(&[0xff, 0x01], &[0x00, 0x00]));
+    assert_eq!(8, hamming_distance(&[0x00, 0x00], &[0x00]));
+}
@@ -0,0 +1,83 @@
+mod fixtures;
+use sharp::{histogram::HistogramOptions, Colour, Sharp};
+
+#[test]
+pub fn histogram() {
+    let histogram = Sharp::new_from_file(fixtures::inputJpg()).unwrap().histogram(None).unwrap();
+    assert_eq!(256, histogram.bins);
+    assert_eq!(3, histogram.channels.len());
+    assert!(histogram.luminance.is_none());
+    let (width, height) = (2725.0, 2225.0);
+    for channel in histogram.channels.iter() {
+        assert_eq!(256, channel.len());
+        assert_eq!(width * height, channel.iter().sum::<f64>());
+    }
+
+    //Bins and luminance
+    let histogram = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .histogram(Some(HistogramOptions {
+            bins: Some(16),
+            luminance: Some(true),
+            ..Default::default()
+        }))
+        .unwrap();
+    assert_eq!(16, histogram.channels[0].len());
+    let luminance = histogram.luminance.unwrap();
+    assert_eq!(16, luminance.len());
+    assert_eq!(width * height, luminance.iter().sum::<f64>());
+
+    //Processed output
+    let histogram = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .greyscale(true)
+        .histogram(Some(HistogramOptions {
+            processed: Some(true),
+            ..Default::default()
+        }))
+        .unwrap();
+    assert_eq!(1, histogram.channels.len());
+    assert_eq!(320.0 * 240.0, histogram.channels[0].iter().sum::<f64>());
+
+    //Processed output includes the operations applied before encoding
+    let histogram = fixtures::solid(10, 10, 3, Colour::new(0, 0, 0, 1.0))
+        .negate(true, None)
+        .unwrap()
+        .histogram(Some(HistogramOptions {
+            processed: Some(true),
+            ..Default::default()
+        }))
+        .unwrap();
+    for channel in histogram.channels.iter() {
+        assert_eq!(100.0, channel[255]);
+    }
+
+    //Invalid parameters
+    assert!(Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .histogram(Some(HistogramOptions {
+            bins: Some(0),
+            ..Default::default()
+        }))
+        .is_err());
+}
+
+#[test]
+pub fn histogram_alpha_weighted() {
+    //Transparent red
+    let image = fixtures::solid(10, 10, 4, Colour::new(255, 0, 0, 0.0));
+    let histogram = image.histogram(None).unwrap();
+    assert_eq!(3, histogram.channels.len());
+    assert_eq!(100.0, histogram.channels[0][255]);
+
+    //Transparent pixels are not counted
+    let histogram = image
+        .histogram(Some(HistogramOptions {
+            alpha_weighted: Some(true),
+            ..Default::default()
+        }))
+        .unwrap();
+    assert_eq!(0.0, histogram.channels[0].iter().sum::<f64>());
+}
@@ -0,0 +1,125 @@
+mod fixtures;
+use sharp::{
+    iiif::{IiifQuality, IiifRegion, IiifRequest, IiifScale},
+    Sharp, SharpError,
+};
+
+#[test]
+pub fn iiif_parse() {
+    //Request forms
+    let request =
+        IiifRequest::parse("/iiif/image-1/pct:10,20,50,50/^!400,300/!90/gray.webp").unwrap();
+    assert_eq!(
+        IiifRegion::Percent {
+            x: 10.0,
+            y: 20.0,
+            width: 50.0,
+            height: 50.0
+        },
+        request.region
+    );
+    assert!(request.size.upscale);
+    assert_eq!(IiifScale::BestFit(400, 300), request.size.scale);
+    assert!(request.rotation.mirror);
+    assert_eq!(90.0, request.rotation.degrees);
+    assert_eq!(IiifQuality::Gray, request.quality);
+    assert_eq!("image/webp", request.content_type());
+
+    assert_eq!(
+        IiifScale::Width(100),
+        IiifRequest::parse("full/100,/0/default.jpg").unwrap().size.scale
+    );
+    assert_eq!(
+        IiifScale::Height(100),
+        IiifRequest::parse("full/,100/0/default.jpg").unwrap().size.scale
+    );
+    assert_eq!(
+        IiifScale::Percent(50.0),
+        IiifRequest::parse("full/pct:50/0/default.jpg").unwrap().size.scale
+    );
+
+    //Invalid requests
+    for path in [
+        "full/max/0/default",
+        "full/max/0/sepia.jpg",
+        "full/max/361/default.jpg",
+        "full/max/22.5/default.jpg",
+        "full/max/45/default.jpg",
+        "full/max/!45/default.jpg",
+        "0,0,0,10/max/0/default.jpg",
+        "full/0,/0/default.jpg",
+        "full/max/0/default.bmp",
+        "max/0/default.jpg",
+    ] {
+        assert!(
+            matches!(IiifRequest::parse(path), Err(SharpError::InvalidParameter { .. })),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+pub fn iiif_request() {
+    //Region, size, rotation, quality and format
+    let request = IiifRequest::parse("0,0,400,200/200,/!90/gray.png").unwrap();
+    let (data, info) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .iiif(&request)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert!(!data.is_empty());
+    assert_eq!("png", info.format);
+    assert_eq!(100, info.width);
+    assert_eq!(200, info.height);
+    assert_eq!(1, info.channels);
+
+    //Square region with best fit size
+    let request = IiifRequest::parse("square/!300,200/0/default.jpg").unwrap();
+    let (_, info) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .iiif(&request)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert_eq!("jpeg", info.format);
+    assert_eq!(200, info.width);
+    assert_eq!(200, info.height);
+
+    //Upscaling requires ^
+    let request = IiifRequest::parse("0,0,100,100/200,200/0/default.jpg").unwrap();
+    assert!(Sharp::new_from_file(fixtures::inputJpg()).unwrap().iiif(&request).is_err());
+    let request = IiifRequest::parse("0,0,100,100/^200,200/0/default.jpg").unwrap();
+    let (_, info) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .iiif(&request)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert_eq!(200, info.width);
+
+    //Region outside the image
+    let request = IiifRequest::parse("5000,0,100,100/max/0/default.jpg").unwrap();
+    assert!(Sharp::new_from_file(fixtures::inputJpg()).unwrap().iiif(&request).is_err());
+}
+
+#[test]
+pub fn iiif_info() {
+    //info.json from metadata
+    let info = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .iiif_info("https://example.com/iiif/image-1/", Some(256))
+        .unwrap();
+    assert_eq!("https://example.com/iiif/image-1", info.id);
+    assert_eq!(2725, info.width);
+    assert_eq!(2225, info.height);
+    assert_eq!(vec![1, 2, 4, 8, 16], info.tiles[0].scale_factors);
+    assert!(!info.extra_features.contains(&"rotationArbitrary".to_string()));
+
+    let json = serde_json::to_value(&info).unwrap();
+    assert_eq!("http://iiif.io/api/image/3/context.json", json["@context"]);
+    assert_eq!("ImageService3", json["type"]);
+    assert_eq!(256, json["tiles"][0]["width"]);
+    assert!(json["tiles"][0]["scaleFactors"].is_array());
+}
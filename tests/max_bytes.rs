@@ -0,0 +1,89 @@
+mod fixtures;
+use sharp::{budget::MaxBytesOptions, output::FormatEnum, Sharp, SharpError};
+
+#[test]
+pub fn max_bytes_quality() {
+    //Highest quality within the limit
+    let (data, info, quality) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .to_buffer_max_bytes(8000, FormatEnum::Jpeg, None)
+        .unwrap();
+    assert!(data.len() <= 8000);
+    assert_eq!("jpeg", info.format);
+    assert_eq!(320, info.width);
+    assert!((1.0..100.0).contains(&quality));
+
+    //One level higher no longer fits
+    let larger = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .jpeg(Some(sharp::output::JpegOptions {
+            quality: Some(quality as i32 + 1),
+            ..Default::default()
+        }))
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+    assert!(larger.len() > 8000);
+
+    //Distance for JXL
+    if Sharp::available_formats().contains_key("jxl") {
+        let (data, _, distance) = Sharp::new_from_file(fixtures::inputJpg())
+            .unwrap()
+            .resize(320, 240)
+            .unwrap()
+            .to_buffer_max_bytes(8000, FormatEnum::Jxl, None)
+            .unwrap();
+        assert!(data.len() <= 8000);
+        assert!((0.1..=15.0).contains(&distance));
+    }
+}
+
+#[test]
+pub fn max_bytes_reduce() {
+    //Lowest quality is too large
+    let result = Sharp::new_from_file(fixtures::inputJpg()).unwrap().to_buffer_max_bytes(
+        2000,
+        FormatEnum::Webp,
+        Some(MaxBytesOptions {
+            min_quality: Some(50),
+            ..Default::default()
+        }),
+    );
+    assert!(matches!(result, Err(SharpError::LimitExceeded(_))));
+
+    //Width is reduced until it fits
+    let (data, info, quality) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .to_buffer_max_bytes(
+            2000,
+            FormatEnum::Webp,
+            Some(MaxBytesOptions {
+                min_quality: Some(50),
+                reduce: Some(true),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    assert!(data.len() <= 2000);
+    assert!(info.width < 2725);
+    assert!(quality >= 50.0);
+
+    //Invalid parameters
+    let sharp = || Sharp::new_from_file(fixtures::inputJpg()).unwrap();
+    assert!(sharp().to_buffer_max_bytes(0, FormatEnum::Jpeg, None).is_err());
+    assert!(sharp().to_buffer_max_bytes(1000, FormatEnum::Png, None).is_err());
+    assert!(sharp()
+        .to_buffer_max_bytes(
+            1000,
+            FormatEnum::Jpeg,
+            Some(MaxBytesOptions {
+                reduce_factor: Some(1.5),
+                ..Default::default()
+            })
+        )
+        .is_err());
+}
@@ -7,7 +7,8 @@ use sharp::{
     metadata::BackgroundColor,
     operation::BlurOptions,
     output::{
-        Exif, JpegOptions, PngOptions, WebpOptions, WithIccProfileOptions, WriteableMetadata,
+        Exif, JpegOptions, MetadataPolicy, PngOptions, WebpOptions, WithIccProfileOptions,
+        WriteableMetadata,
     },
     resize::ResizeOptions,
     Colour, Sharp,
@@ -1011,3 +1012,157 @@ pub enum ProfileClassSignature {
     ColorSpaceClass = 1_936_744_803,
     NamedColorClass = 1_852_662_636,
 }
+
+#[test]
+fn exif_data() {
+    let data = fixtures::redSquare()
+        .with_exif(Exif {
+            ifd0: Some(HashMap::from([
+                ("Make".to_string(), "Camera Maker".to_string()),
+                ("Model".to_string(), "Model (Mark II)".to_string()),
+            ])),
+            ifd2: Some(HashMap::from([
+                ("DateTimeOriginal".to_string(), "2024:05:01 12:30:00".to_string()),
+                ("ExposureTime".to_string(), "1/200".to_string()),
+                ("FNumber".to_string(), "28/10".to_string()),
+                ("ISOSpeedRatings".to_string(), "400".to_string()),
+            ])),
+            ifd3: Some(HashMap::from([
+                ("GPSLatitudeRef".to_string(), "N".to_string()),
+                ("GPSLatitude".to_string(), "51/1 30/1 3230/100".to_string()),
+                ("GPSLongitudeRef".to_string(), "W".to_string()),
+                ("GPSLongitude".to_string(), "0/1 7/1 4366/100".to_string()),
+            ])),
+            ..Default::default()
+        })
+        .jpeg(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+
+    let exif = Sharp::new_from_buffer(data).unwrap().metadata().unwrap().exif_data;
+    assert_eq!(Some("Camera Maker".to_string()), exif.make);
+    assert_eq!(Some("Model (Mark II)".to_string()), exif.model);
+    assert_eq!(Some("2024:05:01 12:30:00".to_string()), exif.date_time_original);
+    assert_eq!(Some(0.005), exif.exposure_time);
+    assert_eq!(Some(2.8), exif.f_number);
+    assert_eq!(Some(400), exif.iso);
+    assert!((exif.gps_latitude.unwrap() - 51.508972).abs() < 0.00001);
+    assert!((exif.gps_longitude.unwrap() + 0.128794).abs() < 0.00001);
+    assert!(exif.gps_altitude.is_none());
+    assert!(exif.ifd2.contains_key("ExposureTime"));
+
+    //Altitude below sea level
+    let data = fixtures::redSquare()
+        .with_exif(Exif {
+            ifd3: Some(HashMap::from([
+                ("GPSAltitudeRef".to_string(), "1".to_string()),
+                ("GPSAltitude".to_string(), "4250/10".to_string()),
+            ])),
+            ..Default::default()
+        })
+        .jpeg(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+    let exif = Sharp::new_from_buffer(data).unwrap().metadata().unwrap().exif_data;
+    assert_eq!(Some(-425.0), exif.gps_altitude);
+
+    //No EXIF
+    let metadata = Sharp::new_from_file(fixtures::inputPng()).unwrap().metadata().unwrap();
+    assert!(metadata.exif_data.ifd0.is_empty());
+    assert!(metadata.exif_data.make.is_none());
+}
+
+#[test]
+fn strip_metadata() {
+    let xmp = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/" xmlns:exif="http://ns.adobe.com/exif/1.0/" photoshop:City="London" photoshop:Credit="sharp">
+      <dc:creator><rdf:Seq><rdf:li>John Doe</rdf:li></rdf:Seq></dc:creator>
+      <exif:GPSLatitude>51,30.5383N</exif:GPSLatitude>
+      <exif:GPSLongitude>0,7.7277W</exif:GPSLongitude>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+    let data = fixtures::redSquare()
+        .with_exif(Exif {
+            ifd0: Some(HashMap::from([
+                ("Copyright".to_string(), "Test 1".to_string()),
+                ("Artist".to_string(), "John Doe".to_string()),
+                ("Model".to_string(), "Camera".to_string()),
+            ])),
+            ifd3: Some(HashMap::from([
+                ("GPSLatitudeRef".to_string(), "N".to_string()),
+                ("GPSLatitude".to_string(), "51/1 30/1 3230/100".to_string()),
+            ])),
+            ..Default::default()
+        })
+        .with_xmp(xmp)
+        .jpeg(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+
+    //Deny list
+    let stripped = Sharp::new_from_buffer(data.clone())
+        .unwrap()
+        .keep_metadata()
+        .strip_metadata(MetadataPolicy {
+            deny: vec![
+                "ifd3".to_string(),
+                "Artist".to_string(),
+                "exif:GPS*".to_string(),
+                "City".to_string(),
+            ],
+            ..Default::default()
+        })
+        .to_buffer()
+        .unwrap();
+    let metadata = Sharp::new_from_buffer(stripped).unwrap().metadata().unwrap();
+    assert_eq!(Some("Camera".to_string()), metadata.exif_data.model);
+    assert!(metadata.exif_data.ifd0.contains_key("Copyright"));
+    assert!(!metadata.exif_data.ifd0.contains_key("Artist"));
+    assert!(metadata.exif_data.ifd3.is_empty());
+    assert!(metadata.exif_data.gps_latitude.is_none());
+    let xmp = String::from_utf8_lossy(&metadata.xmp);
+    assert!(xmp.contains("John Doe"));
+    assert!(xmp.contains("photoshop:Credit=\"sharp\""));
+    assert!(xmp.contains("xmlns:photoshop"));
+    assert!(!xmp.contains("London"));
+    assert!(!xmp.contains("GPSLatitude"));
+    assert!(!xmp.contains("GPSLongitude"));
+
+    //Allow list
+    let stripped = Sharp::new_from_buffer(data.clone())
+        .unwrap()
+        .keep_metadata()
+        .strip_metadata(MetadataPolicy {
+            allow: vec!["Copyright".to_string(), "dc:creator".to_string()],
+            ..Default::default()
+        })
+        .to_buffer()
+        .unwrap();
+    let metadata = Sharp::new_from_buffer(stripped).unwrap().metadata().unwrap();
+    assert!(metadata.exif_data.ifd0.contains_key("Copyright"));
+    assert!(metadata.exif_data.model.is_none());
+    let xmp = String::from_utf8_lossy(&metadata.xmp);
+    assert!(xmp.contains("John Doe"));
+    assert!(!xmp.contains("Credit"));
+    assert!(!xmp.contains("GPSLatitude"));
+
+    //Policy alone does not keep metadata
+    let stripped = Sharp::new_from_buffer(data)
+        .unwrap()
+        .strip_metadata(MetadataPolicy {
+            allow: vec!["Copyright".to_string()],
+            ..Default::default()
+        })
+        .to_buffer()
+        .unwrap();
+    let metadata = Sharp::new_from_buffer(stripped).unwrap().metadata().unwrap();
+    assert!(metadata.exif_data.ifd0.is_empty());
+    assert!(metadata.xmp.is_empty());
+}
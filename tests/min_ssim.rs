@@ -0,0 +1,71 @@
+mod fixtures;
+use sharp::{budget::SsimOptions, input::SharpOptions, output::FormatEnum, Sharp, SharpError};
+
+#[test]
+pub fn min_ssim() {
+    //Lowest quality that reaches the target
+    let (data, info, quality) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .to_buffer_min_ssim(0.95, FormatEnum::Webp, None)
+        .unwrap();
+    assert!(!data.is_empty());
+    assert_eq!("webp", info.format);
+    assert_eq!(320, info.width);
+    let ssim = info.ssim.unwrap();
+    assert!((0.95..=1.0).contains(&ssim));
+    assert!((1.0..=100.0).contains(&quality));
+
+    //A higher target needs a higher quality
+    let (higher, info, higher_quality) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .to_buffer_min_ssim(0.99, FormatEnum::Webp, None)
+        .unwrap();
+    assert!(info.ssim.unwrap() >= 0.99);
+    assert!(higher_quality >= quality);
+    assert!(higher.len() >= data.len());
+
+    //Output of other methods has no score
+    let (_, info) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert!(info.ssim.is_none());
+}
+
+#[test]
+pub fn min_ssim_errors() {
+    let sharp = || Sharp::new_from_file(fixtures::inputJpg()).unwrap().resize(320, 240).unwrap();
+
+    //Unreachable target
+    let result = sharp().to_buffer_min_ssim(
+        1.0,
+        FormatEnum::Jpeg,
+        Some(SsimOptions {
+            min_quality: Some(10),
+            ..Default::default()
+        }),
+    );
+    assert!(matches!(result, Err(SharpError::Encode(_))));
+
+    //Multi-page input
+    let result = Sharp::new_from_file_with_opts(
+        fixtures::inputWebPAnimated(),
+        SharpOptions {
+            pages: Some(-1),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .to_buffer_min_ssim(0.98, FormatEnum::Webp, None);
+    assert!(matches!(result, Err(SharpError::InvalidParameter { .. })));
+
+    //Invalid parameters
+    assert!(sharp().to_buffer_min_ssim(1.5, FormatEnum::Jpeg, None).is_err());
+    assert!(sharp().to_buffer_min_ssim(0.98, FormatEnum::Jxl, None).is_err());
+}
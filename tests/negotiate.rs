@@ -0,0 +1,77 @@
+mod fixtures;
+use sharp::{negotiate::NegotiateOptions, output::FormatEnum, Sharp};
+
+fn prefs(formats: &[FormatEnum]) -> Option<NegotiateOptions> {
+    Some(NegotiateOptions {
+        formats: formats.to_vec(),
+        ..Default::default()
+    })
+}
+
+#[test]
+pub fn negotiate_format() {
+    //Highest q-value wins
+    let (sharp, mime_type) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(32, 32)
+        .unwrap()
+        .negotiate_format(
+            "image/webp;q=0.9,image/jpeg",
+            prefs(&[FormatEnum::Webp, FormatEnum::Jpeg]),
+        )
+        .unwrap();
+    assert_eq!("image/jpeg", mime_type);
+    let (_, info) = sharp.to_buffer_with_info().unwrap();
+    assert_eq!("jpeg", info.format);
+
+    //Server preference breaks ties, wildcards match
+    let (sharp, mime_type) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(32, 32)
+        .unwrap()
+        .negotiate_format(
+            "text/html,image/*;q=0.8,*/*;q=0.5",
+            prefs(&[FormatEnum::Webp, FormatEnum::Jpeg]),
+        )
+        .unwrap();
+    assert_eq!("image/webp", mime_type);
+    let (_, info) = sharp.to_buffer_with_info().unwrap();
+    assert_eq!("webp", info.format);
+
+    //Explicitly refused format
+    let (_, mime_type) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .negotiate_format("image/webp;q=0,*/*", prefs(&[FormatEnum::Webp, FormatEnum::Jpeg]))
+        .unwrap();
+    assert_eq!("image/jpeg", mime_type);
+
+    //Nothing acceptable
+    let (_, mime_type) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .negotiate_format("text/html", None)
+        .unwrap();
+    assert_eq!("image/jpeg", mime_type);
+}
+
+#[test]
+pub fn negotiate_format_input_traits() {
+    //Alpha channel is kept
+    let (_, mime_type) = Sharp::new_from_file(fixtures::inputPngWithTransparency())
+        .unwrap()
+        .negotiate_format("image/jpeg,image/png;q=0.5", prefs(&[FormatEnum::Jpeg, FormatEnum::Png]))
+        .unwrap();
+    assert_eq!("image/png", mime_type);
+
+    let (_, mime_type) = Sharp::new_from_file(fixtures::inputPngWithTransparency())
+        .unwrap()
+        .negotiate_format("text/html", prefs(&[FormatEnum::Jpeg]))
+        .unwrap();
+    assert_eq!("image/png", mime_type);
+
+    //Animation is kept
+    let (_, mime_type) = Sharp::new_from_file(fixtures::inputGifAnimated())
+        .unwrap()
+        .negotiate_format("*/*", prefs(&[FormatEnum::Jpeg, FormatEnum::Png, FormatEnum::Gif]))
+        .unwrap();
+    assert_eq!("image/gif", mime_type);
+}
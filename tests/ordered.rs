@@ -0,0 +1,77 @@
+mod fixtures;
+use sharp::{
+    operation::BlurOptions,
+    resize::{ExtendOptions, Region},
+    Sharp,
+};
+
+#[test]
+pub fn ordered() {
+    //Repeated rotation is cumulative
+    let (_, info) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .ordered(true)
+        .resize(320, 240)
+        .unwrap()
+        .rotate(90, None)
+        .unwrap()
+        .rotate(90, None)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert_eq!(320, info.width);
+    assert_eq!(240, info.height);
+
+    //Operations run in call order
+    let (_, info) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .ordered(true)
+        .resize(320, 240)
+        .unwrap()
+        .rotate(90, None)
+        .unwrap()
+        .extract(Region {
+            left: 0,
+            top: 0,
+            width: 200,
+            height: 300,
+        })
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert_eq!(200, info.width);
+    assert_eq!(300, info.height);
+
+    //Repeated extend and blur
+    let (_, info) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .ordered(true)
+        .resize(320, 240)
+        .unwrap()
+        .extend(ExtendOptions {
+            top: Some(10),
+            bottom: Some(10),
+            ..Default::default()
+        })
+        .unwrap()
+        .blur(Some(BlurOptions {
+            sigma: 1.0,
+            ..Default::default()
+        }))
+        .unwrap()
+        .extend(ExtendOptions {
+            left: Some(10),
+            right: Some(10),
+            ..Default::default()
+        })
+        .unwrap()
+        .blur(Some(BlurOptions {
+            sigma: 1.0,
+            ..Default::default()
+        }))
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert_eq!(340, info.width);
+    assert_eq!(260, info.height);
+}
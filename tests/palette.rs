@@ -0,0 +1,62 @@
+mod fixtures;
+use sharp::{palette::PaletteOptions, Colour, Sharp};
+
+#[test]
+pub fn palette() {
+    let palette = Sharp::new_from_file(fixtures::inputJpg()).unwrap().palette(5, None).unwrap();
+    assert_eq!(5, palette.len());
+    let coverage: f64 = palette.iter().map(|colour| colour.coverage).sum();
+    assert!((coverage - 1.0).abs() < 0.001);
+    assert!(palette.windows(2).all(|pair| pair[0].coverage >= pair[1].coverage));
+    assert_eq!(7, palette[0].hex().len());
+
+    //Centre weighting
+    let weighted = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .palette(
+            5,
+            Some(PaletteOptions {
+                centre_weight: Some(1.0),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    assert!(!weighted.is_empty());
+
+    //Invalid parameters
+    assert!(Sharp::new_from_file(fixtures::inputJpg()).unwrap().palette(0, None).is_err());
+    assert!(Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .palette(
+            5,
+            Some(PaletteOptions {
+                centre_weight: Some(2.0),
+                ..Default::default()
+            })
+        )
+        .is_err());
+}
+
+#[test]
+pub fn palette_solid() {
+    //Single colour
+    let solid = fixtures::solid(10, 10, 3, Colour::rgb(255, 0, 0));
+    let palette = solid.palette(4, None).unwrap();
+    assert_eq!(1, palette.len());
+    assert_eq!("#ff0000", palette[0].hex());
+    assert_eq!(1.0, palette[0].coverage);
+
+    //Transparent pixels are ignored
+    let transparent = fixtures::solid(10, 10, 4, Colour::new(0, 0, 255, 0.0));
+    assert!(transparent.palette(4, None).unwrap().is_empty());
+    let palette = transparent
+        .palette(
+            4,
+            Some(PaletteOptions {
+                ignore_transparent: Some(false),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    assert_eq!(1, palette.len());
+}
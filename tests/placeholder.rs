@@ -0,0 +1,103 @@
+mod fixtures;
+use sharp::{
+    input::{CreateRaw, SharpOptions},
+    Sharp, SharpError,
+};
+
+fn gradient() -> Sharp {
+    let mut pixels = Vec::new();
+    for y in 0..4 {
+        for x in 0..8 {
+            pixels.extend([x * 32, y * 64, 128]);
+        }
+    }
+    Sharp::new_from_buffer_with_opts(
+        pixels,
+        SharpOptions {
+            raw: Some(CreateRaw {
+                width: 8,
+                height: 4,
+                channels: 3,
+                premultiplied: false,
+                page_height: None,
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+#[test]
+pub fn blurhash() {
+    //Encode
+    let hash = Sharp::new_from_file(fixtures::inputJpg()).unwrap().blurhash(4, 3).unwrap();
+    assert_eq!(28, hash.len());
+    let hash_1x1 = Sharp::new_from_file(fixtures::inputJpg()).unwrap().blurhash(1, 1).unwrap();
+    assert_eq!(6, hash_1x1.len());
+    assert!(Sharp::new_from_file(fixtures::inputJpg()).unwrap().blurhash(0, 3).is_err());
+    assert!(Sharp::new_from_file(fixtures::inputJpg()).unwrap().blurhash(4, 10).is_err());
+
+    //Encode matches the reference implementation, without enlarging small images
+    assert_eq!("LjF=XW3Ba|xu*?NefQnmd_e;fQe;", gradient().blurhash(4, 3).unwrap());
+    assert_eq!("00F=XW", gradient().blurhash(1, 1).unwrap());
+
+    //Decode through the output methods
+    let (_, info) = Sharp::new_from_blurhash(&hash, 32, 24)
+        .unwrap()
+        .png(None)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert_eq!(32, info.width);
+    assert_eq!(24, info.height);
+    assert_eq!(3, info.channels);
+
+    //Reference hash
+    let (data, _) = Sharp::new_from_blurhash("LEHV6nWB2yk8pyo0adR*.7kCMdnj", 8, 8)
+        .unwrap()
+        .raw(None)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert_eq!(8 * 8 * 3, data.len());
+    let pixel = |x: usize, y: usize| &data[(y * 8 + x) * 3..(y * 8 + x) * 3 + 3];
+    assert_eq!([135, 164, 177], pixel(0, 0));
+    assert_eq!([144, 167, 179], pixel(7, 0));
+    assert_eq!([134, 144, 148], pixel(0, 7));
+    assert_eq!([140, 144, 145], pixel(7, 7));
+    assert_eq!([154, 126, 118], pixel(3, 4));
+
+    //Size is limited
+    assert!(Sharp::new_from_blurhash(&hash, 257, 32).is_err());
+    assert!(Sharp::new_from_blurhash(&hash, 32, 0).is_err());
+
+    //Invalid hash
+    assert!(matches!(
+        Sharp::new_from_blurhash("LEHV6nWB2yk8", 32, 32),
+        Err(SharpError::InvalidParameter { .. })
+    ));
+}
+
+#[test]
+pub fn thumbhash() {
+    //Encode without alpha
+    let hash = Sharp::new_from_file(fixtures::inputJpg()).unwrap().thumbhash().unwrap();
+    assert!(hash.len() > 5);
+    assert_eq!(0, hash[2] & 0x80);
+
+    //Decode keeps the aspect ratio
+    let (_, info) =
+        Sharp::new_from_thumbhash(&hash).unwrap().png(None).unwrap().to_buffer_with_info().unwrap();
+    assert_eq!(32, info.width);
+    assert!((24..=28).contains(&info.height));
+    assert_eq!(4, info.channels);
+
+    //Alpha channel
+    let hash =
+        Sharp::new_from_file(fixtures::inputPngWithTransparency()).unwrap().thumbhash().unwrap();
+    assert_eq!(0x80, hash[2] & 0x80);
+    assert!(Sharp::new_from_thumbhash(&hash).is_ok());
+
+    //Invalid hash
+    assert!(Sharp::new_from_thumbhash(&[1, 2, 3]).is_err());
+}
@@ -0,0 +1,28 @@
+use sharp::{output::Progress, Sharp};
+use std::sync::{Arc, Mutex};
+mod fixtures;
+
+#[test]
+pub fn progress() {
+    //Progress is reported while writing
+    let updates: Arc<Mutex<Vec<Progress>>> = Arc::new(Mutex::new(Vec::new()));
+    let collected = updates.clone();
+    Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .on_progress(move |p| collected.lock().unwrap().push(p))
+        .png(None)
+        .unwrap()
+        .to_buffer()
+        .unwrap();
+
+    let updates = updates.lock().unwrap();
+    assert!(!updates.is_empty());
+    let mut percent = 0;
+    for update in updates.iter() {
+        assert!(update.percent >= percent);
+        assert!(update.percent <= 100);
+        assert!(update.pixels <= update.total_pixels);
+        percent = update.percent;
+    }
+    assert_eq!(2725 * 2225, updates.last().unwrap().total_pixels);
+}
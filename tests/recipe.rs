@@ -0,0 +1,74 @@
+use sharp::{recipe::Recipe, resize::ResizeOptions, Sharp, SharpError};
+mod fixtures;
+
+#[test]
+pub fn apply_recipe() {
+    //Apply operations from JSON
+    let recipe: Recipe = serde_json::from_str(
+        r#"{
+            "operations": [
+                { "op": "resize", "options": { "width": 320, "height": 240, "fit": "fill", "kernel": "nearest" } },
+                { "op": "sharpen", "options": { "sigma": 1.0 } },
+                { "op": "webp", "options": { "quality": 80 } }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let (data, info) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .apply_recipe(&recipe)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert!(!data.is_empty());
+    assert_eq!("webp", info.format);
+    assert_eq!(320, info.width);
+    assert_eq!(240, info.height);
+}
+
+#[test]
+pub fn to_recipe() {
+    //Round trip through JSON
+    let pipeline = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize_with_opts(ResizeOptions {
+            width: Some(320),
+            ..Default::default()
+        })
+        .unwrap()
+        .greyscale(true)
+        .png(None)
+        .unwrap();
+
+    let json = serde_json::to_string(&pipeline.to_recipe()).unwrap();
+    let recipe: Recipe = serde_json::from_str(&json).unwrap();
+    assert_eq!(3, recipe.operations.len());
+    assert_eq!(json, serde_json::to_string(&recipe).unwrap());
+
+    let (_, expected) = pipeline.to_buffer_with_info().unwrap();
+    let (_, actual) = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .apply_recipe(&recipe)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    assert_eq!(expected.format, actual.format);
+    assert_eq!(expected.width, actual.width);
+    assert_eq!(expected.height, actual.height);
+    assert_eq!(1, actual.channels);
+}
+
+#[test]
+pub fn invalid_recipe() {
+    //Operations are validated by the builder methods
+    let recipe: Recipe = serde_json::from_str(
+        r#"{ "operations": [ { "op": "blur", "options": { "sigma": 2000.0 } } ] }"#,
+    )
+    .unwrap();
+    let result = Sharp::new_from_file(fixtures::inputJpg()).unwrap().apply_recipe(&recipe);
+    assert!(matches!(result, Err(SharpError::InvalidParameter { .. })));
+
+    //Unknown operations are rejected
+    assert!(serde_json::from_str::<Recipe>(r#"{ "operations": [ { "op": "unknown" } ] }"#).is_err());
+}
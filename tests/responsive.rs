@@ -0,0 +1,90 @@
+mod fixtures;
+use sharp::{output::FormatEnum, responsive::ResponsiveOptions, Sharp};
+
+#[test]
+pub fn responsive_set() {
+    //Every width in every format, skipping widths larger than the input
+    let set = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .responsive_set(&[640, 320, 4000], &[FormatEnum::Webp, FormatEnum::Jpeg], None)
+        .unwrap();
+    assert_eq!(4, set.renditions.len());
+    let summary: Vec<(String, i32)> =
+        set.renditions.iter().map(|r| (r.format.clone(), r.width)).collect();
+    assert_eq!(
+        vec![
+            ("webp".to_string(), 320),
+            ("webp".to_string(), 640),
+            ("jpeg".to_string(), 320),
+            ("jpeg".to_string(), 640)
+        ],
+        summary
+    );
+    for rendition in set.renditions.iter() {
+        assert!(!rendition.data.is_empty());
+        assert_eq!(rendition.width, rendition.info.width);
+    }
+    assert_eq!("image/webp", set.renditions[0].mime_type);
+    assert_eq!(261, set.renditions[0].height);
+
+    //Markup
+    let url = |r: &sharp::responsive::Rendition| format!("/img/{}.{}", r.width, r.format);
+    assert_eq!("/img/320.webp 320w, /img/640.webp 640w", set.srcset("webp", url));
+    let html = set.picture(url, "(max-width: 640px) 100vw, 640px", "A \"quoted\" alt");
+    assert!(html.starts_with("<picture>"));
+    assert!(html
+        .contains(r#"<source type="image/webp" srcset="/img/320.webp 320w, /img/640.webp 640w""#));
+    assert!(html.contains(r#"<img src="/img/640.jpeg""#));
+    assert!(html.contains("alt=\"A &quot;quoted&quot; alt\""));
+
+    //JSON without image data
+    let json = serde_json::to_value(&set).unwrap();
+    assert_eq!(320, json["renditions"][0]["width"]);
+    assert!(json["renditions"][0].get("data").is_none());
+}
+
+#[test]
+pub fn responsive_set_enlargement() {
+    //Input width is used when every width is larger
+    let set = Sharp::new_from_file(fixtures::inputJpg320x240())
+        .unwrap()
+        .responsive_set(&[640, 1280], &[FormatEnum::Png], None)
+        .unwrap();
+    assert_eq!(1, set.renditions.len());
+    assert_eq!(320, set.renditions[0].width);
+
+    //Widths are compared with the processed image
+    let set = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(500, 500)
+        .unwrap()
+        .responsive_set(&[320, 640], &[FormatEnum::Png], None)
+        .unwrap();
+    assert_eq!(1, set.renditions.len());
+    assert_eq!(320, set.renditions[0].width);
+
+    //Enlargement when allowed
+    let set = Sharp::new_from_file(fixtures::inputJpg320x240())
+        .unwrap()
+        .responsive_set(
+            &[640],
+            &[FormatEnum::Png],
+            Some(ResponsiveOptions {
+                without_enlargement: Some(false),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    assert_eq!(640, set.renditions[0].width);
+    assert_eq!(480, set.renditions[0].height);
+
+    //Invalid arguments
+    assert!(Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .responsive_set(&[], &[FormatEnum::Png], None)
+        .is_err());
+    assert!(Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .responsive_set(&[100], &[], None)
+        .is_err());
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "server")]
+mod fixtures;
+use sharp::{
+    server::{ImageServer, ServerOptions},
+    Sharp,
+};
+
+fn server() -> ImageServer {
+    ImageServer::new(
+        fixtures::path(""),
+        ServerOptions {
+            key: Some(b"secret".to_vec()),
+            salt: Some(b"salt".to_vec()),
+            timeout: Some(30),
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+pub fn server_resize() {
+    //Signed URL is resized and converted
+    let server = server();
+    let url = server.sign("/rs:fill:300:200/q:80/f:webp/2569067123_aca715a2ee_o.jpg");
+    let response = server.handle(&url);
+    assert_eq!(200, response.status);
+    assert_eq!("image/webp", response.content_type);
+
+    let metadata = Sharp::new_from_buffer(response.body).unwrap().metadata().unwrap();
+    assert_eq!("webp", metadata.format);
+    assert_eq!(300, metadata.width);
+    assert_eq!(200, metadata.height);
+
+    //Input format is kept when no format is given
+    let response = server.handle(&server.sign("/w:100/2569067123_aca715a2ee_o.jpg?v=1"));
+    assert_eq!(200, response.status);
+    assert_eq!("image/jpeg", response.content_type);
+    let metadata = Sharp::new_from_buffer(response.body).unwrap().metadata().unwrap();
+    assert_eq!(100, metadata.width);
+}
+
+#[test]
+pub fn server_errors() {
+    let server = server();
+
+    //Invalid or missing signature
+    assert_eq!(403, server.handle("/unsafe/rs:fill:300:200/2569067123_aca715a2ee_o.jpg").status);
+    let url = server.sign("/rs:fill:300:200/2569067123_aca715a2ee_o.jpg");
+    assert_eq!(403, server.handle(&url.replace("300", "301")).status);
+
+    //Paths outside the root directory
+    assert_eq!(400, server.handle(&server.sign("/rs:fit:10:10/../Cargo.toml")).status);
+
+    //Missing file and unknown option
+    assert_eq!(404, server.handle(&server.sign("/rs:fit:10:10/missing.jpg")).status);
+    assert_eq!(400, server.handle(&server.sign("/xx:1/2569067123_aca715a2ee_o.jpg")).status);
+
+    //Input pixel limit
+    let server = ImageServer::new(
+        fixtures::path(""),
+        ServerOptions {
+            limit_input_pixels: Some(100),
+            ..Default::default()
+        },
+    );
+    assert_eq!(413, server.handle("/unsafe/w:10/2569067123_aca715a2ee_o.jpg").status);
+
+    //Signature segment is optional without a key
+    let server = ImageServer::new(fixtures::path(""), ServerOptions::default());
+    assert_eq!(200, server.handle("/w:10/2569067123_aca715a2ee_o.jpg").status);
+}
@@ -0,0 +1,96 @@
+mod fixtures;
+use sharp::{
+    resize::{ExtendOptions, Region},
+    stats::StatsOptions,
+    Colour, Interpretation, Sharp,
+};
+
+#[test]
+pub fn to_stats() {
+    //Processed output
+    let stats = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .to_stats(None)
+        .unwrap();
+    assert_eq!(3, stats.channel_stats.len());
+    assert!(stats.is_opaque);
+    let red = &stats.channel_stats[0];
+    assert!((red.mean * 320.0 * 240.0 - red.sum).abs() < 1.0);
+    assert!(stats.entropy > 0.0);
+
+    //Output colourspace
+    let stats = fixtures::solid(10, 10, 4, Colour::new(255, 0, 0, 1.0))
+        .to_colourspace(Interpretation::BW)
+        .to_stats(None)
+        .unwrap();
+    assert_eq!(2, stats.channel_stats.len());
+    assert!(stats.channel_stats[0].max < 255);
+
+    //Region
+    let stats = fixtures::solid(10, 10, 4, Colour::new(255, 0, 0, 1.0))
+        .extend(ExtendOptions {
+            right: Some(10),
+            background: Some(Colour::rgb(0, 0, 255)),
+            ..Default::default()
+        })
+        .unwrap()
+        .to_stats(Some(StatsOptions {
+            region: Some(Region {
+                left: 0,
+                top: 0,
+                width: 10,
+                height: 10,
+            }),
+            ..Default::default()
+        }))
+        .unwrap();
+    assert_eq!(255, stats.channel_stats[0].min);
+    assert_eq!(0, stats.channel_stats[2].max);
+    assert_eq!(248.0, stats.dominant_red);
+}
+
+#[test]
+pub fn to_stats_alpha_mask() {
+    let transparent = ExtendOptions {
+        top: Some(10),
+        left: Some(10),
+        background: Some(Colour::new(0, 0, 255, 0.0)),
+        ..Default::default()
+    };
+
+    //Transparent pixels are counted by default
+    let stats = fixtures::solid(10, 10, 4, Colour::new(255, 0, 0, 1.0))
+        .extend(transparent.clone())
+        .unwrap()
+        .to_stats(None)
+        .unwrap();
+    assert!(!stats.is_opaque);
+    assert_eq!(0, stats.channel_stats[0].min);
+
+    //Transparent pixels are ignored within the mask
+    let stats = fixtures::solid(10, 10, 4, Colour::new(255, 0, 0, 1.0))
+        .extend(transparent)
+        .unwrap()
+        .to_stats(Some(StatsOptions {
+            alpha_mask: Some(true),
+            ..Default::default()
+        }))
+        .unwrap();
+    assert!(stats.is_opaque);
+    assert_eq!(255, stats.channel_stats[0].min);
+    assert_eq!(10, stats.channel_stats[0].min_x);
+    assert_eq!(255.0 * 100.0, stats.channel_stats[0].sum);
+    assert_eq!(0.0, stats.channel_stats[0].stdev);
+    assert_eq!(248.0, stats.dominant_red);
+    assert_eq!(8.0, stats.dominant_blue);
+
+    //Nothing to measure
+    assert!(fixtures::solid(10, 10, 4, Colour::new(0, 0, 0, 0.0))
+        .to_stats(Some(StatsOptions {
+            alpha_mask: Some(true),
+            ..Default::default()
+        }))
+        .is_err());
+}
@@ -0,0 +1,53 @@
+mod fixtures;
+use sharp::{input::Inputs, Sharp, SharpError};
+use std::fs::File;
+
+#[test]
+pub fn stream() {
+    //Read from a stream and write to a stream
+    let reader = File::open(fixtures::inputJpg()).unwrap();
+    let mut output = Vec::new();
+    let info = Sharp::new(Inputs::new().reader(reader))
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .webp(None)
+        .unwrap()
+        .to_writer(&mut output)
+        .unwrap();
+    assert_eq!("webp", info.format);
+    assert_eq!(320, info.width);
+    assert_eq!(240, info.height);
+    let metadata = Sharp::new_from_buffer(output).unwrap().metadata().unwrap();
+    assert_eq!(320, metadata.width);
+    assert_eq!(240, metadata.height);
+
+    //Read once, later operations report an error
+    let reader = File::open(fixtures::inputJpg()).unwrap();
+    let image = Sharp::new(Inputs::new().reader(reader)).unwrap();
+    let metadata = image.metadata().unwrap();
+    assert_eq!(2725, metadata.width);
+    assert!(matches!(image.to_buffer(), Err(SharpError::Io(_))));
+
+    //Raw output to a stream
+    let mut output = Vec::new();
+    let info = Sharp::new_from_file(fixtures::inputJpg())
+        .unwrap()
+        .resize(32, 24)
+        .unwrap()
+        .raw(None)
+        .unwrap()
+        .to_writer(&mut output)
+        .unwrap();
+    assert_eq!(32 * 24 * info.channels as usize, output.len());
+
+    //Unsupported stream contents
+    let reader = std::io::Cursor::new(vec![0_u8; 64]);
+    let result = Sharp::new(Inputs::new().reader(reader)).unwrap().to_buffer();
+    assert!(result.is_err());
+
+    //Streaming support is reported
+    let formats = Sharp::available_formats();
+    assert!(formats["jpeg"].input.stream);
+    assert!(formats["jpeg"].output.stream);
+}
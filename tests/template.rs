@@ -0,0 +1,64 @@
+mod fixtures;
+use sharp::{input::Input, resize::ResizeOptions, template::SharpTemplate, Sharp};
+
+#[test]
+pub fn template() {
+    //Same operations applied to several inputs
+    let template =
+        SharpTemplate::new(|sharp| sharp.resize(320, 240)?.greyscale(true).webp(None)).unwrap();
+
+    let (data, info) =
+        template.apply(Input::path(fixtures::inputJpg())).unwrap().to_buffer_with_info().unwrap();
+    assert!(!data.is_empty());
+    assert_eq!("webp", info.format);
+    assert_eq!(320, info.width);
+    assert_eq!(240, info.height);
+
+    let (data, info) =
+        template.apply(Input::path(fixtures::inputPng())).unwrap().to_buffer_with_info().unwrap();
+    assert!(!data.is_empty());
+    assert_eq!("webp", info.format);
+    assert_eq!(320, info.width);
+    assert_eq!(240, info.height);
+
+    //Same result as building the pipeline directly
+    let buffer = std::fs::read(fixtures::inputJpg()).unwrap();
+    let (expected, _) = Sharp::new_from_buffer(buffer.clone())
+        .unwrap()
+        .resize(320, 240)
+        .unwrap()
+        .greyscale(true)
+        .webp(None)
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap();
+    let (actual, _) = template.apply(Input::buffer(buffer)).unwrap().to_buffer_with_info().unwrap();
+    assert_eq!(expected, actual);
+
+    //Input settings of the template are kept
+    let template = SharpTemplate::new(|sharp| {
+        sharp.auto_orient()?.resize_with_opts(ResizeOptions {
+            width: Some(320),
+            ..Default::default()
+        })
+    })
+    .unwrap();
+    let info = template
+        .apply(Input::path(fixtures::inputJpgWithExif()))
+        .unwrap()
+        .to_buffer_with_info()
+        .unwrap()
+        .1;
+    assert_eq!(320, info.width);
+    assert_eq!(240, info.height);
+}
+
+#[test]
+pub fn template_invalid() {
+    //Builder methods still validate their arguments
+    assert!(SharpTemplate::new(|sharp| sharp.resize(-1, 240)).is_err());
+
+    //Input is validated when applied
+    let template = SharpTemplate::new(|sharp| sharp.png(None)).unwrap();
+    assert!(template.apply(Input::buffer(Vec::new())).is_err());
+}